@@ -0,0 +1,242 @@
+use image::Rgb;
+use num::{Float, FromPrimitive};
+
+use crate::colour;
+use crate::light::directionlight::DirectionLight;
+use crate::vector::Vec4;
+
+/// What rays that escape the scene see
+pub enum Background<T: Float> {
+    Solid([T; 3]),
+    /// Blends from `bottom` looking straight down to `top` looking straight up
+    Gradient {
+        bottom: [T; 3],
+        top: [T; 3],
+    },
+    Sky(Sky<T>),
+}
+
+impl<T> Background<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn solid(colour: Rgb<u8>) -> Background<T> {
+        Background::Solid(colour::from_rgb(colour))
+    }
+
+    pub fn gradient(bottom: Rgb<u8>, top: Rgb<u8>) -> Background<T> {
+        Background::Gradient {
+            bottom: colour::from_rgb(bottom),
+            top: colour::from_rgb(top),
+        }
+    }
+
+    /// A clear daylight sky lit by the sun shining along `sun`'s direction.
+    /// `turbidity` is the haziness of the atmosphere: 2 is very clear, 10 is hazy.
+    pub fn sky(sun: &DirectionLight<T>, turbidity: T) -> Background<T> {
+        Background::Sky(Sky::new(&sun.direction().reverse(), turbidity))
+    }
+
+    pub fn colour(&self, direction: &Vec4<T>) -> [T; 3] {
+        match self {
+            Background::Solid(colour) => *colour,
+            Background::Gradient { bottom, top } => {
+                let half: T = FromPrimitive::from_f64(0.5).unwrap();
+                let t = (direction.normalized().y + T::one()) * half;
+                [
+                    bottom[0] + (top[0] - bottom[0]) * t,
+                    bottom[1] + (top[1] - bottom[1]) * t,
+                    bottom[2] + (top[2] - bottom[2]) * t,
+                ]
+            }
+            Background::Sky(sky) => sky.colour(direction),
+        }
+    }
+}
+
+impl<T> Default for Background<T>
+where
+    T: Float,
+{
+    fn default() -> Self {
+        Background::Solid([T::zero(); 3])
+    }
+}
+
+/// Perez distribution coefficients (A to E) for one channel of the sky
+type Perez<T> = [T; 5];
+
+/// The Preetham et al. analytic daylight model ("A Practical Analytic Model for
+/// Daylight", 1999). Everything depending only on the sun and turbidity is worked
+/// out up front; each lookup is then just the Perez function and a colour space
+/// conversion.
+pub struct Sky<T: Float> {
+    to_sun: Vec4<T>,
+    perez: [Perez<T>; 3],
+    /// Zenith luminance and chromaticity (Y, x, y), already divided through by the
+    /// Perez function at the zenith
+    zenith: [T; 3],
+}
+
+/// Brings the model's luminance (in kcd/m²) into roughly [0, 1] for display
+const SKY_EXPOSURE: f64 = 0.05;
+
+impl<T> Sky<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn new(to_sun: &Vec4<T>, turbidity: T) -> Sky<T> {
+        let f = |x: f64| -> T { FromPrimitive::from_f64(x).unwrap() };
+        let t = turbidity;
+
+        let to_sun = to_sun.normalized();
+
+        // The model isn't defined with the sun below the horizon
+        let theta_s = T::acos(T::max(T::zero(), T::min(T::one(), to_sun.y)));
+
+        let perez = [
+            [
+                f(0.1787) * t - f(1.4630),
+                f(-0.3554) * t + f(0.4275),
+                f(-0.0227) * t + f(5.3251),
+                f(0.1206) * t - f(2.5771),
+                f(-0.0670) * t + f(0.3703),
+            ],
+            [
+                f(-0.0193) * t - f(0.2592),
+                f(-0.0665) * t + f(0.0008),
+                f(-0.0004) * t + f(0.2125),
+                f(-0.0641) * t - f(0.8989),
+                f(-0.0033) * t + f(0.0452),
+            ],
+            [
+                f(-0.0167) * t - f(0.2608),
+                f(-0.0950) * t + f(0.0092),
+                f(-0.0079) * t + f(0.2102),
+                f(-0.0441) * t - f(1.6537),
+                f(-0.0109) * t + f(0.0529),
+            ],
+        ];
+
+        let chi = (f(4.0 / 9.0) - t / f(120.0)) * (f(std::f64::consts::PI) - f(2.0) * theta_s);
+        let zenith_luminance =
+            (f(4.0453) * t - f(4.9710)) * T::tan(chi) - f(0.2155) * t + f(2.4192);
+
+        let poly = |c: [f64; 4]| {
+            f(c[0]) * theta_s.powi(3) + f(c[1]) * theta_s.powi(2) + f(c[2]) * theta_s + f(c[3])
+        };
+        let t2 = t * t;
+        let zenith_x = t2 * poly([0.00166, -0.00375, 0.00209, 0.0])
+            + t * poly([-0.02903, 0.06377, -0.03202, 0.00394])
+            + poly([0.11693, -0.21196, 0.06052, 0.25886]);
+        let zenith_y = t2 * poly([0.00275, -0.00610, 0.00317, 0.0])
+            + t * poly([-0.04214, 0.08970, -0.04153, 0.00516])
+            + poly([0.15346, -0.26756, 0.06670, 0.26688]);
+
+        let zenith = [zenith_luminance, zenith_x, zenith_y];
+        let mut normalised = [T::zero(); 3];
+        for i in 0..3 {
+            normalised[i] = zenith[i] / Self::perez_function(&perez[i], T::zero(), theta_s);
+        }
+
+        Sky {
+            to_sun,
+            perez,
+            zenith: normalised,
+        }
+    }
+
+    /// F(theta, gamma): theta is the angle from the zenith, gamma from the sun
+    fn perez_function(p: &Perez<T>, theta: T, gamma: T) -> T {
+        let cos_gamma = T::cos(gamma);
+        (T::one() + p[0] * T::exp(p[1] / T::cos(theta)))
+            * (T::one() + p[2] * T::exp(p[3] * gamma) + p[4] * cos_gamma * cos_gamma)
+    }
+
+    pub fn colour(&self, direction: &Vec4<T>) -> [T; 3] {
+        let f = |x: f64| -> T { FromPrimitive::from_f64(x).unwrap() };
+
+        let d = direction.normalized();
+
+        // Below the horizon just carries on the colour at the horizon
+        let cos_theta = T::max(f(0.001), d.y);
+        let theta = T::acos(cos_theta);
+        let cos_gamma = T::max(-T::one(), T::min(T::one(), d.dot_product(&self.to_sun)));
+        let gamma = T::acos(cos_gamma);
+
+        let mut yxy = self.zenith;
+        for (channel, perez) in yxy.iter_mut().zip(self.perez.iter()) {
+            *channel = *channel * Self::perez_function(perez, theta, gamma);
+        }
+
+        // Yxy -> XYZ -> linear sRGB
+        let big_y = yxy[0] * f(SKY_EXPOSURE);
+        let (x, y) = (yxy[1], yxy[2]);
+        let big_x = x / y * big_y;
+        let big_z = (T::one() - x - y) / y * big_y;
+
+        [
+            f(3.2406) * big_x - f(1.5372) * big_y - f(0.4986) * big_z,
+            f(-0.9689) * big_x + f(1.8758) * big_y + f(0.0415) * big_z,
+            f(0.0557) * big_x - f(0.2040) * big_y + f(1.0570) * big_z,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solid() {
+        let b: Background<f64> = Background::solid(Rgb([255, 0, 255]));
+        assert_eq!([1.0, 0.0, 1.0], b.colour(&Vec4::direction(0.3, 0.2, 0.1)));
+    }
+
+    #[test]
+    fn default_is_black() {
+        let b: Background<f64> = Default::default();
+        assert_eq!([0.0, 0.0, 0.0], b.colour(&Vec4::direction(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn gradient() {
+        let b: Background<f64> = Background::gradient(Rgb([0, 0, 0]), Rgb([255, 255, 255]));
+        assert_eq!([0.0, 0.0, 0.0], b.colour(&Vec4::direction(0.0, -1.0, 0.0)));
+        assert_eq!([1.0, 1.0, 1.0], b.colour(&Vec4::direction(0.0, 1.0, 0.0)));
+        assert_eq!([0.5, 0.5, 0.5], b.colour(&Vec4::direction(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn sky_is_brightest_near_the_sun() {
+        let sun = DirectionLight::new(Vec4::direction(0.0, -1.0, -1.0));
+        let b: Background<f64> = Background::sky(&sun, 3.0);
+
+        let towards = colour::luminance(&b.colour(&Vec4::direction(0.0, 1.0, 1.1)));
+        let away = colour::luminance(&b.colour(&Vec4::direction(0.0, 1.0, -1.1)));
+        assert!(towards > away);
+    }
+
+    #[test]
+    fn sky_is_blue() {
+        let sun = DirectionLight::new(Vec4::direction(0.0, -1.0, -1.0));
+        let b: Background<f64> = Background::sky(&sun, 3.0);
+
+        let zenith = b.colour(&Vec4::direction(0.0, 1.0, 0.0));
+        assert!(zenith.iter().all(|c| *c > 0.0));
+        assert!(zenith[2] > zenith[0]);
+    }
+
+    #[test]
+    fn sky_dims_at_sunset() {
+        let noon = DirectionLight::new(Vec4::direction(0.0, -1.0, -0.2));
+        let dusk = DirectionLight::new(Vec4::direction(0.0, -0.05, -1.0));
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+
+        let noon_sky: Background<f64> = Background::sky(&noon, 3.0);
+        let dusk_sky: Background<f64> = Background::sky(&dusk, 3.0);
+        assert!(
+            colour::luminance(&noon_sky.colour(&up)) > colour::luminance(&dusk_sky.colour(&up))
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use num::{Float, FromPrimitive};
+
+use super::microfacet::Ggx;
+use super::*;
+
+/// A metal, reflecting off microfacets with GGX roughness. Its colour comes
+/// from its complex index of refraction, `eta` + i`k`, given for red, green
+/// and blue light. Both sides of the surface look the same.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Conductor<T: Float> {
+    eta: [T; 3],
+    k: [T; 3],
+    distribution: Ggx<T>,
+}
+
+impl<T> Conductor<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn new(eta: [T; 3], k: [T; 3], roughness: T) -> Conductor<T> {
+        Conductor {
+            eta,
+            k,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    fn preset(eta: [f64; 3], k: [f64; 3], roughness: T) -> Conductor<T> {
+        let convert = |v: [f64; 3]| v.map(|c| FromPrimitive::from_f64(c).unwrap());
+        Conductor::new(convert(eta), convert(k), roughness)
+    }
+
+    pub fn gold(roughness: T) -> Conductor<T> {
+        Conductor::preset([0.143, 0.375, 1.442], [3.983, 2.386, 1.603], roughness)
+    }
+
+    pub fn copper(roughness: T) -> Conductor<T> {
+        Conductor::preset([0.200, 0.924, 1.102], [3.913, 2.453, 2.142], roughness)
+    }
+
+    pub fn aluminium(roughness: T) -> Conductor<T> {
+        Conductor::preset([1.657, 0.880, 0.521], [9.224, 6.270, 4.837], roughness)
+    }
+
+    fn fresnel(&self, cos_i: T) -> [T; 3] {
+        [0, 1, 2].map(|c| fresnel_conductor(cos_i, self.eta[c], self.k[c]))
+    }
+}
+
+/// Both directions moved above the surface, for surfaces that look the same
+/// from either side
+pub(crate) fn upper<T: Float>(wo: &Vec4<T>, wi: &Vec4<T>) -> (Vec4<T>, Vec4<T>) {
+    if wo.z < T::zero() {
+        let flip = |w: &Vec4<T>| Vec4::direction(w.x, w.y, -w.z);
+        (flip(wo), flip(wi))
+    } else {
+        (*wo, *wi)
+    }
+}
+
+impl<T> Bsdf<T> for Conductor<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn evaluate(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> [T; 3] {
+        let (wo, wi) = upper(wo, wi);
+        if wo.z <= T::zero() || wi.z <= T::zero() {
+            return [T::zero(); 3];
+        }
+        let wm = (&wo + &wi).normalized();
+
+        let four: T = FromPrimitive::from_f64(4.0).unwrap();
+        let scale = self.distribution.d(&wm) * self.distribution.g(&wo, &wi) / (four * wo.z * wi.z);
+        self.fresnel(wo.dot_product(&wm)).map(|f| f * scale)
+    }
+
+    fn sample(&self, wo: &Vec4<T>, _: T, u: (T, T)) -> Option<BsdfSample<T>> {
+        let flipped = wo.z < T::zero();
+        let (up, _) = upper(wo, wo);
+        if up.z == T::zero() {
+            return None;
+        }
+
+        let wm = self.distribution.sample_normal(&up, u);
+        let wi = reflect(&up, &wm);
+        if wi.z <= T::zero() {
+            return None;
+        }
+        let wi = if flipped {
+            Vec4::direction(wi.x, wi.y, -wi.z)
+        } else {
+            wi
+        };
+
+        Some(BsdfSample {
+            wi,
+            value: self.evaluate(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+        })
+    }
+
+    fn pdf(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> T {
+        let (wo, wi) = upper(wo, wi);
+        if wo.z <= T::zero() || wi.z <= T::zero() {
+            return T::zero();
+        }
+        let wm = (&wo + &wi).normalized();
+
+        let four: T = FromPrimitive::from_f64(4.0).unwrap();
+        self.distribution.visible_d(&wo, &wm) / (four * wo.dot_product(&wm).abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsdf::tests::{check_sampling, pdf_integral};
+
+    #[test]
+    fn metals() {
+        let straight_on = |c: &Conductor<f64>| c.fresnel(1.0);
+
+        // Gold and copper are warm, aluminium a bright, nearly neutral grey
+        let gold = straight_on(&Conductor::gold(0.0));
+        assert!(gold[0] > 0.9 && gold[2] < 0.5, "{:?}", gold);
+        let copper = straight_on(&Conductor::copper(0.0));
+        assert!(
+            copper[0] > copper[1] && copper[1] > copper[2],
+            "{:?}",
+            copper
+        );
+        let aluminium = straight_on(&Conductor::aluminium(0.0));
+        assert!(aluminium.iter().all(|c| *c > 0.9), "{:?}", aluminium);
+    }
+
+    #[test]
+    fn rough_reflection() {
+        let gold = Conductor::gold(0.5);
+        let wo = Vec4::direction(0.6, 0.0, 0.8);
+
+        // Some light is lost to shadowing between the microfacets, but none is
+        // made
+        let reflected = check_sampling(&gold, &wo);
+        let fresnel = gold.fresnel(1.0);
+        for c in 0..3 {
+            assert!(reflected[c] <= 1.0);
+            assert!(reflected[c] > 0.8 * fresnel[c], "{:?}", reflected);
+        }
+        // Sampled reflections that would go below the surface are dropped, so a
+        // little is missing
+        let covered = pdf_integral(&gold, &wo);
+        assert!(covered > 0.9 && covered < 1.02, "{}", covered);
+
+        // The same from underneath
+        let below = check_sampling(&gold, &wo.reverse());
+        assert!((below[0] - reflected[0]).abs() < 0.05);
+    }
+
+    #[test]
+    fn nearly_a_mirror() {
+        let mirror = Conductor::aluminium(0.0);
+        let wo = Vec4::direction(0.6, 0.0, 0.8);
+        let s = mirror.sample(&wo, 0.5, (0.3, 0.7)).unwrap();
+        assert!((&s.wi - &Vec4::direction(-0.6, 0.0, 0.8)).mag() < 0.01);
+    }
+}
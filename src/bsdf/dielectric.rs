@@ -0,0 +1,185 @@
+use num::{Float, FromPrimitive};
+
+use super::microfacet::Ggx;
+use super::*;
+
+/// Glass, water and the like: light is partly reflected and partly refracted
+/// through the surface, in the proportions the Fresnel equations give, off
+/// microfacets with GGX roughness. The outward normal points out of the
+/// material.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Dielectric<T: Float> {
+    eta: T,
+    distribution: Ggx<T>,
+}
+
+impl<T> Dielectric<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// `eta` is the material's index of refraction, relative to what's outside
+    pub fn new(eta: T, roughness: T) -> Dielectric<T> {
+        Dielectric {
+            eta,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    pub fn glass(roughness: T) -> Dielectric<T> {
+        Dielectric::new(FromPrimitive::from_f64(1.5).unwrap(), roughness)
+    }
+
+    /// The microfacet that would scatter `wo` into `wi`, facing out, and the
+    /// ratio of indices crossed on the way. None if no microfacet can.
+    fn half_vector(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> Option<(Vec4<T>, T)> {
+        let (cos_o, cos_i) = (wo.z, wi.z);
+        if cos_o == T::zero() || cos_i == T::zero() {
+            return None;
+        }
+
+        let eta = if same_hemisphere(wo, wi) {
+            T::one()
+        } else if cos_o > T::zero() {
+            self.eta
+        } else {
+            T::one() / self.eta
+        };
+
+        let wm = &(wi * eta) + wo;
+        if wm.dot_product(&wm) == T::zero() {
+            return None;
+        }
+        let mut wm = wm.normalized();
+        if wm.z < T::zero() {
+            wm = wm.reverse();
+        }
+
+        // Microfacets seen from behind don't count
+        if wm.dot_product(wi) * cos_i < T::zero() || wm.dot_product(wo) * cos_o < T::zero() {
+            return None;
+        }
+        Some((wm, eta))
+    }
+
+    /// The BSDF and pdf together, since they share most of their working
+    fn scatter(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> ([T; 3], T) {
+        let none = ([T::zero(); 3], T::zero());
+        let (wm, eta) = match self.half_vector(wo, wi) {
+            Some(h) => h,
+            None => return none,
+        };
+
+        let four: T = FromPrimitive::from_f64(4.0).unwrap();
+        let reflectance = fresnel_dielectric(wo.dot_product(&wm), self.eta);
+        let transmittance = T::one() - reflectance;
+        let d = self.distribution.d(&wm);
+        let g = self.distribution.g(wo, wi);
+        let visible = self.distribution.visible_d(wo, &wm);
+
+        if same_hemisphere(wo, wi) {
+            let f = d * g * reflectance / (four * wi.z * wo.z).abs();
+            let pdf = visible / (four * wo.dot_product(&wm).abs()) * reflectance;
+            ([f; 3], pdf)
+        } else {
+            let denominator = wi.dot_product(&wm) + wo.dot_product(&wm) / eta;
+            let denominator = denominator * denominator;
+            // Light is squeezed into a narrower cone going into the denser
+            // material, so it gets brighter
+            let f = d
+                * transmittance
+                * g
+                * (wi.dot_product(&wm) * wo.dot_product(&wm) / (wi.z * wo.z * denominator)).abs()
+                / (eta * eta);
+            let pdf = visible * wi.dot_product(&wm).abs() / denominator * transmittance;
+            ([f; 3], pdf)
+        }
+    }
+}
+
+impl<T> Bsdf<T> for Dielectric<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn evaluate(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> [T; 3] {
+        self.scatter(wo, wi).0
+    }
+
+    fn sample(&self, wo: &Vec4<T>, choice: T, u: (T, T)) -> Option<BsdfSample<T>> {
+        if wo.z == T::zero() {
+            return None;
+        }
+
+        let wm = self.distribution.sample_normal(wo, u);
+        let reflectance = fresnel_dielectric(wo.dot_product(&wm), self.eta);
+
+        let wi = if choice < reflectance {
+            let wi = reflect(wo, &wm);
+            if !same_hemisphere(wo, &wi) {
+                return None;
+            }
+            wi
+        } else {
+            let (wi, _) = refract(wo, &wm, self.eta)?;
+            if same_hemisphere(wo, &wi) || wi.z == T::zero() {
+                return None;
+            }
+            wi
+        };
+
+        let (value, pdf) = self.scatter(wo, &wi);
+        if pdf <= T::zero() {
+            return None;
+        }
+        Some(BsdfSample { wi, value, pdf })
+    }
+
+    fn pdf(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> T {
+        self.scatter(wo, wi).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsdf::tests::{check_sampling, pdf_integral};
+
+    #[test]
+    fn mostly_goes_through() {
+        let glass = Dielectric::glass(0.05);
+        let wo = Vec4::direction(0.0, 0.0, 1.0);
+
+        let mut reflected = 0;
+        for i in 0..1000 {
+            let choice = i as f64 / 1000.0;
+            let s = glass.sample(&wo, choice, (0.5, 0.5)).unwrap();
+            if s.wi.z > 0.0 {
+                reflected += 1;
+            }
+        }
+        // About 4% straight on
+        assert!((30..50).contains(&reflected), "{}", reflected);
+    }
+
+    #[test]
+    fn rough_glass() {
+        let glass = Dielectric::glass(0.4);
+        let wo = Vec4::direction(0.6, 0.0, 0.8);
+        check_sampling(&glass, &wo);
+        assert!((pdf_integral(&glass, &wo) - 1.0).abs() < 0.05);
+
+        // From inside too, where some is trapped by total internal reflection
+        let inside = Vec4::direction(0.3, 0.0, -0.95).normalized();
+        check_sampling(&glass, &inside);
+        assert!((pdf_integral(&glass, &inside) - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn refracts_towards_the_normal() {
+        let glass = Dielectric::glass(0.0);
+        let wo = Vec4::direction(0.6, 0.0, 0.8);
+        // Well past the 4-ish percent that's reflected
+        let s = glass.sample(&wo, 0.9, (0.3, 0.3)).unwrap();
+        assert!(s.wi.z < 0.0);
+        assert!((s.wi.x + 0.4).abs() < 0.01, "{:?}", s.wi);
+    }
+}
@@ -0,0 +1,81 @@
+use image::Rgb;
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::colour;
+use crate::random::cosine_hemisphere;
+
+/// A perfectly matte surface, which looks equally bright from every direction
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Lambert<T: Float> {
+    reflectance: [T; 3],
+}
+
+impl<T> Lambert<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn new(colour: Rgb<u8>) -> Lambert<T> {
+        Lambert {
+            reflectance: colour::from_rgb(colour),
+        }
+    }
+}
+
+impl<T> Bsdf<T> for Lambert<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn evaluate(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> [T; 3] {
+        if !same_hemisphere(wo, wi) {
+            return [T::zero(); 3];
+        }
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        self.reflectance.map(|r| r / pi)
+    }
+
+    fn sample(&self, wo: &Vec4<T>, _: T, u: (T, T)) -> Option<BsdfSample<T>> {
+        if wo.z == T::zero() {
+            return None;
+        }
+        let up = Vec4::direction(T::zero(), T::zero(), wo.z.signum());
+        let wi = cosine_hemisphere(&up, u.0, u.1);
+        Some(BsdfSample {
+            wi,
+            value: self.evaluate(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+        })
+    }
+
+    fn pdf(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> T {
+        if !same_hemisphere(wo, wi) {
+            return T::zero();
+        }
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        wi.z.abs() / pi
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsdf::tests::{check_sampling, pdf_integral};
+
+    #[test]
+    fn reflects_its_colour() {
+        let lambert = Lambert::new(Rgb([255, 128, 0]));
+        let wo = Vec4::direction(0.6, 0.0, 0.8);
+
+        let reflected = check_sampling(&lambert, &wo);
+        assert!((reflected[0] - 1.0).abs() < 1e-9);
+        assert!((reflected[1] - 128.0 / 255.0).abs() < 1e-9);
+        assert_eq!(0.0, reflected[2]);
+        assert!((pdf_integral(&lambert, &wo) - 1.0).abs() < 0.02);
+
+        // Nothing gets through
+        let below = Vec4::direction(0.0, 0.0, -1.0);
+        assert_eq!([0.0; 3], lambert.evaluate(&wo, &below));
+        // Lit from inside, it reflects inside
+        assert!(check_sampling(&lambert, &wo.reverse())[0] > 0.99);
+    }
+}
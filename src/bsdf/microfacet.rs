@@ -0,0 +1,153 @@
+use num::{Float, FromPrimitive};
+
+use crate::vector::Vec4;
+
+/// The GGX (Trowbridge-Reitz) distribution of microfacet normals, for a
+/// surface made of tiny mirrors tilted at random. Works in the surface frame,
+/// with the normal along +z.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Ggx<T: Float> {
+    alpha: T,
+}
+
+impl<T> Ggx<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// `roughness` runs from 0 for a mirror to 1 for a very rough surface. It's
+    /// squared to give the width of the distribution, which looks more even to
+    /// the eye, and kept a hair above zero so the maths stays finite.
+    pub fn from_roughness(roughness: T) -> Ggx<T> {
+        let smallest: T = FromPrimitive::from_f64(1e-3).unwrap();
+        let roughness = roughness.max(T::zero()).min(T::one());
+        Ggx {
+            alpha: (roughness * roughness).max(smallest),
+        }
+    }
+
+    /// Density of microfacets facing along `wm`, per unit of solid angle and
+    /// of surface
+    pub fn d(&self, wm: &Vec4<T>) -> T {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let cos2 = wm.z * wm.z;
+        if cos2 <= T::zero() {
+            return T::zero();
+        }
+        let tan2 = (T::one() - cos2) / cos2;
+        let a2 = self.alpha * self.alpha;
+        let e = T::one() + tan2 / a2;
+        T::one() / (pi * a2 * cos2 * cos2 * e * e)
+    }
+
+    /// Smith's auxiliary function: the area of microfacets hidden from `w`, per
+    /// unit of visible area
+    fn lambda(&self, w: &Vec4<T>) -> T {
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let cos2 = w.z * w.z;
+        if cos2 <= T::zero() {
+            return T::infinity();
+        }
+        let tan2 = (T::one() - cos2) / cos2;
+        ((T::one() + self.alpha * self.alpha * tan2).sqrt() - T::one()) / two
+    }
+
+    /// The fraction of microfacets seen from `w`
+    pub fn g1(&self, w: &Vec4<T>) -> T {
+        T::one() / (T::one() + self.lambda(w))
+    }
+
+    /// The fraction of microfacets seen from both `wo` and `wi`
+    pub fn g(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> T {
+        T::one() / (T::one() + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density of microfacets facing along `wm` among those seen from `w`
+    pub fn visible_d(&self, w: &Vec4<T>, wm: &Vec4<T>) -> T {
+        if w.z == T::zero() {
+            return T::zero();
+        }
+        self.g1(w) / w.z.abs() * self.d(wm) * w.dot_product(wm).abs()
+    }
+
+    /// A microfacet normal seen from `w`, picked in proportion to `visible_d`
+    /// (Heitz, "Sampling the GGX Distribution of Visible Normals", 2018)
+    pub fn sample_normal(&self, w: &Vec4<T>, u: (T, T)) -> Vec4<T> {
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let two_pi: T = FromPrimitive::from_f64(std::f64::consts::PI * 2.0).unwrap();
+        let nearly_up: T = FromPrimitive::from_f64(0.99999).unwrap();
+        let tiny: T = FromPrimitive::from_f64(1e-6).unwrap();
+
+        // Stretch the view so the distribution becomes a hemisphere
+        let mut wh = Vec4::direction(self.alpha * w.x, self.alpha * w.y, w.z).normalized();
+        if wh.z < T::zero() {
+            wh = wh.reverse();
+        }
+        let t1 = if wh.z < nearly_up {
+            Vec4::direction(T::zero(), T::zero(), T::one())
+                .cross_product(&wh)
+                .normalized()
+        } else {
+            Vec4::direction(T::one(), T::zero(), T::zero())
+        };
+        let t2 = wh.cross_product(&t1);
+
+        // A point on the disc, squeezed to the part of the hemisphere in view
+        let r = u.0.sqrt();
+        let phi = two_pi * u.1;
+        let (px, py) = (r * phi.cos(), r * phi.sin());
+        let h = (T::one() - px * px).max(T::zero()).sqrt();
+        let s = (T::one() + wh.z) / two;
+        let py = (T::one() - s) * h + s * py;
+        let pz = (T::one() - px * px - py * py).max(T::zero()).sqrt();
+
+        let nh = &(&(&t1 * px) + &(&t2 * py)) + &(&wh * pz);
+        Vec4::direction(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(tiny)).normalized()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    #[test]
+    fn normals_cover_the_surface() {
+        // Projected onto the surface, the microfacets add up to its area
+        let ggx = Ggx::from_roughness(0.6);
+        let mut rng = Rng::new(1);
+        let n = 200000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            // Uniformly over the hemisphere
+            let z: f64 = rng.next_float();
+            let r = (1.0 - z * z).sqrt();
+            let phi = 2.0 * std::f64::consts::PI * rng.next_float::<f64>();
+            let wm = Vec4::direction(r * phi.cos(), r * phi.sin(), z);
+            total += ggx.d(&wm) * z * 2.0 * std::f64::consts::PI;
+        }
+        assert!(
+            (total / n as f64 - 1.0).abs() < 0.02,
+            "{}",
+            total / n as f64
+        );
+    }
+
+    #[test]
+    fn visible_normals() {
+        let ggx = Ggx::from_roughness(0.5);
+        let w = Vec4::direction(0.6, 0.0, 0.8);
+        let mut rng = Rng::new(2);
+        for _ in 0..1000 {
+            let wm = ggx.sample_normal(&w, (rng.next_float(), rng.next_float()));
+            assert!((wm.mag() - 1.0).abs() < 1e-9);
+            assert!(wm.z > 0.0);
+            // Only microfacets facing the viewer can be seen
+            assert!(wm.dot_product(&w) >= -1e-9);
+        }
+
+        // Nothing is hidden looking straight down
+        let up = Vec4::direction(0.0, 0.0, 1.0);
+        assert!((ggx.g1(&up) - 1.0).abs() < 1e-12);
+        assert!(ggx.g1(&w) < 1.0);
+    }
+}
@@ -0,0 +1,291 @@
+use num::complex::Complex;
+use num::{Float, FromPrimitive};
+
+use crate::vector::Vec4;
+
+pub mod conductor;
+pub mod dielectric;
+pub mod lambert;
+pub mod microfacet;
+pub mod principled;
+
+/// A direction picked by `Bsdf::sample`, with what the BSDF is for it and how
+/// likely it was to be picked
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct BsdfSample<T: Float> {
+    /// Where the light arrives from, pointing away from the surface
+    pub wi: Vec4<T>,
+    /// The BSDF for the pair of directions, as `evaluate` would give
+    pub value: [T; 3],
+    /// Density over solid angle, as `pdf` would give
+    pub pdf: T,
+}
+
+/// How a surface scatters light: the fraction of the light arriving from one
+/// direction that leaves in another, per unit of solid angle.
+///
+/// Directions are in the surface's own frame, given by `Frame`, with the
+/// outward normal along +z, so a direction with negative z is inside the
+/// surface. Both point away from the point being shaded.
+pub trait Bsdf<T: Float> {
+    /// How much of the light arriving from `wi` leaves towards `wo`, per
+    /// channel. Doesn't include the cosine at `wi`.
+    fn evaluate(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> [T; 3];
+
+    /// A direction for light to arrive from, picked roughly in proportion to
+    /// how much it would contribute. `choice` picks between the kinds of
+    /// scattering, such as reflecting or refracting, and `u` picks a direction
+    /// for that kind. None if nothing leaves towards `wo`.
+    fn sample(&self, wo: &Vec4<T>, choice: T, u: (T, T)) -> Option<BsdfSample<T>>;
+
+    /// The density over solid angle with which `sample` picks `wi`
+    fn pdf(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> T;
+}
+
+impl<T> Bsdf<T> for Box<dyn Bsdf<T>>
+where
+    T: Float,
+{
+    fn evaluate(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> [T; 3] {
+        self.as_ref().evaluate(wo, wi)
+    }
+
+    fn sample(&self, wo: &Vec4<T>, choice: T, u: (T, T)) -> Option<BsdfSample<T>> {
+        self.as_ref().sample(wo, choice, u)
+    }
+
+    fn pdf(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> T {
+        self.as_ref().pdf(wo, wi)
+    }
+}
+
+/// The axes of a surface at a point, for moving directions between the world
+/// and the surface's own frame
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Frame<T: Float> {
+    tangent: Vec4<T>,
+    bitangent: Vec4<T>,
+    normal: Vec4<T>,
+}
+
+impl<T> Frame<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// The tangent is straightened up to be perpendicular to the normal. If
+    /// it's along the normal, any perpendicular direction is used instead.
+    pub fn new(normal: &Vec4<T>, tangent: &Vec4<T>) -> Frame<T> {
+        let tiny: T = FromPrimitive::from_f64(1e-9).unwrap();
+        let normal = Vec4::direction(normal.x, normal.y, normal.z).normalized();
+        let tangent = Vec4::direction(tangent.x, tangent.y, tangent.z);
+        let tangent = &tangent - &(&normal * normal.dot_product(&tangent));
+
+        let tangent = if tangent.mag() > tiny {
+            tangent.normalized()
+        } else {
+            normal.basis().0
+        };
+
+        Frame {
+            bitangent: normal.cross_product(&tangent),
+            tangent,
+            normal,
+        }
+    }
+
+    pub fn to_local(&self, v: &Vec4<T>) -> Vec4<T> {
+        Vec4::direction(
+            v.dot_product(&self.tangent),
+            v.dot_product(&self.bitangent),
+            v.dot_product(&self.normal),
+        )
+    }
+
+    pub fn to_world(&self, v: &Vec4<T>) -> Vec4<T> {
+        &(&(&self.tangent * v.x) + &(&self.bitangent * v.y)) + &(&self.normal * v.z)
+    }
+}
+
+/// Whether two directions in a surface frame are on the same side of it
+pub(crate) fn same_hemisphere<T: Float>(a: &Vec4<T>, b: &Vec4<T>) -> bool {
+    a.z * b.z > T::zero()
+}
+
+/// `w` mirrored about `n`
+pub(crate) fn reflect<T: Float + FromPrimitive>(w: &Vec4<T>, n: &Vec4<T>) -> Vec4<T> {
+    let two: T = FromPrimitive::from_f64(2.0).unwrap();
+    &(n * (two * w.dot_product(n))) - w
+}
+
+/// `w` bent through a surface with normal `n`, going from outside to inside
+/// when `w` is on the side `n` points to. `eta` is the inside's index of
+/// refraction over the outside's. Gives the new direction and the ratio of
+/// indices it actually crossed, or None if it's totally internally reflected.
+pub(crate) fn refract<T: Float>(w: &Vec4<T>, n: &Vec4<T>, eta: T) -> Option<(Vec4<T>, T)> {
+    let mut cos_i = n.dot_product(w);
+    let (mut n, mut eta) = (*n, eta);
+    if cos_i < T::zero() {
+        eta = T::one() / eta;
+        cos_i = -cos_i;
+        n = n.reverse();
+    }
+
+    let sin2_i = (T::one() - cos_i * cos_i).max(T::zero());
+    let sin2_t = sin2_i / (eta * eta);
+    if sin2_t >= T::one() {
+        return None;
+    }
+    let cos_t = (T::one() - sin2_t).sqrt();
+
+    let t = &(&w.reverse() * (T::one() / eta)) + &(&n * (cos_i / eta - cos_t));
+    Some((t, eta))
+}
+
+/// The fraction of unpolarised light reflected off a dielectric, arriving
+/// `cos_i` from the normal. `eta` is the inside's index over the outside's;
+/// a negative `cos_i` means the light arrives from inside.
+pub fn fresnel_dielectric<T: Float>(cos_i: T, eta: T) -> T {
+    let (mut cos_i, mut eta) = (cos_i.max(-T::one()).min(T::one()), eta);
+    if cos_i < T::zero() {
+        eta = T::one() / eta;
+        cos_i = -cos_i;
+    }
+
+    let sin2_t = (T::one() - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= T::one() {
+        return T::one();
+    }
+    let cos_t = (T::one() - sin2_t).max(T::zero()).sqrt();
+
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / (T::one() + T::one())
+}
+
+/// The fraction of unpolarised light reflected off a metal with complex index
+/// of refraction `eta` + i`k`, arriving `cos_i` from the normal
+pub fn fresnel_conductor<T: Float>(cos_i: T, eta: T, k: T) -> T {
+    let cos_i = cos_i.max(T::zero()).min(T::one());
+    let eta = Complex::new(eta, k);
+    let cos_i_c = Complex::new(cos_i, T::zero());
+    let one = Complex::new(T::one(), T::zero());
+
+    let sin2_i = one - cos_i_c * cos_i_c;
+    let sin2_t = sin2_i / (eta * eta);
+    let cos_t = (one - sin2_t).sqrt();
+
+    let parallel = (eta * cos_i_c - cos_t) / (eta * cos_i_c + cos_t);
+    let perpendicular = (cos_i_c - eta * cos_t) / (cos_i_c + eta * cos_t);
+    (parallel.norm_sqr() + perpendicular.norm_sqr()) / (T::one() + T::one())
+}
+
+/// Schlick's approximation to the Fresnel term, from the reflectance `f0`
+/// straight on
+pub(crate) fn schlick<T: Float>(f0: [T; 3], cos_i: T) -> [T; 3] {
+    let m = (T::one() - cos_i.abs().min(T::one())).powi(5);
+    f0.map(|f| f + (T::one() - f) * m)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    /// Directions from `sample` should agree with `evaluate` and `pdf`, and
+    /// the average of value * cosine / pdf over them is the fraction of light
+    /// the surface sends back out, per channel, when lit from `wo`
+    pub(crate) fn check_sampling(bsdf: &dyn Bsdf<f64>, wo: &Vec4<f64>) -> [f64; 3] {
+        let mut rng = Rng::new(11);
+        let n = 20000;
+        let mut total = [0.0; 3];
+
+        for _ in 0..n {
+            let choice = rng.next_float();
+            let u = (rng.next_float(), rng.next_float());
+            let s = match bsdf.sample(wo, choice, u) {
+                Some(s) => s,
+                None => continue,
+            };
+            assert!((s.wi.mag() - 1.0).abs() < 1e-9, "{:?}", s.wi);
+            assert!(s.pdf > 0.0);
+
+            let pdf = bsdf.pdf(wo, &s.wi);
+            assert!(
+                (pdf - s.pdf).abs() <= 1e-6 * pdf.max(1.0),
+                "{} {}",
+                pdf,
+                s.pdf
+            );
+            let value = bsdf.evaluate(wo, &s.wi);
+            for c in 0..3 {
+                assert!((value[c] - s.value[c]).abs() <= 1e-6 * value[c].max(1.0));
+                total[c] += s.value[c] * s.wi.z.abs() / s.pdf;
+            }
+        }
+
+        total.map(|t| t / n as f64)
+    }
+
+    /// The pdf should cover the whole sphere of directions exactly once
+    pub(crate) fn pdf_integral(bsdf: &dyn Bsdf<f64>, wo: &Vec4<f64>) -> f64 {
+        let mut rng = Rng::new(5);
+        let n = 200000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            // Uniformly over the sphere
+            let z = 1.0 - 2.0 * rng.next_float::<f64>();
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let phi = 2.0 * std::f64::consts::PI * rng.next_float::<f64>();
+            let wi = Vec4::direction(r * phi.cos(), r * phi.sin(), z);
+            total += bsdf.pdf(wo, &wi) * 4.0 * std::f64::consts::PI;
+        }
+        total / n as f64
+    }
+
+    #[test]
+    fn frame() {
+        let f = Frame::new(
+            &Vec4::direction(0.0, 1.0, 0.0),
+            &Vec4::direction(1.0, 1.0, 0.0),
+        );
+        let v = Vec4::direction(0.3, -0.5, 0.8);
+        let local = f.to_local(&v);
+        assert!((local.z + 0.5).abs() < 1e-12);
+        assert!((local.x - 0.3).abs() < 1e-12);
+        assert!((&f.to_world(&local) - &v).mag() < 1e-12);
+    }
+
+    #[test]
+    fn reflection_and_refraction() {
+        let n = Vec4::direction(0.0, 0.0, 1.0);
+        let w = Vec4::direction(0.6, 0.0, 0.8);
+        assert_eq!(Vec4::direction(-0.6, 0.0, 0.8), reflect(&w, &n));
+
+        // Snell's law, going in and coming back out the same way
+        let (t, eta) = refract(&w, &n, 1.5).unwrap();
+        assert_eq!(1.5, eta);
+        assert!((t.x + 0.4).abs() < 1e-12);
+        assert!(t.z < 0.0);
+        let (back, eta) = refract(&t, &n, 1.5).unwrap();
+        assert_eq!(1.0 / 1.5, eta);
+        assert!((&back - &w).mag() < 1e-12);
+
+        // Too shallow to get out
+        assert!(refract(&Vec4::direction(0.8, 0.0, -0.6), &n, 1.5).is_none());
+    }
+
+    #[test]
+    fn fresnel() {
+        // 4% off glass straight on, all of it at grazing angles or when trapped
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1e-12);
+        assert_eq!(1.0, fresnel_dielectric(-0.5, 1.5));
+
+        // Straight on, ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2)
+        let (n, k) = (0.2, 3.9);
+        let expected = ((n - 1.0) * (n - 1.0) + k * k) / ((n + 1.0) * (n + 1.0) + k * k);
+        assert!((fresnel_conductor(1.0, n, k) - expected).abs() < 1e-12);
+        // A conductor with no absorption is a dielectric
+        assert!((fresnel_conductor(0.7, 1.5, 0.0) - fresnel_dielectric(0.7, 1.5)).abs() < 1e-12);
+    }
+}
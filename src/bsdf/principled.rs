@@ -0,0 +1,186 @@
+use image::Rgb;
+use num::{Float, FromPrimitive};
+
+use super::conductor::upper;
+use super::microfacet::Ggx;
+use super::*;
+use crate::colour;
+use crate::random::cosine_hemisphere;
+
+/// An all-purpose opaque material in the style of Disney's principled BSDF:
+/// one base colour and a few sliders from 0 to 1 cover everything from chalk
+/// to plastic to polished metal. A matte layer with Burley's retro-reflection
+/// sits under a GGX specular layer; the more metallic it is, the less matte
+/// there is and the more the specular takes on the base colour.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Principled<T: Float> {
+    base_colour: [T; 3],
+    metallic: T,
+    roughness: T,
+    specular: T,
+    distribution: Ggx<T>,
+}
+
+impl<T> Principled<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// A plastic-like dielectric, with a specular level of 0.5 (about 4%
+    /// reflected straight on)
+    pub fn new(base_colour: Rgb<u8>, metallic: T, roughness: T) -> Principled<T> {
+        let clamp = |v: T| v.max(T::zero()).min(T::one());
+        Principled {
+            base_colour: colour::from_rgb(base_colour),
+            metallic: clamp(metallic),
+            roughness: clamp(roughness),
+            specular: FromPrimitive::from_f64(0.5).unwrap(),
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    /// How much a dielectric reflects straight on, from 0 to 8% across the
+    /// range
+    pub fn set_specular(&mut self, specular: T) {
+        self.specular = specular.max(T::zero()).min(T::one());
+    }
+
+    /// The specular colour straight on
+    fn f0(&self) -> [T; 3] {
+        let scale: T = FromPrimitive::from_f64(0.08).unwrap();
+        let dielectric = scale * self.specular;
+        self.base_colour
+            .map(|c| dielectric * (T::one() - self.metallic) + c * self.metallic)
+    }
+
+    /// How often `sample` picks the matte layer rather than the specular
+    fn diffuse_chance(&self) -> T {
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        (T::one() - self.metallic) * half
+    }
+
+    fn diffuse(&self, wo: &Vec4<T>, wi: &Vec4<T>, wm: &Vec4<T>) -> [T; 3] {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+
+        // Rough surfaces get brighter towards grazing angles, smooth ones darker
+        let cos_d = wi.dot_product(wm);
+        let fd90 = half + two * self.roughness * cos_d * cos_d;
+        let towards = |cos: T| T::one() + (fd90 - T::one()) * (T::one() - cos).powi(5);
+        let scale = towards(wo.z) * towards(wi.z) * (T::one() - self.metallic) / pi;
+        self.base_colour.map(|c| c * scale)
+    }
+
+    fn specular(&self, wo: &Vec4<T>, wi: &Vec4<T>, wm: &Vec4<T>) -> [T; 3] {
+        let four: T = FromPrimitive::from_f64(4.0).unwrap();
+        let scale = self.distribution.d(wm) * self.distribution.g(wo, wi) / (four * wo.z * wi.z);
+        schlick(self.f0(), wi.dot_product(wm)).map(|f| f * scale)
+    }
+}
+
+impl<T> Bsdf<T> for Principled<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn evaluate(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> [T; 3] {
+        let (wo, wi) = upper(wo, wi);
+        if wo.z <= T::zero() || wi.z <= T::zero() {
+            return [T::zero(); 3];
+        }
+        let wm = (&wo + &wi).normalized();
+
+        let diffuse = self.diffuse(&wo, &wi, &wm);
+        let specular = self.specular(&wo, &wi, &wm);
+        [0, 1, 2].map(|c| diffuse[c] + specular[c])
+    }
+
+    fn sample(&self, wo: &Vec4<T>, choice: T, u: (T, T)) -> Option<BsdfSample<T>> {
+        let flipped = wo.z < T::zero();
+        let (up, _) = upper(wo, wo);
+        if up.z == T::zero() {
+            return None;
+        }
+
+        let wi = if choice < self.diffuse_chance() {
+            cosine_hemisphere(&Vec4::direction(T::zero(), T::zero(), T::one()), u.0, u.1)
+        } else {
+            let wm = self.distribution.sample_normal(&up, u);
+            reflect(&up, &wm)
+        };
+        if wi.z <= T::zero() {
+            return None;
+        }
+        let wi = if flipped {
+            Vec4::direction(wi.x, wi.y, -wi.z)
+        } else {
+            wi
+        };
+
+        Some(BsdfSample {
+            wi,
+            value: self.evaluate(wo, &wi),
+            pdf: self.pdf(wo, &wi),
+        })
+    }
+
+    fn pdf(&self, wo: &Vec4<T>, wi: &Vec4<T>) -> T {
+        let (wo, wi) = upper(wo, wi);
+        if wo.z <= T::zero() || wi.z <= T::zero() {
+            return T::zero();
+        }
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let four: T = FromPrimitive::from_f64(4.0).unwrap();
+        let wm = (&wo + &wi).normalized();
+
+        let diffuse = wi.z / pi;
+        let specular = self.distribution.visible_d(&wo, &wm) / (four * wo.dot_product(&wm).abs());
+        let p = self.diffuse_chance();
+        p * diffuse + (T::one() - p) * specular
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsdf::conductor::Conductor;
+    use crate::bsdf::tests::{check_sampling, pdf_integral};
+
+    #[test]
+    fn plastic() {
+        let red = Principled::new(Rgb([255, 0, 0]), 0.0, 0.5);
+        let wo = Vec4::direction(0.6, 0.0, 0.8);
+
+        let reflected = check_sampling(&red, &wo);
+        // Mostly red, with a little white from the specular layer
+        assert!(reflected[0] > 0.8 && reflected[0] < 1.1, "{:?}", reflected);
+        assert!(reflected[1] > 0.0 && reflected[1] < 0.1, "{:?}", reflected);
+        assert!((pdf_integral(&red, &wo) - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn metal() {
+        // Fully metallic, it's a conductor tinted by the base colour
+        let metal = Principled::new(Rgb([255, 128, 0]), 1.0, 0.3);
+        let wo = Vec4::direction(0.0, 0.6, 0.8);
+        let wi = Vec4::direction(0.1, -0.5, 0.86).normalized();
+
+        let f = metal.evaluate(&wo, &wi);
+        assert!(f[0] > f[1] && f[2] < 0.01 * f[0], "{:?}", f);
+
+        let aluminium = Conductor::aluminium(0.3).evaluate(&wo, &wi);
+        assert!((f[0] - aluminium[0]).abs() / aluminium[0] < 0.1);
+        check_sampling(&metal, &wo);
+    }
+
+    #[test]
+    fn retro_reflection() {
+        let wo = Vec4::direction(0.0, 0.9, 0.2).normalized();
+        let back = Vec4::direction(0.0, 0.85, 0.3).normalized();
+        let rough = Principled::new(Rgb([255, 255, 255]), 0.0, 1.0);
+        let smooth = Principled::new(Rgb([255, 255, 255]), 0.0, 0.0);
+        assert!(
+            rough.diffuse(&wo, &back, &(&wo + &back).normalized())[0]
+                > smooth.diffuse(&wo, &back, &(&wo + &back).normalized())[0]
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use num::{Float, FromPrimitive};
+
+use crate::error::{Error, Result};
+use crate::progressive::Accumulator;
+
+const MAGIC: &[u8; 4] = b"TRCK";
+const VERSION: u32 = 2;
+
+/// Everything needed to carry on a progressive render where it left off: the
+/// running totals and variance for every pixel, and how far through the passes
+/// it had got.
+///
+/// Every sample draws from its own generator, seeded from the pixel and its
+/// sample count, so the totals are all the random state there is. Carrying on from a
+/// checkpoint gives exactly the image an uninterrupted render would have.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint<T: Float> {
+    pub accumulator: Accumulator<T>,
+    /// Complete passes over the image
+    pub passes: u32,
+    /// Rows of the next pass already done
+    pub row: u32,
+}
+
+impl<T> Checkpoint<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Written alongside and then renamed over the old checkpoint, so a crash
+    /// part way through leaves the last good one in place
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        save(&self.accumulator, self.passes, self.row, path.as_ref())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Checkpoint<T>> {
+        Checkpoint::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Little-endian throughout, with the totals stored as f64 whatever `T` is
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        write(&self.accumulator, self.passes, self.row, writer)
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Checkpoint<T>> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a checkpoint file"));
+        }
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            return Err(invalid(&format!("unknown version {}", version)));
+        }
+
+        let width = read_u32(reader)?;
+        let height = read_u32(reader)?;
+        let passes = read_u32(reader)?;
+        let row = read_u32(reader)?;
+        if row >= height.max(1) {
+            return Err(invalid("row is past the bottom of the image"));
+        }
+
+        // Read everything before sizing the accumulator, so a header claiming a
+        // huge image can't make it allocate more than the file actually holds
+        let mut pixels = Vec::new();
+        for _ in 0..width as u64 * height as u64 {
+            pixels.push(read_pixel::<T, R>(reader).map_err(|e| match e {
+                Error::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    invalid("fewer pixels than the header says")
+                }
+                e => e,
+            })?);
+        }
+
+        let mut accumulator = Accumulator::new(width, height);
+        let positions = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)));
+        for ((x, y), (count, [r, g, b, m2])) in positions.zip(pixels) {
+            accumulator.restore(x, y, [r, g, b], count, m2);
+        }
+
+        Ok(Checkpoint {
+            accumulator,
+            passes,
+            row,
+        })
+    }
+}
+
+/// Saves a checkpoint straight from a render's own accumulator, without having
+/// to copy it into a `Checkpoint` first
+pub(crate) fn save<T>(
+    accumulator: &Accumulator<T>,
+    passes: u32,
+    row: u32,
+    path: &Path,
+) -> Result<()>
+where
+    T: Float + FromPrimitive,
+{
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+
+    let mut writer = BufWriter::new(File::create(&partial)?);
+    write(accumulator, passes, row, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+fn write<T, W>(accumulator: &Accumulator<T>, passes: u32, row: u32, writer: &mut W) -> Result<()>
+where
+    T: Float + FromPrimitive,
+    W: Write,
+{
+    let a = accumulator;
+    writer.write_all(MAGIC)?;
+    for n in &[VERSION, a.width(), a.height(), passes, row] {
+        writer.write_all(&n.to_le_bytes())?;
+    }
+
+    for y in 0..a.height() {
+        for x in 0..a.width() {
+            writer.write_all(&a.count(x, y).to_le_bytes())?;
+            for c in a.sum(x, y).iter().chain(Some(a.m2(x, y)).iter()) {
+                let c = c.to_f64().ok_or_else(|| invalid("total out of range"))?;
+                writer.write_all(&c.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// A pixel's sample count, then its colour totals and luminance variance's
+fn read_pixel<T, R>(reader: &mut R) -> Result<(u32, [T; 4])>
+where
+    T: Float + FromPrimitive,
+    R: Read,
+{
+    let count = read_u32(reader)?;
+    let mut totals = [T::zero(); 4];
+    for c in totals.iter_mut() {
+        let mut bytes = [0; 8];
+        reader.read_exact(&mut bytes)?;
+        *c = T::from_f64(f64::from_le_bytes(bytes)).ok_or_else(|| invalid("total out of range"))?;
+    }
+    Ok((count, totals))
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidCheckpoint(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint() -> Checkpoint<f64> {
+        let mut accumulator = Accumulator::new(3, 2);
+        accumulator.add(0, 0, [0.1, 0.2, 0.3]);
+        accumulator.add(0, 0, [1.0 / 3.0, 0.0, 1e-300]);
+        accumulator.add(2, 1, [5.0, 6.0, 7.0]);
+        Checkpoint {
+            accumulator,
+            passes: 1,
+            row: 1,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = checkpoint();
+        let mut bytes = vec![];
+        original.write_to(&mut bytes).unwrap();
+
+        let loaded: Checkpoint<f64> = Checkpoint::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(original, loaded);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let mut bytes = vec![];
+        checkpoint().write_to(&mut bytes).unwrap();
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(matches!(
+            Checkpoint::<f64>::read_from(&mut wrong_magic.as_slice()),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+
+        // Cut off part way through the pixels
+        bytes.truncate(bytes.len() - 4);
+        assert!(matches!(
+            Checkpoint::<f64>::read_from(&mut bytes.as_slice()),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+
+        // Or in the header
+        bytes.truncate(10);
+        assert!(matches!(
+            Checkpoint::<f64>::read_from(&mut bytes.as_slice()),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn huge_header_without_pixels() {
+        // Fails on the missing pixels rather than trying to make room for them
+        let mut bytes = MAGIC.to_vec();
+        for n in &[VERSION, 1 << 20, 1 << 20, 0, 0] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        assert!(matches!(
+            Checkpoint::<f64>::read_from(&mut bytes.as_slice()),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+    }
+}
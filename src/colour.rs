@@ -0,0 +1,59 @@
+use image::Rgb;
+use num::{Float, FromPrimitive};
+
+/// Scale an 8-bit colour into [0, 1] per channel
+pub fn from_rgb<T>(colour: Rgb<u8>) -> [T; 3]
+where
+    T: Float + FromPrimitive,
+{
+    let max_u8: T = FromPrimitive::from_u8(0xff).unwrap();
+
+    let r: T = FromPrimitive::from_u8(colour[0]).unwrap();
+    let g: T = FromPrimitive::from_u8(colour[1]).unwrap();
+    let b: T = FromPrimitive::from_u8(colour[2]).unwrap();
+
+    [r / max_u8, g / max_u8, b / max_u8]
+}
+
+/// Clamp each channel into [0, 1] and scale to 8 bits
+pub fn to_rgb<T>(colour: [T; 3]) -> Rgb<u8>
+where
+    T: Float + FromPrimitive,
+{
+    let max_u8 = FromPrimitive::from_u8(0xff).unwrap();
+    let scaled = colour
+        .iter()
+        .map(|channel| T::min(T::one(), T::max(T::zero(), *channel)))
+        .map(|channel| channel * max_u8)
+        .map(|channel| channel.to_u8().unwrap())
+        .collect::<Vec<_>>();
+
+    Rgb([scaled[0], scaled[1], scaled[2]])
+}
+
+/// Perceived brightness of a linear sRGB colour
+pub fn luminance<T>(c: &[T; 3]) -> T
+where
+    T: Float + FromPrimitive,
+{
+    let r: T = FromPrimitive::from_f64(0.2126).unwrap();
+    let g: T = FromPrimitive::from_f64(0.7152).unwrap();
+    let b: T = FromPrimitive::from_f64(0.0722).unwrap();
+    c[0] * r + c[1] * g + c[2] * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let c = Rgb([0, 128, 255]);
+        assert_eq!(c, to_rgb::<f64>(from_rgb(c)));
+    }
+
+    #[test]
+    fn clamps() {
+        assert_eq!(Rgb([0, 255, 255]), to_rgb([-1.0, 1.0, 100.0]));
+    }
+}
@@ -0,0 +1,431 @@
+use image::RgbImage;
+use num::{Float, FromPrimitive};
+
+use crate::colour;
+use crate::error::{Error, Result};
+use crate::vector::Vec4;
+
+/// An image kept as linear colour, so nothing is lost to clamping or rounding
+/// before it's finished with
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatImage<T: Float> {
+    width: u32,
+    height: u32,
+    pixels: Vec<[T; 3]>,
+}
+
+impl<T> FloatImage<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// All black
+    pub fn new(width: u32, height: u32) -> FloatImage<T> {
+        FloatImage {
+            width,
+            height,
+            pixels: vec![[T::zero(); 3]; width as usize * height as usize],
+        }
+    }
+
+    pub fn from_fn<F>(width: u32, height: u32, mut f: F) -> FloatImage<T>
+    where
+        F: FnMut(u32, u32) -> [T; 3],
+    {
+        let mut image = FloatImage::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                image.put(x, y, f(x, y));
+            }
+        }
+        image
+    }
+
+    /// Each channel scaled into [0, 1]
+    pub fn from_rgb_image(image: &RgbImage) -> FloatImage<T> {
+        FloatImage::from_fn(image.width(), image.height(), |x, y| {
+            colour::from_rgb(*image.get_pixel(x, y))
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> [T; 3] {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn put(&mut self, x: u32, y: u32, colour: [T; 3]) {
+        let i = self.index(x, y);
+        self.pixels[i] = colour;
+    }
+
+    pub fn to_image(&self) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            colour::to_rgb(self.get(x, y))
+        })
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+}
+
+/// What the camera sees first through the middle of each pixel, free of the
+/// noise in the lighting, so a denoiser can tell edges from noise
+#[derive(Debug, Clone, PartialEq)]
+pub struct Guides<T: Float> {
+    albedo: FloatImage<T>,
+    normal: Vec<Vec4<T>>,
+    depth: Vec<T>,
+}
+
+impl<T> Guides<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Seeing nothing at all, until each pixel is `set`
+    pub fn new(width: u32, height: u32) -> Guides<T> {
+        let pixels = width as usize * height as usize;
+        Guides {
+            albedo: FloatImage::new(width, height),
+            normal: vec![Vec4::direction(T::zero(), T::zero(), T::zero()); pixels],
+            depth: vec![T::infinity(); pixels],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.albedo.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.albedo.height
+    }
+
+    /// Where nothing was hit, the normal should be zero and the depth infinite
+    pub fn set(&mut self, x: u32, y: u32, albedo: [T; 3], normal: &Vec4<T>, depth: T) {
+        let i = self.albedo.index(x, y);
+        self.albedo.pixels[i] = albedo;
+        self.normal[i] = Vec4::direction(normal.x, normal.y, normal.z);
+        self.depth[i] = depth;
+    }
+
+    /// The colour of the surface itself, before any lighting
+    pub fn albedo(&self, x: u32, y: u32) -> [T; 3] {
+        self.albedo.get(x, y)
+    }
+
+    pub fn normal(&self, x: u32, y: u32) -> Vec4<T> {
+        self.normal[self.albedo.index(x, y)]
+    }
+
+    /// Distance from the camera
+    pub fn depth(&self, x: u32, y: u32) -> T {
+        self.depth[self.albedo.index(x, y)]
+    }
+
+    /// The albedo as a picture, to check what the denoiser is working from
+    pub fn albedo_image(&self) -> RgbImage {
+        self.albedo.to_image()
+    }
+}
+
+/// Smooths away noise with an edge-avoiding à-trous wavelet filter (Dammertz et
+/// al., 2010). Each pass blurs over a wider spread of pixels, but pixels only
+/// count towards each other while their colour, albedo, normal and depth are
+/// alike, so edges and texture stay sharp.
+#[derive(Debug, Clone)]
+pub struct Denoiser<T: Float> {
+    iterations: u32,
+    colour_sigma: T,
+    albedo_sigma: T,
+    normal_sigma: T,
+    depth_sigma: T,
+}
+
+impl<T> Default for Denoiser<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn default() -> Denoiser<T> {
+        Denoiser::new()
+    }
+}
+
+impl<T> Denoiser<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn new() -> Denoiser<T> {
+        Denoiser {
+            iterations: 5,
+            colour_sigma: FromPrimitive::from_f64(0.5).unwrap(),
+            albedo_sigma: FromPrimitive::from_f64(0.1).unwrap(),
+            normal_sigma: FromPrimitive::from_f64(0.3).unwrap(),
+            depth_sigma: FromPrimitive::from_f64(0.1).unwrap(),
+        }
+    }
+
+    /// Passes of the filter. Each doubles the spacing of the pixels it looks at,
+    /// so five reach 62 pixels either side.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// How different two pixels' lighting can be and still be averaged together.
+    /// Larger is smoother but blurs soft shadows.
+    pub fn colour_sigma(mut self, sigma: T) -> Self {
+        self.colour_sigma = sigma;
+        self
+    }
+
+    /// How different two pixels' surface colour can be
+    pub fn albedo_sigma(mut self, sigma: T) -> Self {
+        self.albedo_sigma = sigma;
+        self
+    }
+
+    /// How far apart two pixels' normals can point
+    pub fn normal_sigma(mut self, sigma: T) -> Self {
+        self.normal_sigma = sigma;
+        self
+    }
+
+    /// How different two pixels' depths can be, as a fraction of the distance
+    pub fn depth_sigma(mut self, sigma: T) -> Self {
+        self.depth_sigma = sigma;
+        self
+    }
+
+    /// The image with its noise smoothed away. The guides must be the same size.
+    pub fn run(&self, image: &FloatImage<T>, guides: &Guides<T>) -> Result<FloatImage<T>> {
+        if image.width != guides.width() || image.height != guides.height() {
+            return Err(Error::InvalidImage(format!(
+                "a {}x{} image can't be denoised with {}x{} guides",
+                image.width,
+                image.height,
+                guides.width(),
+                guides.height()
+            )));
+        }
+
+        // Filter the lighting alone, so texture detail isn't blurred along with
+        // the noise, then put the surface colour back afterwards
+        let epsilon: T = FromPrimitive::from_f64(1e-3).unwrap();
+        let albedo = |x, y| guides.albedo(x, y).map(|a| a.max(T::zero()) + epsilon);
+        let mut lighting = FloatImage::from_fn(image.width, image.height, |x, y| {
+            let (c, a) = (image.get(x, y), albedo(x, y));
+            [c[0] / a[0], c[1] / a[1], c[2] / a[2]]
+        });
+
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let mut colour_sigma = self.colour_sigma;
+        for i in 0..self.iterations.min(16) {
+            lighting = self.pass(&lighting, guides, 1 << i, colour_sigma);
+            // The image is smoother each time, so smaller differences mark edges
+            colour_sigma = colour_sigma / two;
+        }
+
+        Ok(FloatImage::from_fn(image.width, image.height, |x, y| {
+            let (l, a) = (lighting.get(x, y), albedo(x, y));
+            [l[0] * a[0], l[1] * a[1], l[2] * a[2]]
+        }))
+    }
+
+    /// One pass of the filter, looking at pixels `step` apart
+    fn pass(
+        &self,
+        image: &FloatImage<T>,
+        guides: &Guides<T>,
+        step: i64,
+        colour_sigma: T,
+    ) -> FloatImage<T> {
+        let kernel: [T; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0]
+            .map(|k| FromPrimitive::from_f64(k).unwrap());
+        let (width, height) = (image.width as i64, image.height as i64);
+
+        FloatImage::from_fn(image.width, image.height, |x, y| {
+            let centre = image.get(x, y);
+            let mut total = [T::zero(); 3];
+            let mut weights = T::zero();
+
+            for (j, ky) in kernel.iter().enumerate() {
+                let qy = y as i64 + (j as i64 - 2) * step;
+                if qy < 0 || qy >= height {
+                    continue;
+                }
+                for (i, kx) in kernel.iter().enumerate() {
+                    let qx = x as i64 + (i as i64 - 2) * step;
+                    if qx < 0 || qx >= width {
+                        continue;
+                    }
+                    let (qx, qy) = (qx as u32, qy as u32);
+
+                    let c = image.get(qx, qy);
+                    let w = *kx
+                        * *ky
+                        * similarity(distance_squared(&centre, &c), colour_sigma)
+                        * self.guide_weight(guides, (x, y), (qx, qy));
+                    for k in 0..3 {
+                        total[k] = total[k] + c[k] * w;
+                    }
+                    weights = weights + w;
+                }
+            }
+
+            // The middle pixel always counts, so the weights never add up to zero
+            [total[0] / weights, total[1] / weights, total[2] / weights]
+        })
+    }
+
+    /// How alike two pixels are in everything but their lighting
+    fn guide_weight(&self, guides: &Guides<T>, p: (u32, u32), q: (u32, u32)) -> T {
+        let albedo = distance_squared(&guides.albedo(p.0, p.1), &guides.albedo(q.0, q.1));
+        let normal = (&guides.normal(p.0, p.1) - &guides.normal(q.0, q.1)).mag();
+        let depth = relative_difference(guides.depth(p.0, p.1), guides.depth(q.0, q.1));
+
+        similarity(albedo, self.albedo_sigma)
+            * similarity(normal * normal, self.normal_sigma)
+            * similarity(depth * depth, self.depth_sigma)
+    }
+}
+
+/// One where the difference is nothing, falling away as it grows past `sigma`
+fn similarity<T: Float>(difference_squared: T, sigma: T) -> T {
+    (-difference_squared / (sigma * sigma)).exp()
+}
+
+fn distance_squared<T: Float>(a: &[T; 3], b: &[T; 3]) -> T {
+    a.iter()
+        .zip(b.iter())
+        .fold(T::zero(), |sum, (a, b)| sum + (*a - *b) * (*a - *b))
+}
+
+/// The difference between two depths as a fraction of the larger, so a step
+/// counts for less the further away it is. Infinite between something and
+/// nothing.
+fn relative_difference<T: Float>(a: T, b: T) -> T {
+    if a == b {
+        T::zero()
+    } else if a.is_infinite() || b.is_infinite() {
+        T::infinity()
+    } else {
+        (a - b).abs() / a.abs().max(b.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    /// Flat and facing the camera, split down the middle into a near half and a
+    /// far half
+    fn split_guides(width: u32, height: u32) -> Guides<f64> {
+        let mut guides = Guides::new(width, height);
+        let facing = Vec4::direction(0.0, 0.0, -1.0);
+        for y in 0..height {
+            for x in 0..width {
+                let depth = if x < width / 2 { 1.0 } else { 10.0 };
+                guides.set(x, y, [1.0, 1.0, 1.0], &facing, depth);
+            }
+        }
+        guides
+    }
+
+    fn noisy(width: u32, height: u32, colour: impl Fn(u32) -> f64) -> FloatImage<f64> {
+        let mut rng = Rng::new(7);
+        FloatImage::from_fn(width, height, |x, _| {
+            let c = colour(x) + (rng.next_float::<f64>() - 0.5) * 0.2;
+            [c, c, c]
+        })
+    }
+
+    fn spread(image: &FloatImage<f64>, columns: std::ops::Range<u32>) -> f64 {
+        let values: Vec<f64> = columns
+            .flat_map(|x| (0..image.height()).map(move |y| (x, y)))
+            .map(|(x, y)| image.get(x, y)[0])
+            .collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64
+    }
+
+    #[test]
+    fn flat_image() {
+        let image = FloatImage::from_fn(8, 8, |_, _| [0.25, 0.5, 0.75]);
+        let guides = split_guides(8, 8);
+        let denoised = Denoiser::new().run(&image, &guides).unwrap();
+        for y in 0..8 {
+            for x in 0..8 {
+                let c = denoised.get(x, y);
+                assert!((c[0] - 0.25).abs() < 1e-9, "{:?}", c);
+                assert!((c[2] - 0.75).abs() < 1e-9, "{:?}", c);
+            }
+        }
+    }
+
+    #[test]
+    fn removes_noise() {
+        let image = noisy(32, 32, |_| 0.5);
+        let mut guides = Guides::new(32, 32);
+        for y in 0..32 {
+            for x in 0..32 {
+                guides.set(x, y, [1.0; 3], &Vec4::direction(0.0, 0.0, -1.0), 5.0);
+            }
+        }
+
+        let denoised = Denoiser::new().run(&image, &guides).unwrap();
+        assert!(spread(&denoised, 0..32) < spread(&image, 0..32) / 10.0);
+    }
+
+    #[test]
+    fn keeps_edges() {
+        let image = noisy(32, 32, |x| if x < 16 { 0.2 } else { 0.8 });
+        let denoised = Denoiser::new().run(&image, &split_guides(32, 32)).unwrap();
+
+        // Each side is smoothed, but they don't bleed into each other
+        for y in 0..32 {
+            assert!((denoised.get(15, y)[0] - 0.2).abs() < 0.05);
+            assert!((denoised.get(16, y)[0] - 0.8).abs() < 0.05);
+        }
+        assert!(spread(&denoised, 0..16) < spread(&image, 0..16) / 4.0);
+    }
+
+    #[test]
+    fn keeps_texture() {
+        // A checkerboard in the albedo, evenly lit, with noise in the lighting
+        let mut rng = Rng::new(3);
+        let mut guides = Guides::new(16, 16);
+        let image = FloatImage::from_fn(16, 16, |x, y| {
+            let a = if (x + y) % 2 == 0 { 0.2 } else { 0.9 };
+            guides.set(x, y, [a; 3], &Vec4::direction(0.0, 0.0, -1.0), 5.0);
+            let c = a * (0.5 + (rng.next_float::<f64>() - 0.5) * 0.2);
+            [c, c, c]
+        });
+
+        let denoised = Denoiser::new().run(&image, &guides).unwrap();
+        assert!((denoised.get(0, 0)[0] - 0.1).abs() < 0.02);
+        assert!((denoised.get(1, 0)[0] - 0.45).abs() < 0.05);
+    }
+
+    #[test]
+    fn needs_matching_guides() {
+        let image = FloatImage::new(4, 4);
+        assert!(matches!(
+            Denoiser::new().run(&image, &Guides::<f64>::new(4, 5)),
+            Err(Error::InvalidImage(_))
+        ));
+    }
+
+    #[test]
+    fn depth_differences() {
+        assert_eq!(0.0, relative_difference(f64::INFINITY, f64::INFINITY));
+        assert_eq!(f64::INFINITY, relative_difference(1.0, f64::INFINITY));
+        assert!((relative_difference(8.0, 10.0) - 0.2).abs() < 1e-12);
+    }
+}
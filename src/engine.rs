@@ -1,23 +1,72 @@
 extern crate image;
 
-use image::Rgb;
 use num::{Float, FromPrimitive};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::vec;
 
+use crate::background::Background;
+use crate::bsdf::{Bsdf, Frame};
+use crate::denoise::Guides;
+use crate::error::{Error, Result};
 use crate::light::Light;
 use crate::matrix::Mat4;
+use crate::medium::Medium;
+use crate::motion::Motion;
 use crate::object::*;
+use crate::random::Rng;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::session::RenderSession;
 use crate::vector::Vec4;
 
 pub struct Engine<T: Float> {
     view: Mat4<T>,
+    camera_motion: Option<Motion<T>>,
+    samples: u32,
+    max_bounces: u32,
+    background: Background<T>,
     objects: Vec<Box<dyn Intersectable<T>>>,
+    /// Which of the objects glow, and so are sampled as lights
+    emitters: Vec<usize>,
     lights: Vec<Box<dyn Light<T>>>,
+    media: Vec<Box<dyn Medium<T>>>,
+    rays_cast: AtomicU64,
+}
+
+/// Where the eye is, and where each pixel lies on the image plane in front of it
+pub(crate) struct Film<T: Float> {
+    origin: Vec4<T>,
+    scale: T,
+    x_origin: T,
+    y_origin: T,
 }
 
 enum TraceResult<'a, T: Float> {
     Miss,
-    Hit(Vec4<T>, &'a dyn Intersectable<T>),
+    /// The hit, and which object it was on
+    Hit(HitRecord<'a, T>, usize),
+    /// The colour of a light, and which light it was
+    Emitter([T; 3], usize),
+}
+
+/// The scene as it stood at one instant, so shadow rays see moving objects
+/// where the ray that found the hit saw them
+struct Moment<'a, T: Float> {
+    engine: &'a Engine<T>,
+    time: Option<T>,
+}
+
+impl<'a, T> Scene<T> for Moment<'a, T>
+where
+    T: Float + FromPrimitive + std::fmt::Debug,
+{
+    fn occluded(&self, ray: &Ray<T>) -> bool {
+        self.engine.occluded(&Ray {
+            time: ray.time.or(self.time),
+            ..*ray
+        })
+    }
 }
 
 impl<T> Engine<T>
@@ -27,40 +76,275 @@ where
     pub fn new(view: Mat4<T>) -> Engine<T> {
         Engine {
             view,
+            camera_motion: None,
+            samples: 1,
+            max_bounces: 4,
+            background: Background::default(),
             objects: vec![],
+            emitters: vec![],
             lights: vec![],
+            media: vec![],
+            rays_cast: AtomicU64::new(0),
         }
     }
 
+    /// Objects with an emissive material light the scene too, if they can be
+    /// sampled; see `Intersectable::sample_emission`
     pub fn add_object(&mut self, object: Box<dyn Intersectable<T>>) {
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        if object.sample_emission(half, (half, half), None).is_some() {
+            self.emitters.push(self.objects.len());
+        }
         self.objects.push(object);
     }
 
+    /// Move the camera while the shutter is open. The motion is in the camera's
+    /// own coordinates, so it turns about its own position.
+    pub fn set_camera_motion(&mut self, motion: Motion<T>) {
+        self.camera_motion = Some(motion);
+    }
+
+    /// Rays averaged per pixel, each at a different point in the pixel and in the
+    /// exposure
+    pub fn set_samples(&mut self, samples: u32) {
+        self.samples = samples.max(1);
+    }
+
+    /// How many times light is followed off surfaces with a `Bsdf` before
+    /// giving up on it. Without any, they're lit only by lights that shine from
+    /// a single direction or from all around.
+    pub fn set_max_bounces(&mut self, max_bounces: u32) {
+        self.max_bounces = max_bounces;
+    }
+
+    pub fn set_background(&mut self, background: Background<T>) {
+        self.background = background;
+    }
+
     pub fn add_light(&mut self, light: Box<dyn Light<T>>) {
         self.lights.push(light);
     }
 
-    fn trace_ray(&self, origin: &Vec4<T>, direction: &Vec4<T>) -> TraceResult<T> {
-        for o in self.objects.iter() {
-            let result = o.intersect(origin, direction);
+    /// Fill the scene with a medium, such as a `Homogeneous` fog, or the inside
+    /// of an object with one through a `Volume`. Light scattered in a medium
+    /// casts shadows, so beams show up where something blocks part of a light.
+    ///
+    /// Lights shine on plain materials, without a `Bsdf`, as if the air were
+    /// clear. An unbounded medium fills the space around the scene too, so far
+    /// enough away the background fades into it.
+    pub fn add_medium(&mut self, medium: Box<dyn Medium<T>>) {
+        self.media.push(medium);
+    }
+
+    fn trace_ray(&self, ray: &Ray<T>) -> TraceResult<'_, T> {
+        // Every hit cuts the ray short, so anything found afterwards is nearer
+        self.rays_cast.fetch_add(1, Ordering::Relaxed);
+        let mut ray = *ray;
+        let mut nearest = TraceResult::Miss;
 
-            if let IntersectResult::Intersect(t) = result {
-                let v = direction * t;
-                let intersect_point = origin + &v;
-                return TraceResult::Hit(intersect_point, o);
+        for (i, o) in self.objects.iter().enumerate() {
+            if let IntersectResult::Intersect(hit) = o.intersect(&ray) {
+                ray = ray.clipped(hit.t);
+                nearest = TraceResult::Hit(hit, i);
             }
         }
 
-        TraceResult::Miss
+        for (i, l) in self.lights.iter().enumerate() {
+            if let Some((t, colour)) = l.visible(&ray) {
+                ray = ray.clipped(t);
+                nearest = TraceResult::Emitter(colour, i);
+            }
+        }
+
+        nearest
+    }
+
+    /// A point on one of the glowing objects that can be seen from `point`: the
+    /// direction to it, the light it gives off that way, and the density over
+    /// solid angle with which it was picked
+    fn sample_emitter(
+        &self,
+        rng: &mut Rng,
+        sampler: &mut dyn Sampler<T>,
+        point: &Vec4<T>,
+        time: Option<T>,
+    ) -> Option<(Vec4<T>, [T; 3], T)> {
+        if self.emitters.is_empty() {
+            return None;
+        }
+        let count: T = FromPrimitive::from_usize(self.emitters.len()).unwrap();
+        let pick = (sampler.next_1d() * count).to_usize().unwrap_or(0);
+        let object = &self.objects[self.emitters[pick.min(self.emitters.len() - 1)]];
+
+        let choice = sampler.next_1d();
+        let sample = object.sample_emission(choice, sampler.next_2d(), time)?;
+        let to_light = &sample.point - point;
+        let distance = to_light.mag();
+        let direction = to_light.normalized();
+
+        // Only the front glows
+        let cos_light = -direction.dot_product(&sample.normal);
+        if cos_light <= T::zero() {
+            return None;
+        }
+
+        let epsilon: T = FromPrimitive::from_f64(1e-4).unwrap();
+        let shadow = Ray::segment(*point, direction, epsilon, distance - epsilon);
+        let through = self.visibility(rng, &shadow, time);
+        if through == T::zero() {
+            return None;
+        }
+
+        let pdf = sample.pdf * distance * distance / (cos_light * count);
+        Some((direction, sample.emission.map(|c| c * through), pdf))
     }
 
-    fn illuminate(&self, point: &Vec4<T>, object: &dyn Intersectable<T>) -> Rgb<u8> {
+    /// The fraction of light that gets along `ray` through all the media
+    fn transmittance(&self, rng: &mut Rng, ray: &Ray<T>) -> T {
+        self.media
+            .iter()
+            .fold(T::one(), |t, m| t * m.transmittance(rng, ray))
+    }
+
+    /// The fraction of light that gets along `ray` at `time`: none if an object
+    /// is in the way, otherwise whatever the media let through
+    fn visibility(&self, rng: &mut Rng, ray: &Ray<T>, time: Option<T>) -> T {
+        let ray = Ray {
+            time: ray.time.or(time),
+            ..*ray
+        };
+        let scene = Moment { engine: self, time };
+        if scene.occluded(&ray) {
+            T::zero()
+        } else {
+            self.transmittance(rng, &ray)
+        }
+    }
+
+    /// Where along `ray` it first runs into one of the media, and which one
+    fn sample_medium(&self, rng: &mut Rng, ray: &Ray<T>) -> Option<(T, usize)> {
+        // Whichever would be met first; the others would have been too late
+        let mut nearest = None;
+        let mut ray = *ray;
+        for (i, m) in self.media.iter().enumerate() {
+            if let Some(t) = m.sample_scatter(rng, &ray) {
+                ray = ray.clipped(t);
+                nearest = Some((t, i));
+            }
+        }
+        nearest
+    }
+
+    /// Light scattered towards the start of `ray` at `t` along it, by the
+    /// medium `medium`: straight from lights and glowing objects, and from
+    /// whatever one direction picked by the phase function leads to. Lights
+    /// and glowing objects that can be found both ways are weighted by how
+    /// likely each was to find them.
+    fn in_scatter(
+        &self,
+        rng: &mut Rng,
+        sampler: &mut dyn Sampler<T>,
+        ray: &Ray<T>,
+        t: T,
+        medium: usize,
+        bounces: u32,
+    ) -> [T; 3] {
+        let medium = &self.media[medium];
+        let phase = medium.phase();
+        let point = ray.at(t);
+        let along = ray.direction.normalized();
+        let mut total = [T::zero(); 3];
+
+        for l in self.lights.iter() {
+            if let Some((to_light, distance, light)) = l.incident(&point) {
+                let shadow = Ray::segment(point, to_light, T::zero(), distance);
+                let share = phase.evaluate(along.dot_product(&to_light))
+                    * self.visibility(rng, &shadow, ray.time);
+                for c in 0..3 {
+                    total[c] = total[c] + light[c] * share;
+                }
+            }
+
+            if let Some((direction, distance, light, pdf)) =
+                l.sample_incident(&point, sampler.next_2d())
+            {
+                let shadow = Ray::segment(point, direction, T::zero(), distance);
+                let p = phase.evaluate(along.dot_product(&direction));
+                let share = if bounces < self.max_bounces {
+                    power_heuristic(pdf, p)
+                } else {
+                    T::one()
+                };
+                let weight = p * share * self.visibility(rng, &shadow, ray.time) / pdf;
+                for c in 0..3 {
+                    total[c] = total[c] + light[c] * weight;
+                }
+            }
+        }
+
+        if let Some((direction, emission, pdf)) =
+            self.sample_emitter(rng, sampler, &point, ray.time)
+        {
+            let p = phase.evaluate(along.dot_product(&direction));
+            let share = if bounces < self.max_bounces {
+                power_heuristic(pdf, p)
+            } else {
+                T::one()
+            };
+            for c in 0..3 {
+                total[c] = total[c] + emission[c] * p * share / pdf;
+            }
+        }
+
+        if bounces < self.max_bounces {
+            let direction = phase.sample(&along, sampler.next_2d());
+            let next = Ray {
+                origin: point,
+                direction,
+                t_min: T::zero(),
+                t_max: T::infinity(),
+                ..*ray
+            };
+            // Picked with just the density the phase function gives it
+            let pdf = phase.evaluate(along.dot_product(&direction));
+            let incoming = self.trace_path(rng, sampler, &next, bounces + 1, Some(pdf));
+            for c in 0..3 {
+                total[c] = total[c] + incoming[c];
+            }
+        }
+
+        total.map(|c| c * medium.albedo())
+    }
+
+    /// The density over solid angle with which `sample_emitter` would have
+    /// picked the point `hit` on `object`, seen along `ray`
+    fn emitter_pdf(&self, object: usize, hit: &HitRecord<T>, ray: &Ray<T>) -> T {
+        if !self.emitters.contains(&object) {
+            return T::zero();
+        }
+        let count: T = FromPrimitive::from_usize(self.emitters.len()).unwrap();
+        let area_pdf = self.objects[object].emission_pdf(ray);
+
+        let cos_light = ray.direction.dot_product(&hit.geometric_normal).abs();
+        let distance = hit.t * ray.direction.mag();
+        area_pdf * distance * distance / (cos_light * count)
+    }
+
+    fn illuminate(
+        &self,
+        rng: &mut Rng,
+        sampler: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
+        time: Option<T>,
+    ) -> [T; 3] {
         let mut illum: [T; 3] = [T::zero(); 3];
+        let scene = Moment { engine: self, time };
 
         for l in self.lights.iter() {
             let illum_result = l.illuminate(
-                object,
-                point,
+                &scene,
+                sampler,
+                hit,
                 &Vec4::direction(T::zero(), T::zero(), T::zero()),
             );
             for i in 0..3 {
@@ -68,80 +352,1015 @@ where
             }
         }
 
-        let max_u8 = FromPrimitive::from_u8(0xff).unwrap();
-        let illum_scaled = illum
-            .iter()
-            .map(|channel| T::min(T::one(), T::max(T::zero(), *channel)))
-            .map(|channel| channel * max_u8)
-            .map(|channel| channel.to_u8().unwrap())
-            .collect::<Vec<_>>();
+        // Glowing objects light it as they would a white `Lambert` surface
+        if let Some((direction, emission, pdf)) =
+            self.sample_emitter(rng, sampler, &hit.point, time)
+        {
+            let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+            let cos = hit.shading_normal().dot_product(&direction);
+            if cos > T::zero() {
+                for i in 0..3 {
+                    illum[i] = illum[i] + emission[i] * cos / (pi * pdf);
+                }
+            }
+        }
 
-        Rgb([illum_scaled[0], illum_scaled[1], illum_scaled[2]])
+        let diffuse = hit.material.diffuse(&hit.point, hit.uv);
+        [
+            illum[0] * diffuse[0],
+            illum[1] * diffuse[1],
+            illum[2] * diffuse[2],
+        ]
     }
 
-    fn trace_and_illuminate(&self, world_origin: Vec4<T>, target: Vec4<T>) -> Rgb<u8> {
-        let world_target = &self.view * &target;
-        let world_direction = (&world_target - &world_origin).normalized();
-        let hit = self.trace_ray(&world_origin, &world_direction);
+    /// Light arriving at a surface with a `Bsdf` and leaving back along `ray`:
+    /// straight from lights that give an `incident` direction, from `ambient`
+    /// lights, from a direction picked by each light that can pick one and
+    /// from a point on a glowing object, and from whatever one direction picked
+    /// by the BSDF leads to, `bounces` deep. Lights and glowing objects can be
+    /// found both ways, so each is weighted by how likely it was to find them.
+    fn scatter(
+        &self,
+        bsdf: &dyn Bsdf<T>,
+        rng: &mut Rng,
+        sampler: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
+        ray: &Ray<T>,
+        bounces: u32,
+    ) -> [T; 3] {
+        // The BSDF wants the normal facing out of the surface, whichever side
+        // the ray arrived from
+        let normal = if hit.front_face {
+            hit.shading_normal()
+        } else {
+            hit.shading_normal().reverse()
+        };
+        let frame = Frame::new(&normal, &hit.tangent);
+        let wo = frame.to_local(&ray.direction.reverse());
+        let mut total = [T::zero(); 3];
 
-        match hit {
-            TraceResult::Miss => image::Rgb([0, 0, 0]),
-            TraceResult::Hit(point, object) => self.illuminate(&point, object),
+        for l in self.lights.iter() {
+            if let Some((to_light, distance, light)) = l.incident(&hit.point) {
+                let wi = frame.to_local(&to_light);
+                let f = bsdf.evaluate(&wo, &wi);
+                let epsilon: T = FromPrimitive::from_f64(1e-4).unwrap();
+                let shadow = Ray::segment(hit.point, to_light, epsilon, distance - epsilon);
+                let through = self.visibility(rng, &shadow, ray.time);
+                for c in 0..3 {
+                    total[c] = total[c] + f[c] * light[c] * wi.z.abs() * through;
+                }
+            }
+
+            if let Some((direction, distance, light, pdf)) =
+                l.sample_incident(&hit.point, sampler.next_2d())
+            {
+                let wi = frame.to_local(&direction);
+                let f = bsdf.evaluate(&wo, &wi);
+                let epsilon: T = FromPrimitive::from_f64(1e-4).unwrap();
+                let shadow = Ray::segment(hit.point, direction, epsilon, distance - epsilon);
+                let share = if bounces < self.max_bounces {
+                    power_heuristic(pdf, bsdf.pdf(&wo, &wi))
+                } else {
+                    T::one()
+                };
+                let weight = share * wi.z.abs() * self.visibility(rng, &shadow, ray.time) / pdf;
+                for c in 0..3 {
+                    total[c] = total[c] + f[c] * light[c] * weight;
+                }
+            }
         }
+
+        // Light from all around is reflected as much as the BSDF reflects
+        // overall, estimated from one direction it picks
+        if self.lights.iter().any(|l| l.ambient()) {
+            let reflectance = match bsdf.sample(&wo, sampler.next_1d(), sampler.next_2d()) {
+                Some(s) if s.pdf > T::zero() => s.value.map(|v| v * s.wi.z.abs() / s.pdf),
+                _ => [T::zero(); 3],
+            };
+            let scene = Moment {
+                engine: self,
+                time: ray.time,
+            };
+            let eye = Vec4::direction(T::zero(), T::zero(), T::zero());
+            for l in self.lights.iter().filter(|l| l.ambient()) {
+                let light = l.illuminate(&scene, sampler, hit, &eye);
+                for c in 0..3 {
+                    total[c] = total[c] + reflectance[c] * light[c];
+                }
+            }
+        }
+
+        if let Some((direction, emission, pdf)) =
+            self.sample_emitter(rng, sampler, &hit.point, ray.time)
+        {
+            let wi = frame.to_local(&direction);
+            let f = bsdf.evaluate(&wo, &wi);
+            // Without a bounce to find it, this is the only way to
+            let share = if bounces < self.max_bounces {
+                power_heuristic(pdf, bsdf.pdf(&wo, &wi))
+            } else {
+                T::one()
+            };
+            let weight = share * wi.z.abs() / pdf;
+            for c in 0..3 {
+                total[c] = total[c] + f[c] * emission[c] * weight;
+            }
+        }
+
+        if bounces < self.max_bounces {
+            let choice = sampler.next_1d();
+            let u = sampler.next_2d();
+            if let Some(s) = bsdf.sample(&wo, choice, u) {
+                let epsilon: T = FromPrimitive::from_f64(1e-4).unwrap();
+                let next = Ray {
+                    origin: hit.point,
+                    direction: frame.to_world(&s.wi),
+                    t_min: epsilon,
+                    t_max: T::infinity(),
+                    ..*ray
+                };
+                let incoming = self.trace_path(rng, sampler, &next, bounces + 1, Some(s.pdf));
+                let weight = s.wi.z.abs() / s.pdf;
+                for c in 0..3 {
+                    total[c] = total[c] + s.value[c] * incoming[c] * weight;
+                }
+            }
+        }
+
+        total
     }
 
-    pub fn render(&self, width: u32, height: u32) -> image::RgbImage {
-        let mut image = image::RgbImage::new(width, height);
-        image.put_pixel(0, 0, image::Rgb([255, 255, 255]));
+    /// The colour seen along `ray`, after it has already bounced `bounces`
+    /// times. `scattered_pdf` is the density with which a `Bsdf` or a medium
+    /// picked the ray, if one did.
+    fn trace_path(
+        &self,
+        rng: &mut Rng,
+        sampler: &mut dyn Sampler<T>,
+        ray: &Ray<T>,
+        bounces: u32,
+        scattered_pdf: Option<T>,
+    ) -> [T; 3] {
+        // Anything found before the ray runs into a medium is seen instead
+        let scattered = self.sample_medium(rng, ray);
+        let visible = match scattered {
+            Some((t, _)) => ray.clipped(t),
+            None => *ray,
+        };
+
+        match self.trace_ray(&visible) {
+            TraceResult::Miss => match scattered {
+                Some((t, medium)) => self.in_scatter(rng, sampler, ray, t, medium, bounces),
+                None => self.background.colour(&ray.direction),
+            },
+            TraceResult::Hit(hit, object) => {
+                let mut colour = if hit.front_face {
+                    hit.material.emission()
+                } else {
+                    [T::zero(); 3]
+                };
+                if let Some(pdf) = scattered_pdf {
+                    if hit.material.is_emissive() {
+                        let weight = power_heuristic(pdf, self.emitter_pdf(object, &hit, ray));
+                        colour = colour.map(|c| c * weight);
+                    }
+                }
+
+                let reflected = match hit.material.bsdf() {
+                    Some(bsdf) => self.scatter(bsdf, rng, sampler, &hit, ray, bounces),
+                    None => self.illuminate(rng, sampler, &hit, ray.time),
+                };
+                [0, 1, 2].map(|c| colour[c] + reflected[c])
+            }
+            TraceResult::Emitter(colour, light) => match scattered_pdf {
+                Some(pdf) => {
+                    let light_pdf = self.lights[light].incident_pdf(&ray.origin, &ray.direction);
+                    let weight = power_heuristic(pdf, light_pdf);
+                    colour.map(|c| c * weight)
+                }
+                None => colour,
+            },
+        }
+    }
+
+    fn trace_and_illuminate(
+        &self,
+        rng: &mut Rng,
+        sampler: &mut dyn Sampler<T>,
+        ray: &Ray<T>,
+    ) -> [T; 3] {
+        self.trace_path(rng, sampler, ray, 0, None)
+    }
+
+    /// Where the camera is at `time`
+    fn view_at(&self, time: T) -> Mat4<T> {
+        match &self.camera_motion {
+            Some(motion) => &motion.at(time) * &self.view,
+            None => self.view,
+        }
+    }
+
+    /// The ray from the eye through `target`, on the image plane, at `time`
+    fn camera_ray(&self, origin: &Vec4<T>, target: &Vec4<T>, time: T) -> Ray<T> {
+        let view = self.view_at(time);
+        let world_origin = &view * origin;
+        let world_target = &view * target;
+        let world_direction = (&world_target - &world_origin).normalized();
+        Ray::new(world_origin, world_direction).with_time(time)
+    }
+
+    /// Fails if the image is empty, or if any pixel comes out as not-a-number.
+    /// For progress reports or to be able to stop part way, use a
+    /// `RenderSession` instead.
+    pub fn render(&self, width: u32, height: u32) -> Result<image::RgbImage> {
+        RenderSession::new(self, width, height).run()
+    }
+
+    /// Rays traced so far, counting both camera and shadow rays
+    pub fn rays_cast(&self) -> u64 {
+        self.rays_cast.load(Ordering::Relaxed)
+    }
+
+    /// How pixels of an image `width` by `height` map onto the image plane
+    pub(crate) fn film(&self, width: u32, height: u32) -> Result<Film<T>> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidScene(format!(
+                "can't render a {}x{} image",
+                width, height
+            )));
+        }
 
         let two: T = FromPrimitive::from_f64(2.0).unwrap();
         let hfov: T = FromPrimitive::from_f64(90.0).unwrap();
 
         let distance = T::one() / (T::tan(hfov / two));
-        let origin = Vec4::position(T::zero(), T::zero(), -distance);
-        let world_origin = &self.view * &origin;
-
         let fwidth: T = FromPrimitive::from_u32(width).unwrap();
         let fheight: T = FromPrimitive::from_u32(height).unwrap();
 
-        let fx_scale: T;
-        let fy_scale: T;
-        let fx_origin: T;
-        let fy_origin: T;
-
-        if fwidth > fheight {
-            fx_scale = two / (fheight - T::one());
-            fy_scale = two / (fheight - T::one());
-            fx_origin = -(fwidth / fheight);
-            fy_origin = T::one();
+        // The shorter side of the image spans -1 to 1
+        let film = if fwidth > fheight {
+            Film {
+                origin: Vec4::position(T::zero(), T::zero(), -distance),
+                scale: two / (fheight - T::one()),
+                x_origin: -(fwidth / fheight),
+                y_origin: T::one(),
+            }
         } else {
-            fx_scale = two / (fwidth - T::one());
-            fy_scale = two / (fwidth - T::one());
-            fx_origin = -T::one();
-            fy_origin = fheight / fwidth;
+            Film {
+                origin: Vec4::position(T::zero(), T::zero(), -distance),
+                scale: two / (fwidth - T::one()),
+                x_origin: -T::one(),
+                y_origin: fheight / fwidth,
+            }
+        };
+        Ok(film)
+    }
+
+    /// The colour of one pixel, averaged over all its samples, which `sampler`
+    /// spreads over the pixel and the exposure
+    pub(crate) fn render_pixel(
+        &self,
+        film: &Film<T>,
+        sampler: &mut dyn Sampler<T>,
+        x: u32,
+        y: u32,
+    ) -> Result<[T; 3]> {
+        let samples: T = FromPrimitive::from_u32(self.samples).unwrap();
+
+        // Seed per pixel so renders are repeatable, whatever order pixels are done in
+        let mut rng = Rng::new(((y as u64) << 32) | x as u64);
+        let mut total = [T::zero(); 3];
+
+        for i in 0..self.samples {
+            sampler.start_sample(x, y, i);
+            // One sample stays in the middle of the pixel, but still
+            // spread through the exposure
+            let colour = self.render_sample(film, x, y, &mut rng, sampler, self.samples > 1);
+            for c in 0..3 {
+                total[c] = total[c] + colour[c];
+            }
         }
 
+        if total.iter().any(|c| c.is_nan()) {
+            return Err(Error::NanRadiance { x, y });
+        }
+        Ok([total[0] / samples, total[1] / samples, total[2] / samples])
+    }
+
+    /// The colour seen along a single ray through the pixel, at a time in the
+    /// exposure picked by `sampler`; through a point in the pixel it picks too
+    /// if `jitter`, otherwise through its middle. `sampler` should already be
+    /// started on the sample.
+    pub(crate) fn render_sample(
+        &self,
+        film: &Film<T>,
+        x: u32,
+        y: u32,
+        rng: &mut Rng,
+        sampler: &mut dyn Sampler<T>,
+        jitter: bool,
+    ) -> [T; 3] {
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        // Drawn either way, so the same dimensions go to the same choices
+        let (u, v) = sampler.next_2d();
+        let time = sampler.next_1d();
+        let (jx, jy) = if jitter {
+            (u - half, v - half)
+        } else {
+            (T::zero(), T::zero())
+        };
+
+        let ray = self.pixel_ray(film, x, y, jx, jy, time);
+        self.trace_and_illuminate(rng, sampler, &ray)
+    }
+
+    /// The ray through the pixel at `time`, offset from its middle by (`jx`, `jy`)
+    /// pixels
+    fn pixel_ray(&self, film: &Film<T>, x: u32, y: u32, jx: T, jy: T, time: T) -> Ray<T> {
+        let fx: T = FromPrimitive::from_u32(x).unwrap();
+        let fy: T = FromPrimitive::from_u32(y).unwrap();
+        let target = Vec4::position(
+            film.x_origin + (fx + jx) * film.scale,
+            film.y_origin - (fy + jy) * film.scale,
+            T::zero(),
+        );
+
+        self.camera_ray(&film.origin, &target, time)
+    }
+
+    /// The albedo, normal and depth of whatever is seen through the middle of
+    /// each pixel, halfway through the exposure, to guide a `Denoiser`. Lights and
+    /// the background count as albedo with nothing behind them.
+    pub fn render_guides(&self, width: u32, height: u32) -> Result<Guides<T>> {
+        let film = self.film(width, height)?;
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        let nothing = Vec4::direction(T::zero(), T::zero(), T::zero());
+        let mut guides = Guides::new(width, height);
+
         for y in 0..height {
             for x in 0..width {
-                let mut fx = FromPrimitive::from_u32(x).unwrap();
-                let mut fy = FromPrimitive::from_u32(y).unwrap();
-                fx = fx_origin + fx * fx_scale;
-                fy = fy_origin - fy * fy_scale;
-                let target = Vec4::position(fx, fy, T::zero());
-                image.put_pixel(x, y, self.trace_and_illuminate(world_origin, target));
+                let ray = self.pixel_ray(&film, x, y, T::zero(), T::zero(), half);
+                match self.trace_ray(&ray) {
+                    TraceResult::Hit(hit, _) => guides.set(
+                        x,
+                        y,
+                        hit.material.diffuse(&hit.point, hit.uv),
+                        &hit.shading_normal(),
+                        hit.t,
+                    ),
+                    TraceResult::Emitter(colour, _) => {
+                        guides.set(x, y, colour, &nothing, T::infinity())
+                    }
+                    TraceResult::Miss => guides.set(
+                        x,
+                        y,
+                        self.background.colour(&ray.direction),
+                        &nothing,
+                        T::infinity(),
+                    ),
+                }
             }
         }
 
-        image
+        Ok(guides)
+    }
+}
+
+/// How much to trust a sample picked with density `chosen` over one picked
+/// another way with density `other`, for combining the two
+fn power_heuristic<T: Float>(chosen: T, other: T) -> T {
+    let (a, b) = (chosen * chosen, other * other);
+    if a + b == T::zero() || a.is_infinite() {
+        return T::one();
+    }
+    a / (a + b)
+}
+
+impl<T> Scene<T> for Engine<T>
+where
+    T: Float + FromPrimitive + std::fmt::Debug,
+{
+    fn occluded(&self, ray: &Ray<T>) -> bool {
+        self.rays_cast.fetch_add(1, Ordering::Relaxed);
+        self.objects
+            .iter()
+            .any(|o| matches!(o.intersect(ray), IntersectResult::Intersect(_)))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::bsdf::conductor::Conductor;
+    use crate::bsdf::lambert::Lambert;
+    use crate::colour;
+    use crate::light::ambientlight::AmbientLight;
+    use crate::light::ambientocclusion::AmbientOcclusionLight;
+    use crate::light::arealight::AreaLight;
+    use crate::light::environmentlight::EnvironmentLight;
+    use crate::light::pointlight::PointLight;
+    use crate::light::spotlight::SpotLight;
+    use crate::material::Material;
+    use crate::medium::homogeneous::Homogeneous;
+    use crate::medium::volume::Volume;
+    use crate::motion::Pose;
+    use crate::object::instance::Instance;
+    use crate::object::mesh::Mesh;
+    use crate::object::moving::Moving;
+    use crate::object::sphere::Sphere;
+    use crate::sampler::independent::Independent;
+    use std::rc::Rc;
     #[test]
     fn construct() {
         let view = Mat4::i();
         let _: Engine<f64> = Engine::new(view);
     }
+
+    struct NanLight;
+
+    impl Light<f64> for NanLight {
+        fn illuminate(
+            &self,
+            _: &dyn Scene<f64>,
+            _: &mut dyn Sampler<f64>,
+            _: &HitRecord<f64>,
+            _: &Vec4<f64>,
+        ) -> [f64; 3] {
+            [f64::NAN; 3]
+        }
+    }
+
+    #[test]
+    fn render_errors() {
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        assert!(matches!(engine.render(0, 10), Err(Error::InvalidScene(_))));
+
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 5.0), 1.0).unwrap(),
+        ));
+        engine.add_light(Box::new(NanLight));
+        // The background is fine, but not the sphere in the middle
+        assert!(matches!(
+            engine.render(3, 3),
+            Err(Error::NanRadiance { x: 1, y: 1 })
+        ));
+    }
+
+    #[test]
+    fn occlusion() {
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap(),
+        ));
+
+        let origin = Vec4::position(0.0, 0.0, -10.0);
+        let towards = Vec4::direction(0.0, 0.0, 1.0);
+        let away = Vec4::direction(0.0, 0.0, -1.0);
+
+        assert!(engine.occluded(&Ray::segment(origin, towards, 0.0, 20.0)));
+        assert!(!engine.occluded(&Ray::segment(origin, towards, 0.0, 5.0)));
+        assert!(!engine.occluded(&Ray::segment(origin, away, 0.0, 20.0)));
+        // Starting beyond the sphere
+        assert!(!engine.occluded(&Ray::segment(origin, towards, 12.0, 20.0)));
+    }
+
+    #[test]
+    fn nearest_hit() {
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 5.0), 1.0).unwrap(),
+        ));
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap(),
+        ));
+
+        let origin = Vec4::position(0.0, 0.0, -10.0);
+        let direction = Vec4::direction(0.0, 0.0, 1.0);
+
+        match engine.trace_ray(&Ray::new(origin, direction)) {
+            TraceResult::Hit(hit, _) => assert_eq!(Vec4::position(0.0, 0.0, -1.0), hit.point),
+            _ => panic!("expected to hit the nearer sphere"),
+        }
+    }
+
+    #[test]
+    fn lights_are_visible() {
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 5.0), 1.0).unwrap(),
+        ));
+        engine.add_light(Box::new(
+            AreaLight::sphere(
+                Vec4::position(0.0, 0.0, 0.0),
+                1.0,
+                image::Rgb([255, 255, 255]),
+                1,
+            )
+            .unwrap(),
+        ));
+
+        let origin = Vec4::position(0.0, 0.0, -10.0);
+        let direction = Vec4::direction(0.0, 0.0, 1.0);
+
+        match engine.trace_ray(&Ray::new(origin, direction)) {
+            TraceResult::Emitter(colour, _) => assert_eq!([1.0, 1.0, 1.0], colour),
+            _ => panic!("expected to see the light"),
+        }
+    }
+
+    #[test]
+    fn environment_behind_objects() {
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap(),
+        ));
+        engine.add_light(Box::new(
+            EnvironmentLight::new(1, 1, vec![[0.5, 0.5, 0.5]], 1).unwrap(),
+        ));
+
+        let origin = Vec4::position(0.0, 0.0, -10.0);
+
+        match engine.trace_ray(&Ray::new(origin, Vec4::direction(0.0, 0.0, 1.0))) {
+            TraceResult::Hit(..) => (),
+            _ => panic!("expected to hit the sphere"),
+        }
+
+        match engine.trace_ray(&Ray::new(origin, Vec4::direction(0.0, 1.0, 0.0))) {
+            TraceResult::Emitter(colour, _) => assert_eq!([0.5, 0.5, 0.5], colour),
+            _ => panic!("expected to see the environment"),
+        }
+    }
+
+    #[test]
+    fn lambert_lit_by_the_environment() {
+        // Found by sampling the environment with no bounces, and by both that
+        // and bounces with them, a uniform sky lights a white surface evenly
+        for bounces in [0, 4] {
+            let mut sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+            let mut material = Material::default();
+            material.set_bsdf(Box::new(Lambert::new(image::Rgb([255, 255, 255]))));
+            sphere.set_material(material);
+
+            let mut engine: Engine<f64> = Engine::new(Mat4::i());
+            engine.set_max_bounces(bounces);
+            engine.add_object(Box::new(sphere));
+            engine.add_light(Box::new(
+                EnvironmentLight::new(16, 8, vec![[0.5, 0.5, 0.5]; 16 * 8], 1).unwrap(),
+            ));
+
+            let ray = Ray::new(
+                Vec4::position(0.0, 0.0, -10.0),
+                Vec4::direction(0.0, 0.0, 1.0),
+            );
+            let colour = average(&engine, &ray);
+            assert!((colour[0] - 0.5).abs() < 0.05, "{} {:?}", bounces, colour);
+        }
+    }
+
+    #[test]
+    fn material_colour() {
+        let mut sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+        sphere.set_material(Material::colour(image::Rgb([255, 0, 0])));
+
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(sphere));
+        engine.add_light(Box::new(AmbientLight::new(image::Rgb([255, 255, 255]))));
+
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        let colour = engine.trace_and_illuminate(&mut Rng::new(0), &mut Independent::new(0), &ray);
+        assert_eq!(image::Rgb([255, 0, 0]), colour::to_rgb(colour));
+    }
+
+    #[test]
+    fn lambert_matches_plain_material() {
+        let engine_with = |material: Material<f64>| {
+            let mut sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+            sphere.set_material(material);
+            let mut engine: Engine<f64> = Engine::new(Mat4::i());
+            engine.add_object(Box::new(sphere));
+            engine.add_light(Box::new(PointLight::new(Vec4::position(-5.0, 5.0, -10.0))));
+            engine
+        };
+        let plain = engine_with(Material::colour(image::Rgb([255, 128, 0])));
+        let mut material = Material::default();
+        material.set_bsdf(Box::new(Lambert::new(image::Rgb([255, 128, 0]))));
+        let lambert = engine_with(material);
+
+        // Bounces off into the black background add nothing
+        let ray = Ray::new(
+            Vec4::position(0.2, 0.3, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        let expected = plain.trace_and_illuminate(&mut Rng::new(0), &mut Independent::new(0), &ray);
+        let colour = lambert.trace_and_illuminate(&mut Rng::new(0), &mut Independent::new(0), &ray);
+        for c in 0..3 {
+            assert!((colour[c] - expected[c]).abs() < 1e-9, "{:?}", colour);
+        }
+    }
+
+    #[test]
+    fn lambert_lit_by_ambient_lights() {
+        let engine_with = |material: Material<f64>| {
+            let mut sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+            sphere.set_material(material);
+            let mut engine: Engine<f64> = Engine::new(Mat4::i());
+            engine.add_object(Box::new(sphere));
+            engine.add_light(Box::new(AmbientLight::new(image::Rgb([100, 100, 100]))));
+            engine.add_light(Box::new(AmbientOcclusionLight::new(
+                image::Rgb([100, 100, 100]),
+                1.0,
+                8,
+            )));
+            engine
+        };
+        let plain = engine_with(Material::colour(image::Rgb([255, 255, 255])));
+        let mut material = Material::default();
+        material.set_bsdf(Box::new(Lambert::new(image::Rgb([255, 255, 255]))));
+        let lambert = engine_with(material);
+
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        let expected = plain.trace_and_illuminate(&mut Rng::new(0), &mut Independent::new(0), &ray);
+        let colour = lambert.trace_and_illuminate(&mut Rng::new(0), &mut Independent::new(0), &ray);
+        assert_eq!(image::Rgb([200, 200, 200]), colour::to_rgb(expected));
+        for c in 0..3 {
+            assert!((colour[c] - expected[c]).abs() < 1e-9, "{:?}", colour);
+        }
+    }
+
+    #[test]
+    fn lambert_in_shadow() {
+        // A sphere between a point light and a Lambert floor shades the floor
+        // under it, but not off to the side
+        let mut floor = Mesh::new(
+            vec![
+                Vec4::position(-10.0, 0.0, -10.0),
+                Vec4::position(-10.0, 0.0, 10.0),
+                Vec4::position(10.0, 0.0, 10.0),
+                Vec4::position(10.0, 0.0, -10.0),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+        .unwrap();
+        let mut material = Material::default();
+        material.set_bsdf(Box::new(Lambert::new(image::Rgb([255, 255, 255]))));
+        floor.set_material(material);
+
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(floor));
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 2.5, 0.0), 1.0).unwrap(),
+        ));
+        engine.add_light(Box::new(PointLight::new(Vec4::position(0.0, 5.0, 0.0))));
+        engine.set_max_bounces(0);
+
+        let floor_at = |x: f64| {
+            let eye = Vec4::position(x, 1.0, -5.0);
+            let ray = Ray::new(eye, (&Vec4::position(x, 0.0, 0.0) - &eye).normalized());
+            engine.trace_and_illuminate(&mut Rng::new(0), &mut Independent::new(0), &ray)[0]
+        };
+        assert_eq!(0.0, floor_at(0.0));
+        assert!(floor_at(8.0) > 0.0);
+    }
+
+    #[test]
+    fn mirror_reflects_the_background() {
+        let mut sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+        let mut material = Material::default();
+        material.set_bsdf(Box::new(Conductor::aluminium(0.0)));
+        sphere.set_material(material);
+
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(sphere));
+        engine.set_background(Background::solid(image::Rgb([0, 0, 255])));
+
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        let colour = engine.trace_and_illuminate(&mut Rng::new(0), &mut Independent::new(0), &ray);
+        assert!(colour[2] > 0.9 && colour[0] == 0.0, "{:?}", colour);
+
+        // With no bounces there's nothing to reflect
+        engine.set_max_bounces(0);
+        assert_eq!(
+            [0.0; 3],
+            engine.trace_and_illuminate(&mut Rng::new(0), &mut Independent::new(0), &ray)
+        );
+    }
+
+    /// A floor facing up, lit by `light` hanging above it, and the average of
+    /// many looks at the middle of the floor
+    fn lit_floor(floor: Material<f64>, light: Box<dyn Intersectable<f64>>) -> [f64; 3] {
+        let mut mesh = Mesh::new(
+            vec![
+                Vec4::position(-10.0, 0.0, -10.0),
+                Vec4::position(-10.0, 0.0, 10.0),
+                Vec4::position(10.0, 0.0, 10.0),
+                Vec4::position(10.0, 0.0, -10.0),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+        .unwrap();
+        mesh.set_material(floor);
+
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(mesh));
+        engine.add_object(light);
+
+        let eye = Vec4::position(0.0, 1.0, -5.0);
+        let ray = Ray::new(eye, (&Vec4::position(0.0, 0.0, 0.0) - &eye).normalized());
+        let mut rng = Rng::new(3);
+        let mut sampler = Independent::new(3);
+        let n = 4000;
+        let mut total = [0.0; 3];
+        for i in 0..n {
+            sampler.start_sample(0, 0, i);
+            let colour = engine.trace_and_illuminate(&mut rng, &mut sampler, &ray);
+            for c in 0..3 {
+                total[c] += colour[c];
+            }
+        }
+        total.map(|t| t / n as f64)
+    }
+
+    fn glowing(strength: f64) -> Material<f64> {
+        let mut material = Material::default();
+        material.set_emission(image::Rgb([255, 255, 255]), strength);
+        material
+    }
+
+    #[test]
+    fn emissive_sphere() {
+        // A sphere of radius r, d away, lights a white floor facing it to
+        // emission * r^2 / d^2
+        let light = || {
+            let mut sphere = Sphere::new(Vec4::position(0.0, 3.0, 0.0), 0.5).unwrap();
+            sphere.set_material(glowing(36.0));
+            Box::new(sphere)
+        };
+
+        let plain = lit_floor(Material::default(), light());
+        assert!((plain[0] - 1.0).abs() < 0.03, "{:?}", plain);
+
+        // Found both by sampling the sphere and by bouncing off the floor
+        let mut lambert = Material::default();
+        lambert.set_bsdf(Box::new(Lambert::new(image::Rgb([255, 255, 255]))));
+        let lambert = lit_floor(lambert, light());
+        assert!((lambert[0] - 1.0).abs() < 0.03, "{:?}", lambert);
+
+        // And it's seen for what it is
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(light());
+        let ray = Ray::new(
+            Vec4::position(0.0, 3.0, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        assert_eq!(
+            [36.0; 3],
+            engine.trace_and_illuminate(&mut Rng::new(0), &mut Independent::new(0), &ray)
+        );
+    }
+
+    #[test]
+    fn mesh_light() {
+        // A small square facing down, which lights about as a point would
+        let mut square = Mesh::new(
+            vec![
+                Vec4::position(-0.25, 3.0, -0.25),
+                Vec4::position(0.25, 3.0, -0.25),
+                Vec4::position(0.25, 3.0, 0.25),
+                Vec4::position(-0.25, 3.0, 0.25),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+        .unwrap();
+        square.set_material(glowing(36.0 * std::f64::consts::PI));
+
+        let lit = lit_floor(Material::default(), Box::new(square));
+        assert!((lit[0] - 1.0).abs() < 0.03, "{:?}", lit);
+
+        // Facing away, it lights nothing
+        let mut square = Mesh::new(
+            vec![
+                Vec4::position(-0.25, 3.0, -0.25),
+                Vec4::position(0.25, 3.0, 0.25),
+                Vec4::position(0.25, 3.0, -0.25),
+            ],
+            vec![[0, 1, 2]],
+        )
+        .unwrap();
+        square.set_material(glowing(36.0));
+        assert_eq!([0.0; 3], lit_floor(Material::default(), Box::new(square)));
+    }
+
+    #[test]
+    fn instanced_light() {
+        // The same small square as a light, placed through an instance
+        let mut square = Mesh::new(
+            vec![
+                Vec4::position(-0.25, 0.0, -0.25),
+                Vec4::position(0.25, 0.0, -0.25),
+                Vec4::position(0.25, 0.0, 0.25),
+                Vec4::position(-0.25, 0.0, 0.25),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+        .unwrap();
+        square.set_material(glowing(36.0 * std::f64::consts::PI));
+        let placed = Instance::new(
+            Rc::new(square),
+            Mat4::translation(&Vec4::direction(0.0, 3.0, 0.0)),
+        )
+        .unwrap();
+
+        let lit = lit_floor(Material::default(), Box::new(placed));
+        assert!((lit[0] - 1.0).abs() < 0.03, "{:?}", lit);
+    }
+
+    /// The average colour seen along `ray`
+    fn average(engine: &Engine<f64>, ray: &Ray<f64>) -> [f64; 3] {
+        let mut rng = Rng::new(9);
+        let mut sampler = Independent::new(9);
+        let n = 4000;
+        let mut total = [0.0; 3];
+        for i in 0..n {
+            sampler.start_sample(0, 0, i);
+            let colour = engine.trace_and_illuminate(&mut rng, &mut sampler, ray);
+            for c in 0..3 {
+                total[c] += colour[c];
+            }
+        }
+        total.map(|t| t / n as f64)
+    }
+
+    #[test]
+    fn fog_absorbs() {
+        let mut sphere = Sphere::new(Vec4::position(0.0, 0.0, 10.0), 1.0).unwrap();
+        sphere.set_material(glowing(1.0));
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(sphere));
+        engine.add_medium(Box::new(Homogeneous::new(0.1, 0.0, 0.0)));
+
+        // 9 units of fog in front of the sphere
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        let seen = average(&engine, &ray);
+        assert!((seen[0] - (-0.9f64).exp()).abs() < 0.03, "{:?}", seen);
+    }
+
+    #[test]
+    fn god_rays() {
+        // A spotlight shining down through fog lights up a cone of it
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_light(Box::new(SpotLight::new(
+            Vec4::position(0.0, 5.0, 0.0),
+            Vec4::direction(0.0, -1.0, 0.0),
+            0.2,
+            0.4,
+        )));
+        engine.add_medium(Box::new(Homogeneous::new(0.0, 0.05, 0.3)));
+        engine.set_max_bounces(0);
+
+        let across = |x: f64| {
+            let ray = Ray::new(
+                Vec4::position(x, 0.0, -10.0),
+                Vec4::direction(0.0, 0.0, 1.0),
+            );
+            average(&engine, &ray)[0]
+        };
+        assert!(across(0.0) > 0.01, "{}", across(0.0));
+        assert_eq!(0.0, across(5.0));
+    }
+
+    #[test]
+    fn shadows_in_fog() {
+        // A ball of fog under a sphere is in its shadow, and one off to the side
+        // isn't
+        let fog_at = |x: f64| {
+            let mut engine: Engine<f64> = Engine::new(Mat4::i());
+            engine.add_light(Box::new(PointLight::new(Vec4::position(0.0, 5.0, 0.0))));
+            engine.add_object(Box::new(
+                Sphere::new(Vec4::position(0.0, 2.5, 0.0), 1.0).unwrap(),
+            ));
+            engine.add_medium(Box::new(Volume::new(
+                Box::new(Sphere::new(Vec4::position(x, 0.0, 0.0), 0.5).unwrap()),
+                Box::new(Homogeneous::new(0.0, 1.0, 0.0)),
+            )));
+            engine.set_max_bounces(0);
+
+            let ray = Ray::new(
+                Vec4::position(x, 0.0, -10.0),
+                Vec4::direction(0.0, 0.0, 1.0),
+            );
+            average(&engine, &ray)[0]
+        };
+
+        assert_eq!(0.0, fog_at(0.0));
+        let lit = fog_at(3.0);
+        // Single scattering in a ball of fog: the quarter of pi that an
+        // isotropic medium sends this way, times the share that scatters, and
+        // dimmed by up to the width of the ball again on the way to the light
+        let scattered = 0.25 * (1.0 - (-1.0f64).exp());
+        assert!(
+            lit < scattered && lit > scattered * (-1.0f64).exp(),
+            "{}",
+            lit
+        );
+    }
+
+    fn sliding_sphere() -> Moving<f64> {
+        Moving::new(
+            Box::new(Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap()),
+            Motion::new(
+                Pose::translation(Vec4::direction(-2.0, 0.0, 0.0)),
+                Pose::translation(Vec4::direction(2.0, 0.0, 0.0)),
+            ),
+        )
+    }
+
+    #[test]
+    fn shadows_at_the_same_time() {
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(sliding_sphere()));
+
+        // Straight down through where the sphere starts
+        let down = Ray::new(
+            Vec4::position(-2.0, 10.0, 0.0),
+            Vec4::direction(0.0, -1.0, 0.0),
+        );
+        let early = Moment {
+            engine: &engine,
+            time: Some(0.0),
+        };
+        let late = Moment {
+            engine: &engine,
+            time: Some(1.0),
+        };
+        assert!(early.occluded(&down));
+        assert!(!late.occluded(&down));
+        // A ray's own time wins
+        assert!(late.occluded(&down.with_time(0.0)));
+    }
+
+    #[test]
+    fn motion_blur() {
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.add_object(Box::new(sliding_sphere()));
+        engine.add_light(Box::new(AmbientLight::new(image::Rgb([255, 255, 255]))));
+        engine.set_samples(16);
+
+        // The middle of the sphere's path is covered for part of the exposure, so
+        // it comes out somewhere between the sphere and the background
+        let image = engine.render(9, 9).unwrap();
+        let middle = image.get_pixel(4, 4)[0];
+        assert!(middle > 0 && middle < 255, "{}", middle);
+    }
+
+    #[test]
+    fn guides() {
+        let mut sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 5.0).unwrap();
+        sphere.set_material(Material::colour(image::Rgb([255, 0, 0])));
+
+        let eye = Vec4::position(0.0, 0.0, -10.0);
+        let mut engine: Engine<f64> = Engine::new(Mat4::look(&eye, &Vec4::position(0.0, 0.0, 0.0)));
+        engine.add_object(Box::new(sphere));
+        let guides = engine.render_guides(9, 9).unwrap();
+
+        assert_eq!([1.0, 0.0, 0.0], guides.albedo(4, 4));
+        let normal = guides.normal(4, 4);
+        assert!((normal.z + 1.0).abs() < 1e-9, "{:?}", normal);
+        // Along the camera ray, to the near side of the sphere
+        let depth = guides.depth(4, 4);
+        assert!(depth > 4.0 && depth < 6.0, "{}", depth);
+
+        assert_eq!(f64::INFINITY, guides.depth(0, 0));
+        assert_eq!(0.0, guides.normal(0, 0).mag());
+    }
+
+    #[test]
+    fn camera_motion() {
+        let mut engine: Engine<f64> = Engine::new(Mat4::i());
+        engine.set_camera_motion(Motion::new(
+            Pose::identity(),
+            Pose::translation(Vec4::direction(3.0, 0.0, 0.0)),
+        ));
+
+        let origin = Vec4::position(0.0, 0.0, -1.0);
+        let target = Vec4::position(0.0, 0.0, 0.0);
+        let start = engine.camera_ray(&origin, &target, 0.0);
+        let end = engine.camera_ray(&origin, &target, 1.0);
+
+        assert_eq!(Vec4::position(0.0, 0.0, -1.0), start.origin);
+        assert_eq!(Vec4::position(3.0, 0.0, -1.0), end.origin);
+        assert_eq!(start.direction, end.direction);
+        assert_eq!(Some(1.0), end.time);
+    }
 }
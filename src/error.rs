@@ -0,0 +1,70 @@
+use std::fmt;
+use std::io;
+
+use image::ImageError;
+
+/// Everything that can go wrong building or rendering a scene
+#[derive(Debug)]
+pub enum Error {
+    /// Something in the scene makes no sense, such as a sphere with a negative
+    /// radius
+    InvalidScene(String),
+    /// A transform squashes space flat, so there's no undoing it to get back into
+    /// an object's own coordinates
+    SingularTransform,
+    /// A pixel came out as not-a-number, which usually means something degenerate
+    /// in the scene
+    NanRadiance {
+        x: u32,
+        y: u32,
+    },
+    /// A checkpoint file couldn't be understood, or doesn't fit the render
+    /// resuming from it
+    InvalidCheckpoint(String),
+    /// An image can't be used as asked, such as denoising it with guide buffers
+    /// of a different size
+    InvalidImage(String),
+    /// The render was stopped through its cancel token before it finished
+    Cancelled,
+    Io(io::Error),
+    Image(ImageError),
+}
+
+pub type Result<V> = std::result::Result<V, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidScene(reason) => write!(f, "invalid scene: {}", reason),
+            Error::SingularTransform => write!(f, "transform has no inverse"),
+            Error::NanRadiance { x, y } => write!(f, "pixel ({}, {}) is not a number", x, y),
+            Error::InvalidCheckpoint(reason) => write!(f, "invalid checkpoint: {}", reason),
+            Error::InvalidImage(reason) => write!(f, "invalid image: {}", reason),
+            Error::Cancelled => write!(f, "render cancelled"),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Image(e) => write!(f, "image error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(e: ImageError) -> Error {
+        Error::Image(e)
+    }
+}
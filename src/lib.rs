@@ -0,0 +1,38 @@
+//! A small raytracer. Build a scene by adding objects and lights to an [`Engine`],
+//! then render it to an image.
+
+pub mod background;
+pub mod bsdf;
+pub mod checkpoint;
+pub mod colour;
+pub mod denoise;
+pub mod engine;
+pub mod error;
+pub mod light;
+pub mod material;
+pub mod matrix;
+pub mod medium;
+pub mod motion;
+pub mod object;
+pub mod polynomial;
+pub mod progressive;
+pub mod random;
+pub mod ray;
+pub mod sampler;
+pub mod scene;
+pub mod session;
+pub mod texture;
+pub mod vector;
+
+pub use bsdf::Bsdf;
+pub use checkpoint::Checkpoint;
+pub use denoise::{Denoiser, FloatImage, Guides};
+pub use engine::Engine;
+pub use error::{Error, Result};
+pub use matrix::Mat4;
+pub use medium::Medium;
+pub use progressive::ProgressiveRender;
+pub use ray::Ray;
+pub use sampler::Sampler;
+pub use session::{CancelToken, Progress, RenderSession};
+pub use vector::Vec4;
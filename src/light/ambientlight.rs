@@ -2,7 +2,10 @@ use image::Rgb;
 use num::Float;
 use num::FromPrimitive;
 
-use crate::object::Intersectable;
+use crate::colour;
+use crate::object::HitRecord;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
 use crate::vector::Vec4;
 
 use super::Light;
@@ -16,14 +19,8 @@ where
     T: Float + FromPrimitive,
 {
     pub fn new(colour: Rgb<u8>) -> AmbientLight<T> {
-        let max_u8: T = FromPrimitive::from_u8(0xff).unwrap();
-
-        let r: T = FromPrimitive::from_u8(colour[0]).unwrap();
-        let g: T = FromPrimitive::from_u8(colour[1]).unwrap();
-        let b: T = FromPrimitive::from_u8(colour[2]).unwrap();
-
         AmbientLight {
-            colour: [r / max_u8, g / max_u8, b / max_u8],
+            colour: colour::from_rgb(colour),
         }
     }
 }
@@ -32,7 +29,17 @@ impl<T> Light<T> for AmbientLight<T>
 where
     T: Float,
 {
-    fn illuminate(&self, _: &dyn Intersectable<T>, _: &Vec4<T>, _: &Vec4<T>) -> [T; 3] {
+    fn illuminate(
+        &self,
+        _: &dyn Scene<T>,
+        _: &mut dyn Sampler<T>,
+        _: &HitRecord<T>,
+        _: &Vec4<T>,
+    ) -> [T; 3] {
         self.colour
     }
+
+    fn ambient(&self) -> bool {
+        true
+    }
 }
@@ -0,0 +1,145 @@
+use image::Rgb;
+use num::{Float, FromPrimitive};
+
+use crate::colour;
+use crate::object::HitRecord;
+use crate::random::cosine_hemisphere;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::vector::Vec4;
+
+use super::Light;
+
+/// Ambient light that is attenuated by nearby geometry: a number of rays are cast
+/// over the hemisphere above each hit point, and the colour is scaled by the
+/// fraction that escape to `radius` without hitting anything.
+pub struct AmbientOcclusionLight<T: Float> {
+    colour: [T; 3],
+    radius: T,
+    samples: u32,
+}
+
+impl<T> AmbientOcclusionLight<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn new(colour: Rgb<u8>, radius: T, samples: u32) -> AmbientOcclusionLight<T> {
+        AmbientOcclusionLight {
+            colour: colour::from_rgb(colour),
+            radius,
+            samples: u32::max(1, samples),
+        }
+    }
+}
+
+impl<T> Light<T> for AmbientOcclusionLight<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn illuminate(
+        &self,
+        scene: &dyn Scene<T>,
+        sampler: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
+        _: &Vec4<T>,
+    ) -> [T; 3] {
+        // Start the rays just clear of the surface so they don't hit it straight away
+        let epsilon: T = FromPrimitive::from_f64(1e-4).unwrap();
+
+        let mut unoccluded = 0;
+        for _ in 0..self.samples {
+            let (u, v) = sampler.next_2d();
+            let direction = cosine_hemisphere(&hit.shading_normal(), u, v);
+            if !scene.occluded(&Ray::segment(hit.point, direction, epsilon, self.radius)) {
+                unoccluded += 1;
+            }
+        }
+
+        let visible: T = FromPrimitive::from_u32(unoccluded).unwrap();
+        let samples: T = FromPrimitive::from_u32(self.samples).unwrap();
+        let scale = visible / samples;
+
+        [
+            self.colour[0] * scale,
+            self.colour[1] * scale,
+            self.colour[2] * scale,
+        ]
+    }
+
+    fn ambient(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::sampler::independent::Independent;
+    use crate::scene::tests::{Enclosed, Open};
+
+    #[test]
+    fn unoccluded_is_full_colour() {
+        let light = AmbientOcclusionLight::new(Rgb([255, 255, 255]), 1.0, 16);
+        let p = Vec4::position(0.0, 1.0, 0.0);
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+        let mut sampler = Independent::new(0);
+
+        let illum = light.illuminate(
+            &Open,
+            &mut sampler,
+            &HitRecord::at(p, up, &Material::default()),
+            &p,
+        );
+        assert_eq!([1.0, 1.0, 1.0], illum);
+    }
+
+    #[test]
+    fn occluded_is_black() {
+        let light = AmbientOcclusionLight::new(Rgb([255, 255, 255]), 1.0, 16);
+        let p = Vec4::position(0.0, 1.0, 0.0);
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+        let mut sampler = Independent::new(0);
+
+        let illum = light.illuminate(
+            &Enclosed,
+            &mut sampler,
+            &HitRecord::at(p, up, &Material::default()),
+            &p,
+        );
+        assert_eq!([0.0, 0.0, 0.0], illum);
+    }
+
+    /// Counts the pairs drawn from it
+    struct Counting(u32);
+
+    impl Sampler<f64> for Counting {
+        fn start_sample(&mut self, _: u32, _: u32, _: u32) {}
+
+        fn next_1d(&mut self) -> f64 {
+            0.5
+        }
+
+        fn next_2d(&mut self) -> (f64, f64) {
+            self.0 += 1;
+            (0.5, 0.5)
+        }
+    }
+
+    #[test]
+    fn directions_from_the_sampler() {
+        let light = AmbientOcclusionLight::new(Rgb([255, 255, 255]), 1.0, 16);
+        let p = Vec4::position(0.0, 1.0, 0.0);
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+        let mut sampler = Counting(0);
+
+        light.illuminate(
+            &Open,
+            &mut sampler,
+            &HitRecord::at(p, up, &Material::default()),
+            &p,
+        );
+        assert_eq!(16, sampler.0);
+    }
+}
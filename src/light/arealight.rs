@@ -0,0 +1,362 @@
+use image::Rgb;
+use num::{Float, FromPrimitive};
+
+use crate::colour;
+use crate::error::Result;
+use crate::object::sphere::Sphere;
+use crate::object::{HitRecord, IntersectResult, Intersectable};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::vector::Vec4;
+
+use super::Light;
+
+pub enum AreaShape<T: Float> {
+    /// Parallelogram spanned by two edges from a corner. Emits from both faces.
+    Rectangle {
+        corner: Vec4<T>,
+        edge_u: Vec4<T>,
+        edge_v: Vec4<T>,
+    },
+    /// Flat disc facing along `normal`. Emits from both faces.
+    Disc {
+        centre: Vec4<T>,
+        normal: Vec4<T>,
+        radius: T,
+    },
+    Sphere {
+        centre: Vec4<T>,
+        radius: T,
+        sphere: Sphere<T>,
+    },
+}
+
+/// A light with a physical extent. Each shading point takes a number of samples
+/// spread over the light's surface, giving shadows soft edges; the shape is also
+/// seen by camera rays that hit it directly.
+///
+/// Like `PointLight`, there is no falloff with distance.
+pub struct AreaLight<T: Float> {
+    shape: AreaShape<T>,
+    colour: [T; 3],
+    samples: u32,
+}
+
+impl<T> AreaLight<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    pub fn rectangle(
+        corner: Vec4<T>,
+        edge_u: Vec4<T>,
+        edge_v: Vec4<T>,
+        colour: Rgb<u8>,
+        samples: u32,
+    ) -> AreaLight<T> {
+        AreaLight::new(
+            AreaShape::Rectangle {
+                corner,
+                edge_u,
+                edge_v,
+            },
+            colour,
+            samples,
+        )
+    }
+
+    pub fn disc(
+        centre: Vec4<T>,
+        normal: Vec4<T>,
+        radius: T,
+        colour: Rgb<u8>,
+        samples: u32,
+    ) -> AreaLight<T> {
+        AreaLight::new(
+            AreaShape::Disc {
+                centre,
+                normal: normal.normalized(),
+                radius,
+            },
+            colour,
+            samples,
+        )
+    }
+
+    pub fn sphere(
+        centre: Vec4<T>,
+        radius: T,
+        colour: Rgb<u8>,
+        samples: u32,
+    ) -> Result<AreaLight<T>> {
+        Ok(AreaLight::new(
+            AreaShape::Sphere {
+                centre,
+                radius,
+                sphere: Sphere::new(centre, radius)?,
+            },
+            colour,
+            samples,
+        ))
+    }
+
+    fn new(shape: AreaShape<T>, colour: Rgb<u8>, samples: u32) -> AreaLight<T> {
+        AreaLight {
+            shape,
+            colour: colour::from_rgb(colour),
+            samples: u32::max(1, samples),
+        }
+    }
+
+    /// A point on the surface of the light, as seen from `from`
+    fn sample_point(&self, sampler: &mut dyn Sampler<T>, from: &Vec4<T>) -> Vec4<T> {
+        let (u, v) = sampler.next_2d();
+
+        match &self.shape {
+            AreaShape::Rectangle {
+                corner,
+                edge_u,
+                edge_v,
+            } => &(corner + &(edge_u * u)) + &(edge_v * v),
+
+            AreaShape::Disc {
+                centre,
+                normal,
+                radius,
+            } => {
+                let two_pi: T = FromPrimitive::from_f64(std::f64::consts::PI * 2.0).unwrap();
+                let (tangent, bitangent) = normal.basis();
+                let r = *radius * T::sqrt(u);
+                let phi = two_pi * v;
+                let offset = &(&tangent * (r * T::cos(phi))) + &(&bitangent * (r * T::sin(phi)));
+                centre + &offset
+            }
+
+            AreaShape::Sphere { centre, radius, .. } => {
+                // Uniform over the sphere, then flipped onto the hemisphere facing the
+                // shading point; the far side can't be seen from there anyway
+                let two: T = FromPrimitive::from_f64(2.0).unwrap();
+                let two_pi: T = FromPrimitive::from_f64(std::f64::consts::PI * 2.0).unwrap();
+                let z = T::one() - two * u;
+                let r = T::sqrt(T::max(T::zero(), T::one() - z * z));
+                let phi = two_pi * v;
+                let mut d = Vec4::direction(r * T::cos(phi), r * T::sin(phi), z);
+                if d.dot_product(&(from - centre)) < T::zero() {
+                    d = d.reverse();
+                }
+                centre + &(&d * *radius)
+            }
+        }
+    }
+
+    fn intersect_plane(ray: &Ray<T>, point: &Vec4<T>, normal: &Vec4<T>) -> Option<(T, Vec4<T>)> {
+        let denominator = ray.direction.dot_product(normal);
+        if denominator == T::zero() {
+            return None;
+        }
+
+        let t = (point - &ray.origin).dot_product(normal) / denominator;
+        if !ray.contains(t) {
+            return None;
+        }
+
+        Some((t, ray.at(t)))
+    }
+}
+
+impl<T> Light<T> for AreaLight<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    fn illuminate(
+        &self,
+        scene: &dyn Scene<T>,
+        sampler: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
+        _: &Vec4<T>,
+    ) -> [T; 3] {
+        let epsilon: T = FromPrimitive::from_f64(1e-4).unwrap();
+
+        let mut total = T::zero();
+        for _ in 0..self.samples {
+            let light_vec = &self.sample_point(sampler, &hit.point) - &hit.point;
+            let distance = light_vec.mag();
+            let direction = light_vec.normalized();
+
+            let illum = hit.shading_normal().dot_product(&direction);
+            if illum <= T::zero() {
+                continue;
+            }
+
+            let shadow = Ray::segment(hit.point, direction, epsilon, distance - epsilon);
+            if !scene.occluded(&shadow) {
+                total = total + illum;
+            }
+        }
+
+        let samples: T = FromPrimitive::from_u32(self.samples).unwrap();
+        let scale = total / samples;
+
+        [
+            self.colour[0] * scale,
+            self.colour[1] * scale,
+            self.colour[2] * scale,
+        ]
+    }
+
+    fn visible(&self, ray: &Ray<T>) -> Option<(T, [T; 3])> {
+        let t = match &self.shape {
+            AreaShape::Rectangle {
+                corner,
+                edge_u,
+                edge_v,
+            } => {
+                let normal = edge_u.cross_product(edge_v);
+                let (t, p) = Self::intersect_plane(ray, corner, &normal)?;
+
+                // Solve p - corner = a * edge_u + b * edge_v
+                let rel = &p - corner;
+                let n2 = normal.dot_product(&normal);
+                let a = rel.cross_product(edge_v).dot_product(&normal) / n2;
+                let b = edge_u.cross_product(&rel).dot_product(&normal) / n2;
+
+                let inside = |x: T| x >= T::zero() && x <= T::one();
+                if !inside(a) || !inside(b) {
+                    return None;
+                }
+                t
+            }
+
+            AreaShape::Disc {
+                centre,
+                normal,
+                radius,
+            } => {
+                let (t, p) = Self::intersect_plane(ray, centre, normal)?;
+                if (&p - centre).mag() > *radius {
+                    return None;
+                }
+                t
+            }
+
+            AreaShape::Sphere { sphere, .. } => match sphere.intersect(ray) {
+                IntersectResult::Intersect(hit) => hit.t,
+                IntersectResult::NoIntersect => return None,
+            },
+        };
+
+        Some((t, self.colour))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::sampler::independent::Independent;
+    use crate::scene::tests::{Enclosed, Open};
+
+    fn overhead_rectangle() -> AreaLight<f64> {
+        AreaLight::rectangle(
+            Vec4::position(-1.0, 5.0, -1.0),
+            Vec4::direction(2.0, 0.0, 0.0),
+            Vec4::direction(0.0, 0.0, 2.0),
+            Rgb([255, 255, 255]),
+            32,
+        )
+    }
+
+    #[test]
+    fn rectangle_visible() {
+        let light = overhead_rectangle();
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+
+        match light.visible(&Ray::new(Vec4::position(0.5, 0.0, 0.5), up)) {
+            Some((t, colour)) => {
+                assert!((t - 5.0).abs() < 1e-9);
+                assert_eq!([1.0, 1.0, 1.0], colour);
+            }
+            None => panic!("expected to see the light"),
+        }
+
+        assert!(light
+            .visible(&Ray::new(Vec4::position(1.5, 0.0, 0.5), up))
+            .is_none());
+        assert!(light
+            .visible(&Ray::new(Vec4::position(0.5, 0.0, 0.5), up.reverse()))
+            .is_none());
+    }
+
+    #[test]
+    fn disc_visible() {
+        let light = AreaLight::disc(
+            Vec4::position(0.0, 0.0, 5.0),
+            Vec4::direction(0.0, 0.0, -1.0),
+            1.0,
+            Rgb([255, 255, 255]),
+            1,
+        );
+        let fwd = Vec4::direction(0.0, 0.0, 1.0);
+
+        assert!(light
+            .visible(&Ray::new(Vec4::position(0.5, 0.5, 0.0), fwd))
+            .is_some());
+        assert!(light
+            .visible(&Ray::new(Vec4::position(0.9, 0.9, 0.0), fwd))
+            .is_none());
+    }
+
+    #[test]
+    fn sphere_visible() {
+        let light =
+            AreaLight::sphere(Vec4::position(0.0, 0.0, 5.0), 1.0, Rgb([255, 0, 0]), 1).unwrap();
+
+        match light.visible(&Ray::new(
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        )) {
+            Some((t, colour)) => {
+                assert!((t - 4.0).abs() < 1e-9);
+                assert_eq!([1.0, 0.0, 0.0], colour);
+            }
+            None => panic!("expected to see the light"),
+        }
+    }
+
+    #[test]
+    fn soft_shadows() {
+        let light = overhead_rectangle();
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+        let p = Vec4::position(0.0, 0.0, 0.0);
+        let mut sampler = Independent::new(0);
+
+        let lit = light.illuminate(
+            &Open,
+            &mut sampler,
+            &HitRecord::at(p, up, &Material::default()),
+            &p,
+        );
+        assert!(lit[0] > 0.9 && lit[0] <= 1.0);
+
+        let shadowed = light.illuminate(
+            &Enclosed,
+            &mut sampler,
+            &HitRecord::at(p, up, &Material::default()),
+            &p,
+        );
+        assert_eq!([0.0, 0.0, 0.0], shadowed);
+    }
+
+    #[test]
+    fn sphere_samples_face_the_shading_point() {
+        let light =
+            AreaLight::sphere(Vec4::position(0.0, 5.0, 0.0), 1.0, Rgb([255, 0, 0]), 1).unwrap();
+        let from = Vec4::position(0.0, 0.0, 0.0);
+        let mut sampler = Independent::new(1);
+
+        for _ in 0..100 {
+            let p = light.sample_point(&mut sampler, &from);
+            assert!(p.y <= 5.0);
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use num::{Float, FromPrimitive};
 
-use crate::{object::Intersectable, vector::Vec4};
+use crate::{object::HitRecord, sampler::Sampler, scene::Scene, vector::Vec4};
 
 use super::Light;
 
@@ -20,6 +20,11 @@ where
             direction_norm_inv: direction.normalized().reverse(),
         }
     }
+
+    /// The (unit) direction the light travels in
+    pub fn direction(&self) -> Vec4<T> {
+        self.direction_norm_inv.reverse()
+    }
 }
 
 impl<T> Light<T> for DirectionLight<T>
@@ -28,17 +33,21 @@ where
 {
     fn illuminate(
         &self,
-        object: &dyn Intersectable<T>,
-        hit_point: &Vec4<T>,
+        _: &dyn Scene<T>,
+        _: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
         _: &Vec4<T>,
     ) -> [T; 3] {
-        let norm = object.normal(hit_point).normalized();
-
-        let illum = norm.dot_product(&self.direction_norm_inv);
+        let illum = hit.shading_normal().dot_product(&self.direction_norm_inv);
         if illum < T::zero() {
             return [T::zero(), T::zero(), T::zero()];
         }
 
         [illum, illum, illum]
     }
+
+    fn incident(&self, _: &Vec4<T>) -> Option<(Vec4<T>, T, [T; 3])> {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        Some((self.direction_norm_inv, T::infinity(), [pi; 3]))
+    }
 }
@@ -0,0 +1,436 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::codecs::hdr::HdrDecoder;
+use num::{Float, FromPrimitive};
+
+use crate::colour::luminance;
+use crate::error::{Error, Result};
+use crate::object::HitRecord;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::vector::Vec4;
+
+use super::Light;
+
+/// Light arriving from infinitely far away in every direction, taken from an
+/// equirectangular (latitude/longitude) image. +y is up; the centre of the image
+/// looks down +z.
+///
+/// Rays that escape the scene see the image directly. For lighting, sample
+/// directions are chosen in proportion to the brightness of the image so that
+/// small, bright features like the sun are found quickly, both for plain
+/// materials and, through `sample_incident`, for surfaces with a `Bsdf` and
+/// the reflections in them.
+pub struct EnvironmentLight<T: Float> {
+    width: usize,
+    height: usize,
+    pixels: Vec<[T; 3]>,
+    samples: u32,
+
+    /// Per row, the cumulative distribution over the columns (width + 1 entries)
+    conditional_cdf: Vec<Vec<T>>,
+    /// Cumulative distribution over the rows (height + 1 entries)
+    marginal_cdf: Vec<T>,
+    /// Sum of all the sampling weights; zero for an entirely black image
+    total_weight: T,
+}
+
+impl<T> EnvironmentLight<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Load a Radiance `.hdr` file
+    pub fn load<P: AsRef<Path>>(path: P, samples: u32) -> Result<EnvironmentLight<T>> {
+        let reader = BufReader::new(File::open(path)?);
+        let decoder = HdrDecoder::new(reader)?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()?
+            .iter()
+            .map(|p| {
+                [
+                    FromPrimitive::from_f32(p[0]).unwrap(),
+                    FromPrimitive::from_f32(p[1]).unwrap(),
+                    FromPrimitive::from_f32(p[2]).unwrap(),
+                ]
+            })
+            .collect();
+
+        EnvironmentLight::new(
+            metadata.width as usize,
+            metadata.height as usize,
+            pixels,
+            samples,
+        )
+    }
+
+    /// `pixels` are in row-major order, top row first. Fails if the image is
+    /// empty or `pixels` doesn't fill it.
+    pub fn new(
+        width: usize,
+        height: usize,
+        pixels: Vec<[T; 3]>,
+        samples: u32,
+    ) -> Result<EnvironmentLight<T>> {
+        if width == 0 || height == 0 || width * height != pixels.len() {
+            return Err(Error::InvalidScene(format!(
+                "a {}x{} environment can't have {} pixels",
+                width,
+                height,
+                pixels.len()
+            )));
+        }
+
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        let fheight: T = FromPrimitive::from_usize(height).unwrap();
+
+        let mut conditional_cdf = Vec::with_capacity(height);
+        let mut marginal_cdf = Vec::with_capacity(height + 1);
+        marginal_cdf.push(T::zero());
+
+        for y in 0..height {
+            // Rows near the poles cover less solid angle, so weight them down
+            let fy: T = FromPrimitive::from_usize(y).unwrap();
+            let sin_theta = T::sin(pi * (fy + half) / fheight);
+
+            let mut cdf = Vec::with_capacity(width + 1);
+            cdf.push(T::zero());
+            for x in 0..width {
+                let weight = luminance(&pixels[y * width + x]) * sin_theta;
+                cdf.push(cdf[x] + weight);
+            }
+
+            marginal_cdf.push(marginal_cdf[y] + cdf[width]);
+            conditional_cdf.push(cdf);
+        }
+
+        let total_weight = marginal_cdf[height];
+
+        Ok(EnvironmentLight {
+            width,
+            height,
+            pixels,
+            samples: u32::max(1, samples),
+            conditional_cdf,
+            marginal_cdf,
+            total_weight,
+        })
+    }
+
+    /// The colour seen looking along `direction`
+    pub fn lookup(&self, direction: &Vec4<T>) -> [T; 3] {
+        let (u, v) = Self::direction_to_uv(&direction.normalized());
+        let (x, y) = self.texel(u, v);
+        self.pixels[y * self.width + x]
+    }
+
+    fn texel(&self, u: T, v: T) -> (usize, usize) {
+        let fwidth: T = FromPrimitive::from_usize(self.width).unwrap();
+        let fheight: T = FromPrimitive::from_usize(self.height).unwrap();
+        let x = (u * fwidth).to_usize().unwrap_or(0);
+        let y = (v * fheight).to_usize().unwrap_or(0);
+        (
+            usize::min(x, self.width - 1),
+            usize::min(y, self.height - 1),
+        )
+    }
+
+    fn direction_to_uv(d: &Vec4<T>) -> (T, T) {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+
+        let u = half + T::atan2(d.x, d.z) / (two * pi);
+        let v = T::acos(T::max(-T::one(), T::min(T::one(), d.y))) / pi;
+        (u, v)
+    }
+
+    fn uv_to_direction(u: T, v: T) -> Vec4<T> {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+
+        let phi = (u - half) * two * pi;
+        let theta = v * pi;
+        Vec4::direction(
+            T::sin(theta) * T::sin(phi),
+            T::cos(theta),
+            T::sin(theta) * T::cos(phi),
+        )
+    }
+
+    /// Index of the bucket of `cdf` that `target` falls into, and how far
+    /// through the bucket it lies, from 0 to 1
+    fn find_bucket(cdf: &[T], target: T) -> (usize, T) {
+        // First entry strictly greater than target, less one, skipping empty buckets
+        let upper = cdf.partition_point(|c| *c <= target);
+        let bucket = usize::min(usize::max(upper, 1), cdf.len() - 1) - 1;
+
+        let width = cdf[bucket + 1] - cdf[bucket];
+        let along = if width > T::zero() {
+            (target - cdf[bucket]) / width
+        } else {
+            T::zero()
+        };
+        (bucket, along.max(T::zero()).min(T::one() - T::epsilon()))
+    }
+
+    /// Choose a direction with probability proportional to the image brightness,
+    /// picked by `u`: the first value chooses the row and the second the column,
+    /// and what's left of each the point within the texel. Returns the
+    /// direction and its probability density over solid angle.
+    fn sample(&self, u: (T, T)) -> Option<(Vec4<T>, T)> {
+        if self.total_weight <= T::zero() {
+            return None;
+        }
+
+        let fwidth: T = FromPrimitive::from_usize(self.width).unwrap();
+        let fheight: T = FromPrimitive::from_usize(self.height).unwrap();
+
+        let (y, along_y) = Self::find_bucket(&self.marginal_cdf, u.0 * self.total_weight);
+
+        let row = &self.conditional_cdf[y];
+        let (x, along_x) = Self::find_bucket(row, u.1 * row[self.width]);
+
+        // Anywhere within the chosen texel
+        let fx: T = FromPrimitive::from_usize(x).unwrap();
+        let fy: T = FromPrimitive::from_usize(y).unwrap();
+        let u = (fx + along_x) / fwidth;
+        let v = (fy + along_y) / fheight;
+
+        let pdf = self.texel_pdf(x, y, v);
+        if pdf <= T::zero() {
+            return None;
+        }
+        Some((Self::uv_to_direction(u, v), pdf))
+    }
+
+    /// The density over solid angle with which `sample` picks a direction `v`
+    /// down the image within texel (`x`, `y`)
+    fn texel_pdf(&self, x: usize, y: usize, v: T) -> T {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let fwidth: T = FromPrimitive::from_usize(self.width).unwrap();
+        let fheight: T = FromPrimitive::from_usize(self.height).unwrap();
+
+        let sin_theta = T::sin(v * pi);
+        if self.total_weight <= T::zero() || sin_theta <= T::zero() {
+            return T::zero();
+        }
+
+        let row = &self.conditional_cdf[y];
+        let weight = row[x + 1] - row[x];
+        let pdf_uv = weight / self.total_weight * fwidth * fheight;
+        pdf_uv / (two * pi * pi * sin_theta)
+    }
+}
+
+impl<T> Light<T> for EnvironmentLight<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn illuminate(
+        &self,
+        scene: &dyn Scene<T>,
+        sampler: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
+        _: &Vec4<T>,
+    ) -> [T; 3] {
+        let epsilon: T = FromPrimitive::from_f64(1e-4).unwrap();
+
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let mut total = [T::zero(); 3];
+
+        for _ in 0..self.samples {
+            let (direction, pdf) = match self.sample(sampler.next_2d()) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let cos_theta = hit.shading_normal().dot_product(&direction);
+            if cos_theta <= T::zero() || pdf <= T::zero() {
+                continue;
+            }
+
+            if scene.occluded(&Ray::segment(hit.point, direction, epsilon, T::infinity())) {
+                continue;
+            }
+
+            // Scaled by 1/pi so that a uniformly white environment lights a surface
+            // as brightly as a white AmbientLight would
+            let radiance = self.lookup(&direction);
+            let weight = cos_theta / (pdf * pi);
+            for i in 0..3 {
+                total[i] = total[i] + radiance[i] * weight;
+            }
+        }
+
+        let samples: T = FromPrimitive::from_u32(self.samples).unwrap();
+        [total[0] / samples, total[1] / samples, total[2] / samples]
+    }
+
+    fn visible(&self, ray: &Ray<T>) -> Option<(T, [T; 3])> {
+        if !ray.contains(T::infinity()) {
+            return None;
+        }
+        Some((T::infinity(), self.lookup(&ray.direction)))
+    }
+
+    fn sample_incident(&self, _: &Vec4<T>, u: (T, T)) -> Option<(Vec4<T>, T, [T; 3], T)> {
+        let (direction, pdf) = self.sample(u)?;
+        Some((direction, T::infinity(), self.lookup(&direction), pdf))
+    }
+
+    fn incident_pdf(&self, _: &Vec4<T>, direction: &Vec4<T>) -> T {
+        let (u, v) = Self::direction_to_uv(&direction.normalized());
+        let (x, y) = self.texel(u, v);
+        self.texel_pdf(x, y, v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::random::Rng;
+    use crate::sampler::independent::Independent;
+    use crate::scene::tests::Open;
+    use image::codecs::hdr::HdrEncoder;
+    use image::Rgb;
+
+    fn uniform(colour: [f64; 3], samples: u32) -> EnvironmentLight<f64> {
+        EnvironmentLight::new(16, 8, vec![colour; 16 * 8], samples).unwrap()
+    }
+
+    #[test]
+    fn uv_round_trip() {
+        for &(u, v) in &[(0.5, 0.5), (0.1, 0.3), (0.9, 0.8), (0.25, 0.1)] {
+            let d = EnvironmentLight::<f64>::uv_to_direction(u, v);
+            let (u2, v2) = EnvironmentLight::<f64>::direction_to_uv(&d);
+            assert!((u - u2).abs() < 1e-9);
+            assert!((v - v2).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            EnvironmentLight::<f64>::new(0, 0, vec![], 1),
+            Err(Error::InvalidScene(_))
+        ));
+        assert!(matches!(
+            EnvironmentLight::new(4, 2, vec![[1.0; 3]; 7], 1),
+            Err(Error::InvalidScene(_))
+        ));
+    }
+
+    #[test]
+    fn visible_at_infinity() {
+        let light = uniform([0.5, 0.25, 1.0], 1);
+        let (t, colour) = light
+            .visible(&Ray::new(
+                Vec4::position(0.0, 0.0, 0.0),
+                Vec4::direction(0.0, 1.0, 0.0),
+            ))
+            .unwrap();
+        assert_eq!(f64::INFINITY, t);
+        assert_eq!([0.5, 0.25, 1.0], colour);
+    }
+
+    #[test]
+    fn uniform_white_lights_fully() {
+        let light = uniform([1.0, 1.0, 1.0], 256);
+        let p = Vec4::position(0.0, 1.0, 0.0);
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+
+        let illum = light.illuminate(
+            &Open,
+            &mut Independent::new(3),
+            &HitRecord::at(p, up, &Material::default()),
+            &p,
+        );
+        for c in illum.iter() {
+            assert!((c - 1.0).abs() < 0.1, "{}", c);
+        }
+    }
+
+    #[test]
+    fn samples_find_bright_texels() {
+        let mut pixels = vec![[0.0, 0.0, 0.0]; 16 * 8];
+        // A single bright texel in the upper half
+        pixels[2 * 16 + 5] = [100.0, 100.0, 100.0];
+        let light = EnvironmentLight::new(16, 8, pixels, 1).unwrap();
+
+        let mut rng = Rng::new(11);
+        for _ in 0..100 {
+            let (d, pdf) = light.sample((rng.next_float(), rng.next_float())).unwrap();
+            assert!(pdf > 0.0);
+            assert_eq!([100.0, 100.0, 100.0], light.lookup(&d));
+        }
+    }
+
+    #[test]
+    fn sampled_directions_agree() {
+        let mut pixels = vec![[1.0, 1.0, 1.0]; 16 * 8];
+        pixels[3 * 16 + 9] = [50.0, 20.0, 10.0];
+        let light = EnvironmentLight::new(16, 8, pixels, 1).unwrap();
+        let p = Vec4::position(1.0, 2.0, 3.0);
+
+        let mut rng = Rng::new(5);
+        for _ in 0..200 {
+            let u = (rng.next_float(), rng.next_float());
+            let (d, distance, colour, pdf) = light.sample_incident(&p, u).unwrap();
+            assert_eq!(f64::INFINITY, distance);
+            // The same light as a ray along it sees, picked as likely as the
+            // density for it says
+            let (_, seen) = light.visible(&Ray::new(p, d)).unwrap();
+            assert_eq!(seen, colour);
+            let expected = light.incident_pdf(&p, &d);
+            assert!(
+                (pdf - expected).abs() < 1e-9 * expected,
+                "{} {}",
+                pdf,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn black_environment_is_dark() {
+        let light = uniform([0.0, 0.0, 0.0], 8);
+        let p = Vec4::position(0.0, 1.0, 0.0);
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+
+        let illum = light.illuminate(
+            &Open,
+            &mut Independent::new(0),
+            &HitRecord::at(p, up, &Material::default()),
+            &p,
+        );
+        assert_eq!([0.0, 0.0, 0.0], illum);
+    }
+
+    #[test]
+    fn load_hdr() {
+        let path = std::env::temp_dir().join(format!(
+            "tracer-rs-environment-test-{}.hdr",
+            std::process::id()
+        ));
+        let data = vec![Rgb([0.5f32, 1.0, 2.0]); 4 * 2];
+        HdrEncoder::new(File::create(&path).unwrap())
+            .encode(&data, 4, 2)
+            .unwrap();
+
+        let light: EnvironmentLight<f64> = EnvironmentLight::load(&path, 1).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            [0.5, 1.0, 2.0],
+            light.lookup(&Vec4::direction(0.0, 0.0, 1.0))
+        );
+    }
+}
@@ -1,19 +1,71 @@
 use num::Float;
 
-use crate::object::Intersectable;
+use crate::object::HitRecord;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
 use crate::vector::Vec4;
 
 pub mod ambientlight;
+pub mod ambientocclusion;
+pub mod arealight;
 pub mod directionlight;
+pub mod environmentlight;
 pub mod pointlight;
+pub mod spotlight;
 
 pub trait Light<T: Float> {
+    /// The light falling on `hit` from this light, with any random choices,
+    /// such as points on an area light, drawn from `sampler`
     fn illuminate(
         &self,
-        object: &dyn Intersectable<T>,
-        hit_point: &Vec4<T>,
+        scene: &dyn Scene<T>,
+        sampler: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
         eye_pos: &Vec4<T>,
     ) -> [T; 3];
+
+    /// Lights with a physical presence can be hit by rays; returns the distance along
+    /// the ray to the light, and the colour seen there
+    fn visible(&self, _ray: &Ray<T>) -> Option<(T, [T; 3])> {
+        None
+    }
+
+    /// For lights that shine from a single direction at `point`, such as point
+    /// lights, the (unit) direction towards the light, how far away it is and
+    /// the light arriving from it, for shading with a `Bsdf` or in a `Medium`.
+    /// Lights that fill a solid angle are found by rays scattered off the
+    /// surface instead, through `visible`, and by `sample_incident`.
+    ///
+    /// The light is scaled by pi, so a white `Lambert` surface comes out as
+    /// bright as a plain white material lit by `illuminate`.
+    fn incident(&self, _point: &Vec4<T>) -> Option<(Vec4<T>, T, [T; 3])> {
+        None
+    }
+
+    /// For lights that fill a solid angle and can pick directions within it,
+    /// such as an environment, a (unit) direction from `point` towards the
+    /// light picked by `u`, how far away it is, the light arriving along it, as
+    /// `visible` would see it, and the density over solid angle with which it
+    /// was picked. Lights found both this way and by rays through `visible` are
+    /// weighted by how likely each was to find them.
+    fn sample_incident(&self, _point: &Vec4<T>, _u: (T, T)) -> Option<(Vec4<T>, T, [T; 3], T)> {
+        None
+    }
+
+    /// The density over solid angle with which `sample_incident` would have
+    /// picked `direction` from `point`; zero for lights it doesn't sample
+    fn incident_pdf(&self, _point: &Vec4<T>, _direction: &Vec4<T>) -> T {
+        T::zero()
+    }
+
+    /// Whether the light shines evenly from all around, like an `AmbientLight`,
+    /// rather than from anywhere in particular. Surfaces with a `Bsdf` take
+    /// what `illuminate` gives for such lights, scaled by how much of it they
+    /// reflect.
+    fn ambient(&self) -> bool {
+        false
+    }
 }
 
 impl<T> Light<T> for Box<dyn Light<T>>
@@ -22,10 +74,31 @@ where
 {
     fn illuminate(
         &self,
-        object: &dyn Intersectable<T>,
-        hit_point: &Vec4<T>,
+        scene: &dyn Scene<T>,
+        sampler: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
         eye_pos: &Vec4<T>,
     ) -> [T; 3] {
-        self.as_ref().illuminate(object, hit_point, eye_pos)
+        self.as_ref().illuminate(scene, sampler, hit, eye_pos)
+    }
+
+    fn visible(&self, ray: &Ray<T>) -> Option<(T, [T; 3])> {
+        self.as_ref().visible(ray)
+    }
+
+    fn incident(&self, point: &Vec4<T>) -> Option<(Vec4<T>, T, [T; 3])> {
+        self.as_ref().incident(point)
+    }
+
+    fn sample_incident(&self, point: &Vec4<T>, u: (T, T)) -> Option<(Vec4<T>, T, [T; 3], T)> {
+        self.as_ref().sample_incident(point, u)
+    }
+
+    fn incident_pdf(&self, point: &Vec4<T>, direction: &Vec4<T>) -> T {
+        self.as_ref().incident_pdf(point, direction)
+    }
+
+    fn ambient(&self) -> bool {
+        self.as_ref().ambient()
     }
 }
@@ -1,6 +1,8 @@
-use num::Float;
+use num::{Float, FromPrimitive};
 
-use crate::object::Intersectable;
+use crate::object::HitRecord;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
 use crate::vector::Vec4;
 
 use super::Light;
@@ -11,25 +13,30 @@ pub struct PointLight<T: Float> {
 
 impl<T> Light<T> for PointLight<T>
 where
-    T: Float,
+    T: Float + FromPrimitive,
 {
     fn illuminate(
         &self,
-        object: &dyn Intersectable<T>,
-        hit_point: &Vec4<T>,
+        _: &dyn Scene<T>,
+        _: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
         _: &Vec4<T>,
     ) -> [T; 3] {
-        let norm = object.normal(hit_point).normalized();
-
-        let light_vec = &self.position - &hit_point;
+        let light_vec = &self.position - &hit.point;
 
-        let illum = norm.dot_product(&light_vec.normalized());
+        let illum = hit.shading_normal().dot_product(&light_vec.normalized());
         if illum < T::zero() {
             return [T::zero(), T::zero(), T::zero()];
         }
 
         [illum, illum, illum]
     }
+
+    fn incident(&self, point: &Vec4<T>) -> Option<(Vec4<T>, T, [T; 3])> {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let light_vec = &self.position - point;
+        Some((light_vec.normalized(), light_vec.mag(), [pi; 3]))
+    }
 }
 
 impl<T> PointLight<T>
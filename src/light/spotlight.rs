@@ -0,0 +1,154 @@
+use num::{Float, FromPrimitive};
+
+use crate::object::HitRecord;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+use crate::vector::Vec4;
+
+use super::Light;
+
+/// A point light restricted to a cone. Inside `inner_angle` of the axis it is at
+/// full strength, beyond `outer_angle` it contributes nothing, and in between it
+/// fades out smoothly. Angles are measured from the axis, in radians.
+pub struct SpotLight<T: Float> {
+    position: Vec4<T>,
+    direction: Vec4<T>,
+    cos_inner: T,
+    cos_outer: T,
+}
+
+impl<T> SpotLight<T>
+where
+    T: Float,
+{
+    pub fn new(
+        position: Vec4<T>,
+        direction: Vec4<T>,
+        inner_angle: T,
+        outer_angle: T,
+    ) -> SpotLight<T> {
+        // A hard-edged spot is fine, but the inner cone can't be wider than the outer
+        let inner_angle = T::min(inner_angle, outer_angle);
+
+        SpotLight {
+            position,
+            direction: direction.normalized(),
+            cos_inner: T::cos(inner_angle),
+            cos_outer: T::cos(outer_angle),
+        }
+    }
+
+    /// How much of the light reaches a point `cos_angle` off-axis
+    fn falloff(&self, cos_angle: T) -> T
+    where
+        T: FromPrimitive,
+    {
+        if cos_angle >= self.cos_inner {
+            return T::one();
+        }
+        if cos_angle <= self.cos_outer {
+            return T::zero();
+        }
+
+        // smoothstep between the outer and inner cones
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let three: T = FromPrimitive::from_f64(3.0).unwrap();
+        let x = (cos_angle - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        x * x * (three - two * x)
+    }
+}
+
+impl<T> Light<T> for SpotLight<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn illuminate(
+        &self,
+        _: &dyn Scene<T>,
+        _: &mut dyn Sampler<T>,
+        hit: &HitRecord<T>,
+        _: &Vec4<T>,
+    ) -> [T; 3] {
+        let light_vec = (&self.position - &hit.point).normalized();
+
+        let spot = self.falloff(light_vec.reverse().dot_product(&self.direction));
+        let illum = hit.shading_normal().dot_product(&light_vec) * spot;
+        if illum < T::zero() {
+            return [T::zero(), T::zero(), T::zero()];
+        }
+
+        [illum, illum, illum]
+    }
+
+    fn incident(&self, point: &Vec4<T>) -> Option<(Vec4<T>, T, [T; 3])> {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let light_vec = &self.position - point;
+        let direction = light_vec.normalized();
+        let spot = self.falloff(direction.reverse().dot_product(&self.direction));
+        if spot <= T::zero() {
+            return None;
+        }
+        Some((direction, light_vec.mag(), [pi * spot; 3]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::sampler::independent::Independent;
+    use crate::scene::tests::Open;
+
+    fn downlight() -> SpotLight<f64> {
+        SpotLight::new(
+            Vec4::position(0.0, 10.0, 0.0),
+            Vec4::direction(0.0, -1.0, 0.0),
+            0.2,
+            0.4,
+        )
+    }
+
+    fn lit(light: &SpotLight<f64>, x: f64) -> f64 {
+        // A floor facing straight up
+        let p = Vec4::position(x, 0.0, 0.0);
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+        let m = Material::default();
+        light.illuminate(
+            &Open,
+            &mut Independent::new(0),
+            &HitRecord::at(p, up, &m),
+            &p,
+        )[0]
+    }
+
+    #[test]
+    fn inside_inner_cone() {
+        assert_eq!(1.0, lit(&downlight(), 0.0));
+    }
+
+    #[test]
+    fn outside_outer_cone() {
+        // atan(5 / 10) is well outside 0.4 radians
+        assert_eq!(0.0, lit(&downlight(), 5.0));
+    }
+
+    #[test]
+    fn penumbra() {
+        // atan(3 / 10) is between the cones
+        let l = lit(&downlight(), 3.0);
+        assert!(l > 0.0 && l < 1.0);
+    }
+
+    #[test]
+    fn falloff_is_monotonic() {
+        let light = downlight();
+        let mut last = 1.0;
+        for i in 0..50 {
+            let angle = 0.2 + 0.2 * i as f64 / 49.0;
+            let f = light.falloff(angle.cos());
+            assert!(f <= last);
+            last = f;
+        }
+        assert_eq!(0.0, last);
+    }
+}
@@ -1,23 +1,71 @@
-mod engine;
-mod light;
-mod matrix;
-mod object;
-mod vector;
-
-use engine::Engine;
-use image::{ImageError, Rgb};
-use light::{ambientlight::AmbientLight, directionlight::DirectionLight, pointlight::PointLight};
-use matrix::Mat4;
-use object::sphere::Sphere;
-use vector::Vec4;
-
-fn main() -> Result<(), ImageError> {
+use std::io::Write;
+
+use image::Rgb;
+use tracer_rs::light::{
+    ambientlight::AmbientLight, directionlight::DirectionLight, pointlight::PointLight,
+};
+use tracer_rs::object::sphere::Sphere;
+use tracer_rs::{Denoiser, Engine, Mat4, Progress, ProgressiveRender, RenderSession, Result, Vec4};
+
+const USAGE: &str = "usage: tracer-rs [--samples N] [--denoise] [OUTPUT]";
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 480;
+
+/// What was asked for on the command line
+struct Options {
+    samples: u32,
+    denoise: bool,
+    output: String,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> std::result::Result<Options, String> {
+        let mut options = Options {
+            samples: 1,
+            denoise: false,
+            output: String::from("output.png"),
+        };
+        let mut output = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--samples" => {
+                    options.samples = args
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .filter(|n| *n > 0)
+                        .ok_or("--samples needs a number of samples per pixel")?
+                }
+                "--denoise" => options.denoise = true,
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ if output.is_none() => output = Some(arg),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        if let Some(output) = output {
+            options.output = output;
+        }
+        Ok(options)
+    }
+}
+
+fn main() -> Result<()> {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            std::process::exit(2);
+        }
+    };
+
     let pos = Vec4::position(0.0, 0.0, -10.0);
     let origin = Vec4::position(0.0, 0.0, 0.0);
 
     let camera = Mat4::look(&pos, &origin);
 
-    let sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 8.0);
+    let sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 8.0)?;
 
     let mut engine = Engine::new(camera);
     engine.add_object(Box::new(sphere));
@@ -31,7 +79,46 @@ fn main() -> Result<(), ImageError> {
     let plight = PointLight::new(Vec4::position(-25.0, 25.0, -25.0));
     engine.add_light(Box::new(plight));
 
-    let img = engine.render(640, 480);
+    // The denoiser works from the unclamped averages, which a progressive
+    // render keeps
+    let img = if options.denoise {
+        let refined = ProgressiveRender::new(&engine, WIDTH, HEIGHT)
+            .target_samples(options.samples)
+            .run()?;
+        let guides = engine.render_guides(WIDTH, HEIGHT)?;
+        Denoiser::new()
+            .run(&refined.accumulator.to_float_image(), &guides)?
+            .to_image()
+    } else {
+        engine.set_samples(options.samples);
+        let img = RenderSession::new(&engine, WIDTH, HEIGHT)
+            .on_progress(|p| {
+                eprint!("\r{}", progress_bar(p));
+                let _ = std::io::stderr().flush();
+            })
+            .run()?;
+        eprintln!();
+        img
+    };
+
+    img.save(&options.output)?;
+    Ok(())
+}
 
-    img.save("output.png")
+/// One line of `[#####     ]  50%  1234 rays  2.5s left`
+fn progress_bar(p: &Progress) -> String {
+    const WIDTH: usize = 30;
+    let filled = (p.fraction() * WIDTH as f64).round() as usize;
+    let eta = match p.eta {
+        Some(eta) => format!("{:.1}s left", eta.as_secs_f64()),
+        None => String::new(),
+    };
+    format!(
+        "[{}{}] {:3.0}%  {} rays  {}",
+        "#".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        p.fraction() * 100.0,
+        p.rays_cast,
+        eta
+    )
 }
@@ -0,0 +1,294 @@
+use std::fmt;
+
+use image::Rgb;
+use num::{Float, FromPrimitive};
+
+use crate::bsdf::Bsdf;
+use crate::colour;
+use crate::texture::{Constant, Texture};
+use crate::vector::Vec4;
+
+/// How a surface responds to the light falling on it
+pub struct Material<T: Float> {
+    diffuse: Box<dyn Texture<T>>,
+    normal_map: Option<Box<dyn Texture<T>>>,
+    bump_map: Option<(Box<dyn Texture<T>>, T)>,
+    bsdf: Option<Box<dyn Bsdf<T>>>,
+    emission: [T; 3],
+}
+
+impl<T> Material<T>
+where
+    T: Float,
+{
+    pub fn new(diffuse: Box<dyn Texture<T>>) -> Material<T> {
+        Material {
+            diffuse,
+            normal_map: None,
+            bump_map: None,
+            bsdf: None,
+            emission: [T::zero(); 3],
+        }
+    }
+
+    /// The fraction of incoming light reflected, per channel, at `point`
+    pub fn diffuse(&self, point: &Vec4<T>, uv: (T, T)) -> [T; 3] {
+        self.diffuse.colour(point, uv)
+    }
+
+    /// A tangent-space normal map: red runs along the surface tangent (increasing
+    /// u), green along the bitangent (decreasing v, i.e. up the image) and blue
+    /// out of the surface, each scaled from [0, 1] to [-1, 1].
+    pub fn set_normal_map(&mut self, normal_map: Box<dyn Texture<T>>) {
+        self.normal_map = Some(normal_map);
+    }
+
+    /// A height map, read from the brightness of the texture. `strength` scales
+    /// how far the surface appears to rise for a change in brightness.
+    ///
+    /// The slope is taken per unit of whatever the texture is read by: per unit
+    /// of distance for textures worked out from the point, such as
+    /// `Procedural`, and per unit of u or v for ones looked up by UV, such as
+    /// `ImageTexture`. A whole image spans one unit of UV however big the
+    /// object is, so image bump maps want a much smaller `strength`.
+    pub fn set_bump_map(&mut self, bump_map: Box<dyn Texture<T>>, strength: T) {
+        self.bump_map = Some((bump_map, strength));
+    }
+
+    /// Shade physically with `bsdf`, lit by whatever it reflects or refracts as
+    /// well as by the lights, instead of with the diffuse colour alone
+    pub fn set_bsdf(&mut self, bsdf: Box<dyn Bsdf<T>>) {
+        self.bsdf = Some(bsdf);
+    }
+
+    pub fn bsdf(&self) -> Option<&dyn Bsdf<T>> {
+        self.bsdf.as_deref()
+    }
+
+    /// The light given off from the front of the surface, per channel, on top
+    /// of whatever it reflects
+    pub fn emission(&self) -> [T; 3] {
+        self.emission
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission.iter().any(|c| *c > T::zero())
+    }
+}
+
+impl<T> Material<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Make the surface glow with `colour`, scaled by `strength`. Objects with
+    /// a glowing material light the scene like an area light as well as being
+    /// seen.
+    pub fn set_emission(&mut self, colour: Rgb<u8>, strength: T) {
+        let colour: [T; 3] = colour::from_rgb(colour);
+        self.emission = colour.map(|c| c * strength.max(T::zero()));
+    }
+
+    /// The normal to shade with, after any normal or bump mapping has been applied
+    /// to the surface's own `normal`
+    pub fn shading_normal(
+        &self,
+        normal: &Vec4<T>,
+        tangent: &Vec4<T>,
+        point: &Vec4<T>,
+        uv: (T, T),
+    ) -> Vec4<T> {
+        if self.normal_map.is_none() && self.bump_map.is_none() {
+            return *normal;
+        }
+
+        // Make sure the tangent frame is orthonormal
+        let tangent = (tangent - &(normal * normal.dot_product(tangent))).normalized();
+        let bitangent = normal.cross_product(&tangent);
+
+        let mut shading = *normal;
+
+        if let Some(normal_map) = &self.normal_map {
+            let two: T = FromPrimitive::from_f64(2.0).unwrap();
+            let m = normal_map.colour(point, uv);
+            let (x, y, z) = (
+                m[0] * two - T::one(),
+                m[1] * two - T::one(),
+                m[2] * two - T::one(),
+            );
+
+            shading = (&(&(&tangent * x) + &(&bitangent * y)) + &(&shading * z)).normalized();
+        }
+
+        if let Some((bump_map, strength)) = &self.bump_map {
+            let epsilon: T = FromPrimitive::from_f64(1e-3).unwrap();
+            let two: T = FromPrimitive::from_f64(2.0).unwrap();
+            let (u, v) = uv;
+
+            // Step the point along the tangent frame and the UVs along u and v
+            // together; a texture only reads one or the other, so this gives
+            // its slope per unit distance or per unit of UV respectively
+            let height = |direction: &Vec4<T>, du: T, dv: T| {
+                let p = point + &(direction * epsilon);
+                colour::luminance(&bump_map.colour(&p, (u + du * epsilon, v + dv * epsilon)))
+            };
+            let down = bitangent.reverse();
+
+            let dh_du = (height(&tangent, T::one(), T::zero())
+                - height(&tangent.reverse(), -T::one(), T::zero()))
+                / (two * epsilon);
+            let dh_dv = (height(&down, T::zero(), T::one())
+                - height(&bitangent, T::zero(), -T::one()))
+                / (two * epsilon);
+
+            let slope = &(&tangent * dh_du) + &(&down * dh_dv);
+            shading = (&shading - &(&slope * *strength)).normalized();
+        }
+
+        shading
+    }
+}
+
+impl<T> Material<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    /// A plain material of a single colour
+    pub fn colour(colour: Rgb<u8>) -> Material<T> {
+        Material::new(Box::new(Constant::new(colour)))
+    }
+}
+
+impl<T> Default for Material<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    fn default() -> Self {
+        Material::colour(Rgb([255, 255, 255]))
+    }
+}
+
+impl<T> fmt::Debug for Material<T>
+where
+    T: Float,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Material").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::Mat4;
+    use crate::texture::procedural::{Pattern, Procedural};
+
+    fn frame() -> (Vec4<f64>, Vec4<f64>, Vec4<f64>) {
+        (
+            Vec4::direction(0.0, 1.0, 0.0),
+            Vec4::direction(1.0, 0.0, 0.0),
+            Vec4::position(0.5, 0.0, 0.5),
+        )
+    }
+
+    #[test]
+    fn emission() {
+        let mut m: Material<f64> = Material::default();
+        assert!(!m.is_emissive());
+        m.set_emission(Rgb([255, 0, 51]), 4.0);
+        assert!(m.is_emissive());
+        assert_eq!([4.0, 0.0, 0.8], m.emission());
+    }
+
+    #[test]
+    fn unmapped_normal_unchanged() {
+        let m: Material<f64> = Material::default();
+        let (n, t, p) = frame();
+        assert_eq!(n, m.shading_normal(&n, &t, &p, (0.5, 0.5)));
+    }
+
+    #[test]
+    fn flat_normal_map() {
+        let mut m: Material<f64> = Material::default();
+        m.set_normal_map(Box::new(Constant::new(Rgb([128, 128, 255]))));
+        let (n, t, p) = frame();
+
+        let shading = m.shading_normal(&n, &t, &p, (0.5, 0.5));
+        assert!((shading.dot_product(&n) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn tilted_normal_map() {
+        let mut m: Material<f64> = Material::default();
+        // Leaning towards +tangent
+        m.set_normal_map(Box::new(Constant::new(Rgb([255, 128, 128]))));
+        let (n, t, p) = frame();
+
+        let shading = m.shading_normal(&n, &t, &p, (0.5, 0.5));
+        assert!(shading.x > 0.9);
+        assert!((shading.mag() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn flat_bump_map() {
+        let mut m: Material<f64> = Material::default();
+        m.set_bump_map(Box::new(Constant::new(Rgb([100, 100, 100]))), 1.0);
+        let (n, t, p) = frame();
+
+        assert_eq!(n, m.shading_normal(&n, &t, &p, (0.5, 0.5)));
+    }
+
+    #[test]
+    fn sloping_bump_map() {
+        // Height rising along +x, i.e. along the tangent; the normal should lean back
+        let mut m: Material<f64> = Material::default();
+        let ramp = Procedural::new(
+            Pattern::Gradient,
+            Rgb([0, 0, 0]),
+            Rgb([255, 255, 255]),
+            Mat4::i(),
+        )
+        .unwrap();
+        m.set_bump_map(Box::new(ramp), 1.0);
+        let (n, t, p) = frame();
+
+        let shading = m.shading_normal(&n, &t, &p, (0.5, 0.5));
+        assert!(shading.x < -0.1);
+        assert!(shading.y > 0.0);
+        assert!(shading.z.abs() < 1e-9);
+    }
+
+    /// Height rising along u at one unit of brightness per unit of UV
+    struct UvRamp;
+
+    impl Texture<f64> for UvRamp {
+        fn colour(&self, _: &Vec4<f64>, uv: (f64, f64)) -> [f64; 3] {
+            [uv.0; 3]
+        }
+    }
+
+    #[test]
+    fn uv_bump_map() {
+        // The same slope per unit of UV as the gradient has per unit distance
+        // leans the normal the same way, wherever the point is
+        let mut by_uv: Material<f64> = Material::default();
+        by_uv.set_bump_map(Box::new(UvRamp), 0.5);
+        let mut by_point: Material<f64> = Material::default();
+        let ramp = Procedural::new(
+            Pattern::Gradient,
+            Rgb([0, 0, 0]),
+            Rgb([255, 255, 255]),
+            Mat4::i(),
+        )
+        .unwrap();
+        by_point.set_bump_map(Box::new(ramp), 0.5);
+        let (n, t, p) = frame();
+
+        let expected = by_point.shading_normal(&n, &t, &p, (0.5, 0.5));
+        let far = Vec4::position(500.0, 0.0, -20.0);
+        for point in [p, far] {
+            let shading = by_uv.shading_normal(&n, &t, &point, (0.5, 0.5));
+            assert!((&shading - &expected).mag() < 1e-6, "{:?}", shading);
+        }
+        assert!(expected.x < -0.1);
+    }
+}
@@ -2,9 +2,10 @@ use num::Float;
 use std::fmt;
 use std::ops::{Index, IndexMut, Mul};
 
+use crate::error::{Error, Result};
 use crate::vector::Vec4;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub struct Mat4<T>
 where
     T: Float,
@@ -79,7 +80,39 @@ where
         s
     }
 
-    pub fn inverse(&self) -> Self {
+    /// Rotation by `angle` radians about `axis`, anticlockwise when looking back
+    /// down the axis
+    pub fn rotation(axis: &Vec4<T>, angle: T) -> Self {
+        let a = axis.normalized();
+        let (s, c) = angle.sin_cos();
+        let t = T::one() - c;
+
+        let mut r: Mat4<T> = Mat4::i();
+        r[(0, 0)] = t * a.x * a.x + c;
+        r[(1, 0)] = t * a.x * a.y - s * a.z;
+        r[(2, 0)] = t * a.x * a.z + s * a.y;
+        r[(0, 1)] = t * a.x * a.y + s * a.z;
+        r[(1, 1)] = t * a.y * a.y + c;
+        r[(2, 1)] = t * a.y * a.z - s * a.x;
+        r[(0, 2)] = t * a.x * a.z - s * a.y;
+        r[(1, 2)] = t * a.y * a.z + s * a.x;
+        r[(2, 2)] = t * a.z * a.z + c;
+
+        r
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Mat4::new();
+        for row in 0..4 {
+            for col in 0..4 {
+                t[(col, row)] = self[(row, col)];
+            }
+        }
+        t
+    }
+
+    /// Fails if the matrix is singular, as from scaling by zero
+    pub fn inverse(&self) -> Result<Self> {
         let mut inv = Mat4::new();
 
         inv[0] = self[5] * self[10] * self[15]
@@ -196,8 +229,8 @@ where
 
         let mut det = self[0] * inv[0] + self[1] * inv[4] + self[2] * inv[8] + self[3] * inv[12];
 
-        if det == T::zero() {
-            return Mat4::i();
+        if det == T::zero() || !det.is_finite() {
+            return Err(Error::SingularTransform);
         }
 
         det = T::one() / det;
@@ -206,7 +239,7 @@ where
             inv[i] = inv[i] * det;
         }
 
-        inv
+        Ok(inv)
     }
 }
 
@@ -365,6 +398,33 @@ mod tests {
         assert_eq!(i, result);
     }
 
+    #[test]
+    fn rotation() {
+        let quarter = std::f64::consts::FRAC_PI_2;
+        let r = Mat4::rotation(&Vec4::direction(0.0, 0.0, 1.0), quarter);
+        let v = &r * &Vec4::position(1.0, 0.0, 0.0);
+
+        assert!((v.x - 0.0).abs() < 1e-12);
+        assert!((v.y - 1.0).abs() < 1e-12);
+        assert_eq!(1.0, v.w);
+
+        // Rotate, then move
+        let t = Mat4::translation(&Vec4::direction(0.0, 0.0, 5.0));
+        let v = &(&r * &t) * &Vec4::position(1.0, 0.0, 0.0);
+        assert!((v.y - 1.0).abs() < 1e-12);
+        assert!((v.z - 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn transpose() {
+        let t = Mat4::translation(&Vec4::direction(1.0, 2.0, 3.0));
+        let tt = t.transpose();
+        assert_eq!(1.0, tt[(0, 3)]);
+        assert_eq!(2.0, tt[(1, 3)]);
+        assert_eq!(3.0, tt[(2, 3)]);
+        assert_eq!(t, tt.transpose());
+    }
+
     #[test]
     fn look_at() {
         let pos = Vec4::position(0.0, 0.0, -10.0);
@@ -373,4 +433,18 @@ mod tests {
 
         println!("{}", camera)
     }
+
+    #[test]
+    fn inverse() {
+        let m = &Mat4::scale(&Vec4::direction(2.0, 4.0, 8.0))
+            * &Mat4::translation(&Vec4::direction(1.0, 2.0, 3.0));
+        let inv = m.inverse().unwrap();
+        assert_eq!(Mat4::i(), &m * &inv);
+    }
+
+    #[test]
+    fn singular() {
+        let flat = Mat4::scale(&Vec4::direction(1.0, 0.0, 1.0));
+        assert!(matches!(flat.inverse(), Err(Error::SingularTransform)));
+    }
 }
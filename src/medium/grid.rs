@@ -0,0 +1,272 @@
+use num::{Float, FromPrimitive};
+
+use super::homogeneous::Homogeneous;
+use super::*;
+use crate::error::{Error, Result};
+
+/// A medium whose density varies through a box, such as a cloud or a plume of
+/// smoke, given by a grid of voxels and blended smoothly between them. Light
+/// runs into it at a density-scaled `Homogeneous` medium's rate.
+#[derive(Debug, Clone)]
+pub struct Grid<T: Float> {
+    medium: Homogeneous<T>,
+    size: [usize; 3],
+    density: Vec<T>,
+    max_density: T,
+    min: Vec4<T>,
+    max: Vec4<T>,
+}
+
+impl<T> Grid<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// `medium` is what a density of 1 is like. `density` runs along x first,
+    /// then y, then z, `size` voxels along each, and the grid fills the box
+    /// from corner `min` to corner `max`.
+    pub fn new(
+        medium: Homogeneous<T>,
+        size: [usize; 3],
+        density: Vec<T>,
+        min: Vec4<T>,
+        max: Vec4<T>,
+    ) -> Result<Grid<T>> {
+        if size.iter().product::<usize>() != density.len() || density.is_empty() {
+            return Err(Error::InvalidScene(format!(
+                "a {}x{}x{} grid can't have {} densities",
+                size[0],
+                size[1],
+                size[2],
+                density.len()
+            )));
+        }
+        if density.iter().any(|d| *d < T::zero() || !d.is_finite()) {
+            return Err(Error::InvalidScene(
+                "grid densities must be finite and not negative".to_string(),
+            ));
+        }
+        if (0..3).any(|i| max[i] <= min[i]) {
+            return Err(Error::InvalidScene("grid box is empty".to_string()));
+        }
+
+        let max_density = density.iter().fold(T::zero(), |m, d| m.max(*d));
+        Ok(Grid {
+            medium,
+            size,
+            density,
+            max_density,
+            min,
+            max,
+        })
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> T {
+        self.density[x + self.size[0] * (y + self.size[1] * z)]
+    }
+
+    /// The density at `point`, blended between the middles of the voxels
+    /// around it, and nothing outside the box
+    pub fn density(&self, point: &Vec4<T>) -> T {
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        let mut lower = [0; 3];
+        let mut upper = [0; 3];
+        let mut weight = [T::zero(); 3];
+
+        for i in 0..3 {
+            if point[i] < self.min[i] || point[i] > self.max[i] {
+                return T::zero();
+            }
+            let n: T = FromPrimitive::from_usize(self.size[i]).unwrap();
+            let last: T = FromPrimitive::from_usize(self.size[i] - 1).unwrap();
+            let at = ((point[i] - self.min[i]) / (self.max[i] - self.min[i]) * n - half)
+                .max(T::zero())
+                .min(last);
+            let floor = at.floor();
+            lower[i] = floor.to_usize().unwrap_or(0);
+            upper[i] = (lower[i] + 1).min(self.size[i] - 1);
+            weight[i] = at - floor;
+        }
+
+        let mut total = T::zero();
+        for corner in 0..8 {
+            let mut w = T::one();
+            let mut at = [0; 3];
+            for i in 0..3 {
+                if corner & (1 << i) == 0 {
+                    at[i] = lower[i];
+                    w = w * (T::one() - weight[i]);
+                } else {
+                    at[i] = upper[i];
+                    w = w * weight[i];
+                }
+            }
+            total = total + w * self.voxel(at[0], at[1], at[2]);
+        }
+        total
+    }
+
+    /// The part of `ray` inside the box, if any
+    fn clip(&self, ray: &Ray<T>) -> Option<(T, T)> {
+        let (mut near, mut far) = (ray.t_min, ray.t_max);
+        for i in 0..3 {
+            let inverse = T::one() / ray.direction[i];
+            let mut t0 = (self.min[i] - ray.origin[i]) * inverse;
+            let mut t1 = (self.max[i] - ray.origin[i]) * inverse;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // Parallel to the slab and starting right on its face gives NaN;
+            // such a ray is either always or never between the faces
+            if t0.is_nan() || t1.is_nan() {
+                if ray.origin[i] < self.min[i] || ray.origin[i] > self.max[i] {
+                    return None;
+                }
+                continue;
+            }
+            near = near.max(t0);
+            far = far.min(t1);
+        }
+
+        if near < far {
+            Some((near, far))
+        } else {
+            None
+        }
+    }
+
+    /// The rate at which light would run into the grid if it were all as dense
+    /// as its densest voxel
+    fn majorant(&self) -> T {
+        self.max_density * self.medium.extinction()
+    }
+}
+
+impl<T> Medium<T> for Grid<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Delta tracking: steps as if the grid were at its densest all over, and
+    /// keeps a step as a real collision in proportion to the actual density
+    fn sample_scatter(&self, rng: &mut Rng, ray: &Ray<T>) -> Option<T> {
+        let (mut t, end) = self.clip(ray)?;
+        let majorant = self.majorant();
+        if majorant == T::zero() {
+            return None;
+        }
+
+        loop {
+            t = t + free_flight(majorant, rng.next_float());
+            if t >= end {
+                return None;
+            }
+            if rng.next_float::<T>() * self.max_density < self.density(&ray.at(t)) {
+                return Some(t);
+            }
+        }
+    }
+
+    /// Ratio tracking: the same steps, each letting through the share of light
+    /// that the density there would
+    fn transmittance(&self, rng: &mut Rng, ray: &Ray<T>) -> T {
+        let (mut t, end) = match self.clip(ray) {
+            Some(range) => range,
+            None => return T::one(),
+        };
+        let majorant = self.majorant();
+        if majorant == T::zero() {
+            return T::one();
+        }
+
+        let mut transmittance = T::one();
+        loop {
+            t = t + free_flight(majorant, rng.next_float());
+            if t >= end {
+                return transmittance;
+            }
+            transmittance =
+                transmittance * (T::one() - self.density(&ray.at(t)) / self.max_density);
+        }
+    }
+
+    fn albedo(&self) -> T {
+        self.medium.albedo()
+    }
+
+    fn phase(&self) -> HenyeyGreenstein<T> {
+        self.medium.phase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box(size: [usize; 3], density: Vec<f64>) -> Result<Grid<f64>> {
+        Grid::new(
+            Homogeneous::new(0.0, 2.0, 0.0),
+            size,
+            density,
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::position(1.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            unit_box([2, 2, 2], vec![1.0; 7]),
+            Err(Error::InvalidScene(_))
+        ));
+        assert!(matches!(
+            unit_box([1, 1, 1], vec![-1.0]),
+            Err(Error::InvalidScene(_))
+        ));
+        assert!(matches!(
+            unit_box([0, 1, 1], vec![]),
+            Err(Error::InvalidScene(_))
+        ));
+    }
+
+    #[test]
+    fn blends_between_voxels() {
+        // Empty on the left, dense on the right
+        let grid = unit_box([2, 1, 1], vec![0.0, 1.0]).unwrap();
+        let at = |x: f64| grid.density(&Vec4::position(x, 0.5, 0.5));
+        assert_eq!(0.0, at(0.1));
+        assert!((at(0.5) - 0.5).abs() < 1e-12);
+        assert_eq!(1.0, at(0.9));
+        assert_eq!(0.0, at(1.5));
+    }
+
+    #[test]
+    fn matches_uniform_density() {
+        // A grid at half density all over is a homogeneous medium at half the rate
+        let grid = unit_box([3, 3, 3], vec![0.5; 27]).unwrap();
+        let ray = Ray::new(
+            Vec4::position(-1.0, 0.5, 0.5),
+            Vec4::direction(1.0, 0.0, 0.0),
+        );
+        let expected = (-1.0f64).exp();
+
+        let mut rng = Rng::new(6);
+        let n = 20000;
+        let mut through = 0;
+        let mut total = 0.0;
+        for _ in 0..n {
+            total += grid.transmittance(&mut rng, &ray);
+            match grid.sample_scatter(&mut rng, &ray) {
+                Some(t) => assert!((1.0..=2.0).contains(&t), "{}", t),
+                None => through += 1,
+            }
+        }
+        assert!((total / n as f64 - expected).abs() < 0.01);
+        assert!((through as f64 / n as f64 - expected).abs() < 0.01);
+
+        // Rays along the box's faces, or missing it, are handled
+        let missing = Ray::new(
+            Vec4::position(-1.0, 2.0, 0.5),
+            Vec4::direction(1.0, 0.0, 0.0),
+        );
+        assert_eq!(1.0, grid.transmittance(&mut rng, &missing));
+    }
+}
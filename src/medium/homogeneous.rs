@@ -0,0 +1,111 @@
+use num::{Float, FromPrimitive};
+
+use super::*;
+
+/// A medium that's the same all the way through, and the same for every
+/// colour. Left unbounded it fills the whole scene, as fog or haze; to fill
+/// just the inside of an object, put it in a `Volume`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Homogeneous<T: Float> {
+    absorption: T,
+    scattering: T,
+    phase: HenyeyGreenstein<T>,
+}
+
+impl<T> Homogeneous<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// `absorption` and `scattering` are the chances per unit distance of light
+    /// being absorbed or scattered, and `g` the asymmetry of the scattering;
+    /// see `HenyeyGreenstein`
+    pub fn new(absorption: T, scattering: T, g: T) -> Homogeneous<T> {
+        Homogeneous {
+            absorption: absorption.max(T::zero()),
+            scattering: scattering.max(T::zero()),
+            phase: HenyeyGreenstein::new(g),
+        }
+    }
+
+    /// The chance per unit distance of light being absorbed or scattered
+    pub fn extinction(&self) -> T {
+        self.absorption + self.scattering
+    }
+}
+
+impl<T> Medium<T> for Homogeneous<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn sample_scatter(&self, rng: &mut Rng, ray: &Ray<T>) -> Option<T> {
+        if self.extinction() == T::zero() {
+            return None;
+        }
+        let t = ray.t_min + free_flight(self.extinction(), rng.next_float());
+        if t < ray.t_max {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    fn transmittance(&self, _: &mut Rng, ray: &Ray<T>) -> T {
+        if self.extinction() == T::zero() {
+            return T::one();
+        }
+        (-self.extinction() * (ray.t_max - ray.t_min)).exp()
+    }
+
+    fn albedo(&self) -> T {
+        if self.extinction() == T::zero() {
+            return T::zero();
+        }
+        self.scattering / self.extinction()
+    }
+
+    fn phase(&self) -> HenyeyGreenstein<T> {
+        self.phase
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beer_lambert() {
+        let fog = Homogeneous::new(0.1, 0.4, 0.0);
+        assert_eq!(0.8, fog.albedo());
+
+        let ray = Ray::segment(
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::direction(1.0, 0.0, 0.0),
+            1.0,
+            3.0,
+        );
+        let mut rng = Rng::new(8);
+        assert!((fog.transmittance(&mut rng, &ray) - (-1.0f64).exp()).abs() < 1e-12);
+
+        // The share that gets through unscattered matches
+        let n = 20000;
+        let through = (0..n)
+            .filter(|_| fog.sample_scatter(&mut rng, &ray).is_none())
+            .count();
+        assert!((through as f64 / n as f64 - (-1.0f64).exp()).abs() < 0.01);
+        // And the rest run into it on the way
+        let t = fog.sample_scatter(&mut Rng::new(1), &Ray::new(ray.origin, ray.direction));
+        assert!(t.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn clear() {
+        let clear = Homogeneous::new(0.0, 0.0, 0.5);
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::direction(1.0, 0.0, 0.0),
+        );
+        let mut rng = Rng::new(0);
+        assert_eq!(None, clear.sample_scatter(&mut rng, &ray));
+        assert_eq!(1.0, clear.transmittance(&mut rng, &ray));
+    }
+}
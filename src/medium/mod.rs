@@ -0,0 +1,163 @@
+use num::{Float, FromPrimitive};
+
+use crate::random::Rng;
+use crate::ray::Ray;
+use crate::vector::Vec4;
+
+pub mod grid;
+pub mod homogeneous;
+pub mod volume;
+
+/// Something light travels through rather than bouncing off, such as fog,
+/// smoke or murky water. Along the way some of it is absorbed, and some is
+/// scattered off in other directions.
+///
+/// Distances are measured along the ray, whose direction should be of unit
+/// length.
+pub trait Medium<T: Float> {
+    /// How far along `ray`, between its `t_min` and `t_max`, light travelling
+    /// along it first runs into the medium, picked in proportion to how likely
+    /// that is to happen there. None if it gets all the way through.
+    fn sample_scatter(&self, rng: &mut Rng, ray: &Ray<T>) -> Option<T>;
+
+    /// The fraction of light that gets from `t_min` to `t_max` along `ray`
+    /// without being absorbed or scattered away. May be an estimate, drawn
+    /// with `rng`, that's right on average.
+    fn transmittance(&self, rng: &mut Rng, ray: &Ray<T>) -> T;
+
+    /// The fraction of the light running into the medium that's scattered
+    /// rather than absorbed
+    fn albedo(&self) -> T;
+
+    /// Which ways the scattered light goes
+    fn phase(&self) -> HenyeyGreenstein<T>;
+}
+
+impl<T> Medium<T> for Box<dyn Medium<T>>
+where
+    T: Float,
+{
+    fn sample_scatter(&self, rng: &mut Rng, ray: &Ray<T>) -> Option<T> {
+        self.as_ref().sample_scatter(rng, ray)
+    }
+
+    fn transmittance(&self, rng: &mut Rng, ray: &Ray<T>) -> T {
+        self.as_ref().transmittance(rng, ray)
+    }
+
+    fn albedo(&self) -> T {
+        self.as_ref().albedo()
+    }
+
+    fn phase(&self) -> HenyeyGreenstein<T> {
+        self.as_ref().phase()
+    }
+}
+
+/// The Henyey-Greenstein phase function: how scattered light spreads out, from
+/// a single asymmetry parameter `g`. Positive `g` mostly carries on forwards, as
+/// in fog and haze, negative sends it mostly back and zero spreads it evenly.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct HenyeyGreenstein<T: Float> {
+    g: T,
+}
+
+impl<T> HenyeyGreenstein<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// `g` is kept a little inside -1 to 1, where all the light would go one way
+    pub fn new(g: T) -> HenyeyGreenstein<T> {
+        let limit: T = FromPrimitive::from_f64(0.99).unwrap();
+        HenyeyGreenstein {
+            g: g.max(-limit).min(limit),
+        }
+    }
+
+    /// The density over solid angle of light travelling along one direction
+    /// being scattered into another `cos_angle` from it
+    pub fn evaluate(&self, cos_angle: T) -> T {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let four: T = FromPrimitive::from_f64(4.0).unwrap();
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let g = self.g;
+
+        let denominator = T::one() + g * g - two * g * cos_angle;
+        (T::one() - g * g) / (four * pi * denominator * denominator.sqrt())
+    }
+
+    /// A direction for light travelling along `direction` to be scattered
+    /// into, picked by `u` with density `evaluate` gives
+    pub fn sample(&self, direction: &Vec4<T>, u: (T, T)) -> Vec4<T> {
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let tiny: T = FromPrimitive::from_f64(1e-3).unwrap();
+        let g = self.g;
+
+        let cos_angle = if g.abs() < tiny {
+            T::one() - two * u.0
+        } else {
+            let s = (T::one() - g * g) / (T::one() - g + two * g * u.0);
+            (T::one() + g * g - s * s) / (two * g)
+        };
+        let cos_angle = cos_angle.max(-T::one()).min(T::one());
+        let sin_angle = (T::one() - cos_angle * cos_angle).max(T::zero()).sqrt();
+        let phi = two * pi * u.1;
+
+        let direction = direction.normalized();
+        let (tangent, bitangent) = direction.basis();
+        let across =
+            &(&tangent * (sin_angle * phi.cos())) + &(&bitangent * (sin_angle * phi.sin()));
+        &across + &(&direction * cos_angle)
+    }
+}
+
+/// How far light goes before running into a medium with extinction
+/// coefficient `extinction`, picked by `u`
+pub(crate) fn free_flight<T: Float>(extinction: T, u: T) -> T {
+    -(T::one() - u).ln() / extinction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_integrates_to_one() {
+        for g in [-0.7, 0.0, 0.3, 0.9] {
+            let hg = HenyeyGreenstein::new(g);
+            // Over the sphere, in slices of equal solid angle
+            let n = 100000;
+            let total: f64 = (0..n)
+                .map(|i| hg.evaluate(1.0 - 2.0 * (i as f64 + 0.5) / n as f64))
+                .sum();
+            let integral = total * 4.0 * std::f64::consts::PI / n as f64;
+            assert!((integral - 1.0).abs() < 1e-3, "{} {}", g, integral);
+        }
+    }
+
+    #[test]
+    fn phase_sampling() {
+        let hg = HenyeyGreenstein::new(0.6);
+        let along = Vec4::direction(0.0, 1.0, 0.0);
+        let mut rng = Rng::new(4);
+
+        // The average cosine is g
+        let n = 20000;
+        let mut total = 0.0;
+        for _ in 0..n {
+            let d = hg.sample(&along, (rng.next_float(), rng.next_float()));
+            assert!((d.mag() - 1.0).abs() < 1e-9);
+            total += d.y;
+        }
+        assert!(
+            (total / n as f64 - 0.6).abs() < 0.02,
+            "{}",
+            total / n as f64
+        );
+
+        // The ends of the range go straight on and straight back
+        assert!((hg.sample(&along, (0.999_999, 0.2)).y - 1.0).abs() < 1e-3);
+        assert!((hg.sample(&along, (0.0, 0.2)).y + 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,152 @@
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::object::Intersectable;
+
+/// A medium filling the inside of a closed object, such as a `Sphere` of
+/// smoke. The object itself isn't seen; add it to the scene as well for a
+/// surface around the medium, like a glass of murky water.
+pub struct Volume<T: Float> {
+    boundary: Box<dyn Intersectable<T>>,
+    medium: Box<dyn Medium<T>>,
+}
+
+impl<T> Volume<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn new(boundary: Box<dyn Intersectable<T>>, medium: Box<dyn Medium<T>>) -> Volume<T> {
+        Volume { boundary, medium }
+    }
+
+    /// The stretches of `ray` that lie inside the boundary, nearest first
+    fn inside(&self, ray: &Ray<T>) -> Vec<Ray<T>> {
+        // Whether the ray starts inside depends on what happens before it does,
+        // so follow the whole line through the boundary, as `Csg` does
+        let line = Ray {
+            t_min: T::neg_infinity(),
+            t_max: T::infinity(),
+            ..*ray
+        };
+        let crossings = self.boundary.crossings(&line);
+
+        // Leaving first means the line started inside
+        let mut entered = match crossings.first() {
+            Some(hit) if !hit.front_face => Some(T::neg_infinity()),
+            _ => None,
+        };
+        let mut spans = vec![];
+        for crossing in &crossings {
+            if crossing.front_face {
+                entered = entered.or(Some(crossing.t));
+            } else if let Some(t) = entered.take() {
+                spans.push((t, crossing.t));
+            }
+        }
+        // Entered but never left
+        if let Some(t) = entered {
+            spans.push((t, T::infinity()));
+        }
+
+        spans
+            .into_iter()
+            .map(|(start, end)| (start.max(ray.t_min), end.min(ray.t_max)))
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| Ray {
+                time: ray.time,
+                ..Ray::segment(ray.origin, ray.direction, start, end)
+            })
+            .collect()
+    }
+}
+
+impl<T> Medium<T> for Volume<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn sample_scatter(&self, rng: &mut Rng, ray: &Ray<T>) -> Option<T> {
+        self.inside(ray)
+            .iter()
+            .find_map(|stretch| self.medium.sample_scatter(rng, stretch))
+    }
+
+    fn transmittance(&self, rng: &mut Rng, ray: &Ray<T>) -> T {
+        self.inside(ray).iter().fold(T::one(), |t, stretch| {
+            t * self.medium.transmittance(rng, stretch)
+        })
+    }
+
+    fn albedo(&self) -> T {
+        self.medium.albedo()
+    }
+
+    fn phase(&self) -> HenyeyGreenstein<T> {
+        self.medium.phase()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::medium::homogeneous::Homogeneous;
+    use crate::object::sphere::Sphere;
+
+    fn smoke_ball() -> Volume<f64> {
+        Volume::new(
+            Box::new(Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap()),
+            Box::new(Homogeneous::new(0.5, 0.5, 0.0)),
+        )
+    }
+
+    #[test]
+    fn only_inside() {
+        let ball = smoke_ball();
+        let mut rng = Rng::new(2);
+        let x = Vec4::direction(1.0, 0.0, 0.0);
+
+        // Straight through the middle crosses 2 units of it
+        let through = Ray::new(Vec4::position(-5.0, 0.0, 0.0), x);
+        assert!((ball.transmittance(&mut rng, &through) - (-2.0f64).exp()).abs() < 1e-12);
+        for _ in 0..100 {
+            if let Some(t) = ball.sample_scatter(&mut rng, &through) {
+                assert!((4.0..=6.0).contains(&t), "{}", t);
+            }
+        }
+
+        // From the middle, 1 unit
+        let out = Ray::new(Vec4::position(0.0, 0.0, 0.0), x);
+        assert!((ball.transmittance(&mut rng, &out) - (-1.0f64).exp()).abs() < 1e-12);
+
+        // Missing it, or stopping short
+        let past = Ray::new(Vec4::position(-5.0, 2.0, 0.0), x);
+        assert_eq!(1.0, ball.transmittance(&mut rng, &past));
+        let short = Ray::segment(Vec4::position(-5.0, 0.0, 0.0), x, 0.0, 3.0);
+        assert_eq!(None, ball.sample_scatter(&mut rng, &short));
+        // Stopping halfway in
+        let half = Ray::segment(Vec4::position(-5.0, 0.0, 0.0), x, 0.0, 5.0);
+        assert!((ball.transmittance(&mut rng, &half) - (-1.0f64).exp()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn wholly_inside() {
+        // A segment that never reaches the boundary still runs through the medium
+        let ball = Volume::new(
+            Box::new(Sphere::new(Vec4::position(0.0, 0.0, 0.0), 10.0).unwrap()),
+            Box::new(Homogeneous::new(0.5, 0.5, 0.0)),
+        );
+        let mut rng = Rng::new(3);
+        let x = Vec4::direction(1.0, 0.0, 0.0);
+        let segment = Ray::segment(Vec4::position(0.0, 0.0, 0.0), x, 0.0, 5.0);
+        assert!((ball.transmittance(&mut rng, &segment) - (-5.0f64).exp()).abs() < 1e-12);
+
+        let scattered = (0..1000)
+            .filter_map(|_| ball.sample_scatter(&mut rng, &segment))
+            .inspect(|t| assert!((0.0..=5.0).contains(t), "{}", t))
+            .count();
+        assert!(scattered > 950, "{}", scattered);
+
+        // Nor does one that starts partway along the ray
+        let later = Ray::segment(Vec4::position(-20.0, 0.0, 0.0), x, 12.0, 14.0);
+        assert!((ball.transmittance(&mut rng, &later) - (-2.0f64).exp()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,231 @@
+use num::{Float, FromPrimitive};
+
+use crate::matrix::Mat4;
+use crate::vector::Vec4;
+
+/// Where something is at one instant: turned by a rotation, then moved by a
+/// translation
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Pose<T: Float> {
+    translation: Vec4<T>,
+    // A unit quaternion, as (w, x, y, z), so in-between rotations are easy to find
+    rotation: [T; 4],
+}
+
+impl<T> Pose<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Rotated by `angle` radians about `axis`, then moved by `translation`
+    pub fn new(translation: Vec4<T>, axis: &Vec4<T>, angle: T) -> Pose<T> {
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        let a = Vec4::direction(axis.x, axis.y, axis.z).normalized();
+        let (s, c) = (angle * half).sin_cos();
+
+        Pose {
+            translation: Vec4::direction(translation.x, translation.y, translation.z),
+            rotation: [c, a.x * s, a.y * s, a.z * s],
+        }
+    }
+
+    /// Just moved by `translation`
+    pub fn translation(translation: Vec4<T>) -> Pose<T> {
+        Pose::new(
+            translation,
+            &Vec4::direction(T::zero(), T::one(), T::zero()),
+            T::zero(),
+        )
+    }
+
+    /// Left where it is
+    pub fn identity() -> Pose<T> {
+        Pose::translation(Vec4::direction(T::zero(), T::zero(), T::zero()))
+    }
+
+    pub fn matrix(&self) -> Mat4<T> {
+        &self.rotation_matrix() * &Mat4::translation(&self.translation)
+    }
+
+    /// Undoes `matrix`. A pose only turns and moves, so this can't fail.
+    pub fn inverse_matrix(&self) -> Mat4<T> {
+        let back = Vec4::direction(T::zero(), T::zero(), T::zero());
+        let back = &back - &self.translation;
+        &Mat4::translation(&back) * &self.rotation_matrix().transpose()
+    }
+
+    fn rotation_matrix(&self) -> Mat4<T> {
+        let [w, x, y, z] = self.rotation;
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let tiny: T = FromPrimitive::from_f64(1e-12).unwrap();
+
+        let s = (T::one() - w * w).max(T::zero()).sqrt();
+        if s < tiny {
+            Mat4::i()
+        } else {
+            let angle = two * w.max(-T::one()).min(T::one()).acos();
+            Mat4::rotation(&Vec4::direction(x / s, y / s, z / s), angle)
+        }
+    }
+
+    /// Part way from this pose to `other`: the translation in a straight line and
+    /// the rotation at a steady rate along the shorter way round
+    pub fn interpolate(&self, other: &Pose<T>, fraction: T) -> Pose<T> {
+        let translation =
+            &self.translation + &(&(&other.translation - &self.translation) * fraction);
+
+        let p = self.rotation;
+        let mut q = other.rotation;
+        let mut cos_theta = (0..4).fold(T::zero(), |sum, i| sum + p[i] * q[i]);
+        if cos_theta < T::zero() {
+            q = [-q[0], -q[1], -q[2], -q[3]];
+            cos_theta = -cos_theta;
+        }
+
+        // Nearly the same rotation, where slerp divides by almost nothing
+        let close: T = FromPrimitive::from_f64(0.9995).unwrap();
+        let (a, b) = if cos_theta > close {
+            (T::one() - fraction, fraction)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((T::one() - fraction) * theta).sin() / sin_theta,
+                (fraction * theta).sin() / sin_theta,
+            )
+        };
+
+        let mut rotation = [T::zero(); 4];
+        for i in 0..4 {
+            rotation[i] = a * p[i] + b * q[i];
+        }
+        let length = rotation
+            .iter()
+            .fold(T::zero(), |sum, c| sum + *c * *c)
+            .sqrt();
+        for c in rotation.iter_mut() {
+            *c = *c / length;
+        }
+
+        Pose {
+            translation,
+            rotation,
+        }
+    }
+}
+
+/// How something moves while the shutter is open, from `start` at time 0 to
+/// `end` at time 1
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Motion<T: Float> {
+    pub start: Pose<T>,
+    pub end: Pose<T>,
+}
+
+impl<T> Motion<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn new(start: Pose<T>, end: Pose<T>) -> Motion<T> {
+        Motion { start, end }
+    }
+
+    /// The transform at `time`, which is held at the ends outside [0, 1]
+    pub fn at(&self, time: T) -> Mat4<T> {
+        self.pose_at(time).matrix()
+    }
+
+    /// The inverse of the transform at `time`
+    pub fn inverse_at(&self, time: T) -> Mat4<T> {
+        self.pose_at(time).inverse_matrix()
+    }
+
+    fn pose_at(&self, time: T) -> Pose<T> {
+        let time = time.max(T::zero()).min(T::one());
+        self.start.interpolate(&self.end, time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: &Vec4<f64>, actual: &Vec4<f64>) {
+        assert!((expected - actual).mag() < 1e-9, "{:?}", actual);
+        assert_eq!(expected.w, actual.w);
+    }
+
+    #[test]
+    fn pose_matrix() {
+        let pose = Pose::new(
+            Vec4::position(1.0, 2.0, 3.0),
+            &Vec4::direction(0.0, 0.0, 1.0),
+            std::f64::consts::FRAC_PI_2,
+        );
+        let m = pose.matrix();
+
+        // Turned first, then moved
+        assert_close(
+            &Vec4::position(1.0, 3.0, 3.0),
+            &(&m * &Vec4::position(1.0, 0.0, 0.0)),
+        );
+        assert_close(
+            &Vec4::direction(0.0, 1.0, 0.0),
+            &(&m * &Vec4::direction(1.0, 0.0, 0.0)),
+        );
+        assert_eq!(Mat4::i(), Pose::<f64>::identity().matrix());
+
+        let p = Vec4::position(0.5, -2.0, 4.0);
+        assert_close(&p, &(&pose.inverse_matrix() * &(&m * &p)));
+    }
+
+    #[test]
+    fn translation() {
+        let motion = Motion::new(
+            Pose::translation(Vec4::direction(-2.0, 0.0, 0.0)),
+            Pose::translation(Vec4::direction(2.0, 4.0, 0.0)),
+        );
+        let origin = Vec4::position(0.0, 0.0, 0.0);
+
+        assert_close(
+            &Vec4::position(-2.0, 0.0, 0.0),
+            &(&motion.at(0.0) * &origin),
+        );
+        assert_close(
+            &Vec4::position(1.0, 3.0, 0.0),
+            &(&motion.at(0.75) * &origin),
+        );
+        assert_close(&Vec4::position(2.0, 4.0, 0.0), &(&motion.at(1.0) * &origin));
+        // Held at the ends
+        assert_close(&Vec4::position(2.0, 4.0, 0.0), &(&motion.at(2.0) * &origin));
+    }
+
+    #[test]
+    fn rotation() {
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+        let motion = Motion::new(
+            Pose::identity(),
+            Pose::new(Vec4::direction(0.0, 0.0, 0.0), &up, 3.0),
+        );
+        let x = Vec4::direction(1.0, 0.0, 0.0);
+
+        // A steady rate of turn, rather than a straight line between the ends
+        for &time in &[0.0, 0.25, 0.5, 1.0] {
+            let expected = &Mat4::rotation(&up, 3.0 * time) * &x;
+            assert_close(&expected, &(&motion.at(time) * &x));
+        }
+    }
+
+    #[test]
+    fn shorter_way_round() {
+        let up = Vec4::direction(0.0, 1.0, 0.0);
+        let origin = Vec4::direction(0.0, 0.0, 0.0);
+        // 350 degrees is the same as -10, so halfway is -5 rather than 175
+        let motion = Motion::new(
+            Pose::identity(),
+            Pose::new(origin, &up, 350.0f64.to_radians()),
+        );
+        let x = Vec4::direction(1.0, 0.0, 0.0);
+        let expected = &Mat4::rotation(&up, -5.0f64.to_radians()) * &x;
+        assert_close(&expected, &(&motion.at(0.5) * &x));
+    }
+}
@@ -0,0 +1,239 @@
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::material::Material;
+use crate::matrix::Mat4;
+use crate::polynomial::solve_quadratic;
+use crate::ray::Ray;
+use crate::vector::Vec4;
+
+/// In object space, a cone with a base of radius 1 on y = 0 and its point at
+/// y = 1, optionally closed off at the base
+#[derive(Debug)]
+pub struct Cone<T: Float> {
+    object: Mat4<T>,
+    object_inverse: Mat4<T>,
+    capped: bool,
+    material: Material<T>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    Side,
+    Base,
+}
+
+impl<T: Float> WorldObject<T> for Cone<T> {
+    fn object_matrix(&self) -> &Mat4<T> {
+        &self.object
+    }
+
+    fn object_matrix_inv(&self) -> &Mat4<T> {
+        &self.object_inverse
+    }
+}
+
+impl<T> Cone<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    /// Upright, standing on `base`
+    pub fn new(base: Vec4<T>, radius: T, height: T, capped: bool) -> Result<Cone<T>> {
+        check_positive(radius, "cone radius")?;
+        check_positive(height, "cone height")?;
+
+        let scale = Mat4::scale(&Vec4::direction(radius, height, radius));
+        let object_matrix = &scale * &Mat4::translation(&base);
+
+        Ok(Cone {
+            object_inverse: object_matrix.inverse()?,
+            object: object_matrix,
+            capped,
+            material: Material::default(),
+        })
+    }
+
+    pub fn set_material(&mut self, material: Material<T>) {
+        self.material = material;
+    }
+}
+
+impl<T> Cone<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Everywhere the line through the ray meets the surface, nearest first
+    fn roots(&self, ray: &Ray<T>) -> Vec<(T, Part)> {
+        let local = ray.transformed(self.object_matrix_inv());
+        let (o, d) = (local.origin, local.direction);
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let within_height = |t: T| {
+            let y = o.y + d.y * t;
+            y >= T::zero() && y <= T::one()
+        };
+
+        // x^2 + z^2 = (1 - y)^2, where 1 - y runs down from the point
+        let k = T::one() - o.y;
+        let mut roots: Vec<(T, Part)> = solve_quadratic(
+            d.x * d.x + d.z * d.z - d.y * d.y,
+            two * (o.x * d.x + o.z * d.z + k * d.y),
+            o.x * o.x + o.z * o.z - k * k,
+        )
+        .into_iter()
+        .filter(|t| within_height(*t))
+        .map(|t| (t, Part::Side))
+        .collect();
+
+        if self.capped && d.y != T::zero() {
+            let t = -o.y / d.y;
+            let (x, z) = (o.x + d.x * t, o.z + d.z * t);
+            if x * x + z * z <= T::one() {
+                roots.push((t, Part::Base));
+            }
+        }
+
+        roots.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        roots
+    }
+
+    fn hit(&self, ray: &Ray<T>, t: T, part: Part) -> HitRecord<'_, T> {
+        let point = ray.at(t);
+        let p = self.object_matrix_inv() * &point;
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+
+        let (normal, tangent, uv) = match part {
+            Part::Side => {
+                let (u, tangent) = around_y_axis(&p);
+                // Straight up at the point itself, where the surface has no normal
+                let normal = if p.x == T::zero() && p.z == T::zero() {
+                    Vec4::direction(T::zero(), T::one(), T::zero())
+                } else {
+                    Vec4::direction(p.x, T::one() - p.y, p.z)
+                };
+                (normal, tangent, (u, T::one() - p.y))
+            }
+            Part::Base => (
+                Vec4::direction(T::zero(), -T::one(), T::zero()),
+                Vec4::direction(T::one(), T::zero(), T::zero()),
+                ((p.x + T::one()) * half, (p.z + T::one()) * half),
+            ),
+        };
+
+        let normal = self.normal_to_world(&normal);
+        HitRecord::new(
+            &ray.direction,
+            t,
+            point,
+            &normal,
+            &normal,
+            &self.direction_to_world(&tangent),
+            uv,
+            &self.material,
+        )
+    }
+}
+
+impl<T> Intersectable<T> for Cone<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        match self.roots(ray).into_iter().find(|(t, _)| ray.contains(*t)) {
+            Some((t, part)) => IntersectResult::Intersect(self.hit(ray, t, part)),
+            None => IntersectResult::NoIntersect,
+        }
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        self.roots(ray)
+            .into_iter()
+            .filter(|(t, _)| ray.contains(*t))
+            .map(|(t, part)| self.hit(ray, t, part))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cone(capped: bool) -> Cone<f64> {
+        Cone::new(Vec4::position(0.0, 0.0, 0.0), 1.0, 1.0, capped).unwrap()
+    }
+
+    #[test]
+    fn cone_intersect() {
+        let c = cone(true);
+        // Halfway up, the cone is half as wide
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.5, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+
+        match c.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 9.5).abs() < 1e-12);
+                let expected = Vec4::direction(0.0, 1.0, -1.0).normalized();
+                assert!((&hit.geometric_normal - &expected).mag() < 1e-12);
+            }
+            _ => panic!("expected an intersection"),
+        }
+        assert_eq!(2, c.crossings(&ray).len());
+    }
+
+    #[test]
+    fn cone_misses_above_point() {
+        // The mathematical cone carries on above y = 1, but this one doesn't
+        let ray = Ray::new(
+            Vec4::position(0.0, 1.5, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        assert!(matches!(
+            cone(true).intersect(&ray),
+            IntersectResult::NoIntersect
+        ));
+    }
+
+    #[test]
+    fn cone_base() {
+        let up = Ray::new(
+            Vec4::position(0.5, -10.0, 0.0),
+            Vec4::direction(0.0, 1.0, 0.0),
+        );
+
+        match cone(true).intersect(&up) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(10.0, hit.t);
+                assert_eq!(Vec4::direction(0.0, -1.0, 0.0), hit.geometric_normal);
+            }
+            _ => panic!("expected an intersection"),
+        }
+
+        // Without the base, the ray goes in and hits the inside of the side
+        match cone(false).intersect(&up) {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 10.5).abs() < 1e-12);
+                assert!(!hit.front_face);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn cone_parallel_to_side() {
+        // Parallel to the side: only one root, from the linear case
+        let c = cone(false);
+        let ray = Ray::new(
+            Vec4::position(-1.0, -1.0, 0.0),
+            Vec4::direction(1.0, 1.0, 0.0),
+        );
+
+        match c.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 1.5).abs() < 1e-12);
+                assert!((&hit.point - &Vec4::position(0.5, 0.5, 0.0)).mag() < 1e-12);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::cmp::Ordering;
+
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::ray::Ray;
+
+/// How the two halves of a `Csg` are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Inside either
+    Union,
+    /// Inside both
+    Intersection,
+    /// Inside the first but not the second
+    Difference,
+}
+
+impl Operation {
+    fn inside(self, left: bool, right: bool) -> bool {
+        match self {
+            Operation::Union => left || right,
+            Operation::Intersection => left && right,
+            Operation::Difference => left && !right,
+        }
+    }
+}
+
+/// Constructive solid geometry: a solid made by combining two others. Both halves
+/// should be closed (or, like planes, divide space in two) for inside and outside
+/// to make sense. Each part of the surface keeps the material of the object it
+/// came from.
+///
+/// Only part of each half's surface is left, so picking points on it to sample
+/// it as a light won't work; halves that glow are turned away.
+pub struct Csg<T: Float> {
+    operation: Operation,
+    left: Box<dyn Intersectable<T>>,
+    right: Box<dyn Intersectable<T>>,
+}
+
+impl<T> Csg<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Fails if either half has an emissive material
+    pub fn new(
+        operation: Operation,
+        left: Box<dyn Intersectable<T>>,
+        right: Box<dyn Intersectable<T>>,
+    ) -> Result<Csg<T>> {
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        for object in [&left, &right] {
+            if object.sample_emission(half, (half, half), None).is_some() {
+                return Err(Error::InvalidScene(
+                    "a CSG solid can't be made from glowing objects".to_string(),
+                ));
+            }
+        }
+        Ok(Csg {
+            operation,
+            left,
+            right,
+        })
+    }
+
+    pub fn union(
+        left: Box<dyn Intersectable<T>>,
+        right: Box<dyn Intersectable<T>>,
+    ) -> Result<Csg<T>> {
+        Csg::new(Operation::Union, left, right)
+    }
+
+    pub fn intersection(
+        left: Box<dyn Intersectable<T>>,
+        right: Box<dyn Intersectable<T>>,
+    ) -> Result<Csg<T>> {
+        Csg::new(Operation::Intersection, left, right)
+    }
+
+    /// `left` with `right` cut out of it
+    pub fn difference(
+        left: Box<dyn Intersectable<T>>,
+        right: Box<dyn Intersectable<T>>,
+    ) -> Result<Csg<T>> {
+        Csg::new(Operation::Difference, left, right)
+    }
+}
+
+/// Whether the line a list of crossings was taken along starts out inside the
+/// solid: it does if the first thing it does is leave
+fn starts_inside<T: Float>(crossings: &[HitRecord<T>]) -> bool {
+    matches!(crossings.first(), Some(hit) if !hit.front_face)
+}
+
+impl<T> Intersectable<T> for Csg<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        match self.crossings(ray).into_iter().next() {
+            Some(hit) => IntersectResult::Intersect(hit),
+            None => IntersectResult::NoIntersect,
+        }
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        // Whether the ray starts inside either half depends on what happens before
+        // it does, so follow the whole line through it
+        let line = Ray {
+            t_min: T::neg_infinity(),
+            t_max: T::infinity(),
+            ..*ray
+        };
+        let left = self.left.crossings(&line);
+        let right = self.right.crossings(&line);
+
+        let mut in_left = starts_inside(&left);
+        let mut in_right = starts_inside(&right);
+        let mut inside = self.operation.inside(in_left, in_right);
+
+        let mut left = left.into_iter().peekable();
+        let mut right = right.into_iter().peekable();
+        let mut crossings = vec![];
+
+        loop {
+            let take_left = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => l.t.partial_cmp(&r.t) != Some(Ordering::Greater),
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+
+            let mut hit = if take_left {
+                let hit = left.next().unwrap();
+                in_left = hit.front_face;
+                hit
+            } else {
+                let hit = right.next().unwrap();
+                in_right = hit.front_face;
+                hit
+            };
+
+            // Only crossings that change whether we're inside the combination are
+            // part of its surface. Normals already face the ray; all that changes
+            // (for surfaces cut out by a difference) is which way through we went.
+            let now_inside = self.operation.inside(in_left, in_right);
+            if now_inside != inside {
+                inside = now_inside;
+                hit.front_face = now_inside;
+                if ray.contains(hit.t) {
+                    crossings.push(hit);
+                }
+            }
+        }
+
+        crossings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::sphere::Sphere;
+    use crate::object::tests::glowing_sphere;
+
+    /// Two unit spheres, overlapping between x = 0 and x = 1
+    fn pair(operation: Operation) -> Csg<f64> {
+        Csg::new(
+            operation,
+            Box::new(Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap()),
+            Box::new(Sphere::new(Vec4::position(1.0, 0.0, 0.0), 1.0).unwrap()),
+        )
+        .unwrap()
+    }
+
+    /// Where a ray along the x axis from x = -10 crosses the surface, and whether
+    /// each crossing is on the way in
+    fn along_x(csg: &Csg<f64>) -> Vec<(f64, bool)> {
+        let ray = Ray::new(
+            Vec4::position(-10.0, 0.0, 0.0),
+            Vec4::direction(1.0, 0.0, 0.0),
+        );
+        csg.crossings(&ray)
+            .iter()
+            .map(|hit| (hit.point.x, hit.front_face))
+            .collect()
+    }
+
+    #[test]
+    fn union() {
+        assert_eq!(
+            vec![(-1.0, true), (2.0, false)],
+            along_x(&pair(Operation::Union))
+        );
+    }
+
+    #[test]
+    fn intersection() {
+        assert_eq!(
+            vec![(0.0, true), (1.0, false)],
+            along_x(&pair(Operation::Intersection))
+        );
+    }
+
+    #[test]
+    fn difference() {
+        let csg = pair(Operation::Difference);
+        assert_eq!(vec![(-1.0, true), (0.0, false)], along_x(&csg));
+
+        // The cut face is lit from inside the bite taken out of the sphere
+        let ray = Ray::new(
+            Vec4::position(0.5, 0.0, 0.0),
+            Vec4::direction(-1.0, 0.0, 0.0),
+        );
+        match csg.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(0.5, hit.t);
+                assert!(hit.front_face);
+                assert_eq!(Vec4::direction(1.0, 0.0, 0.0), hit.geometric_normal);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn starting_inside() {
+        let csg = pair(Operation::Union);
+        let ray = Ray::new(
+            Vec4::position(0.5, 0.0, 0.0),
+            Vec4::direction(1.0, 0.0, 0.0),
+        );
+
+        match csg.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(1.5, hit.t);
+                assert!(!hit.front_face);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn glowing_halves() {
+        assert!(matches!(
+            Csg::union(
+                Box::new(Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap()),
+                Box::new(glowing_sphere(Vec4::position(1.0, 0.0, 0.0), 1.0)),
+            ),
+            Err(Error::InvalidScene(_))
+        ));
+    }
+
+    #[test]
+    fn miss() {
+        let csg = pair(Operation::Intersection);
+        let ray = Ray::new(
+            Vec4::position(-10.0, 5.0, 0.0),
+            Vec4::direction(1.0, 0.0, 0.0),
+        );
+        assert!(matches!(csg.intersect(&ray), IntersectResult::NoIntersect));
+    }
+}
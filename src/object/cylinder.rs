@@ -0,0 +1,255 @@
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::material::Material;
+use crate::matrix::Mat4;
+use crate::polynomial::solve_quadratic;
+use crate::ray::Ray;
+use crate::vector::Vec4;
+
+/// In object space, a cylinder of radius 1 around the y axis from y = 0 to y = 1,
+/// optionally closed off at each end
+#[derive(Debug)]
+pub struct Cylinder<T: Float> {
+    object: Mat4<T>,
+    object_inverse: Mat4<T>,
+    capped: bool,
+    material: Material<T>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    Side,
+    Bottom,
+    Top,
+}
+
+impl<T: Float> WorldObject<T> for Cylinder<T> {
+    fn object_matrix(&self) -> &Mat4<T> {
+        &self.object
+    }
+
+    fn object_matrix_inv(&self) -> &Mat4<T> {
+        &self.object_inverse
+    }
+}
+
+impl<T> Cylinder<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    /// Upright, standing on `base`
+    pub fn new(base: Vec4<T>, radius: T, height: T, capped: bool) -> Result<Cylinder<T>> {
+        check_positive(radius, "cylinder radius")?;
+        check_positive(height, "cylinder height")?;
+
+        let scale = Mat4::scale(&Vec4::direction(radius, height, radius));
+        let object_matrix = &scale * &Mat4::translation(&base);
+
+        Ok(Cylinder {
+            object_inverse: object_matrix.inverse()?,
+            object: object_matrix,
+            capped,
+            material: Material::default(),
+        })
+    }
+
+    pub fn set_material(&mut self, material: Material<T>) {
+        self.material = material;
+    }
+}
+
+impl<T> Cylinder<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Everywhere the line through the ray meets the surface, nearest first
+    fn roots(&self, ray: &Ray<T>) -> Vec<(T, Part)> {
+        let local = ray.transformed(self.object_matrix_inv());
+        let (o, d) = (local.origin, local.direction);
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let within_height = |t: T| {
+            let y = o.y + d.y * t;
+            y >= T::zero() && y <= T::one()
+        };
+
+        let mut roots: Vec<(T, Part)> = solve_quadratic(
+            d.x * d.x + d.z * d.z,
+            two * (o.x * d.x + o.z * d.z),
+            o.x * o.x + o.z * o.z - T::one(),
+        )
+        .into_iter()
+        .filter(|t| within_height(*t))
+        .map(|t| (t, Part::Side))
+        .collect();
+
+        if self.capped && d.y != T::zero() {
+            for &(height, part) in &[(T::zero(), Part::Bottom), (T::one(), Part::Top)] {
+                let t = (height - o.y) / d.y;
+                let (x, z) = (o.x + d.x * t, o.z + d.z * t);
+                if x * x + z * z <= T::one() {
+                    roots.push((t, part));
+                }
+            }
+        }
+
+        roots.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        roots
+    }
+
+    fn hit(&self, ray: &Ray<T>, t: T, part: Part) -> HitRecord<'_, T> {
+        let point = ray.at(t);
+        let p = self.object_matrix_inv() * &point;
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+
+        let (normal, tangent, uv) = match part {
+            Part::Side => {
+                let (u, tangent) = around_y_axis(&p);
+                (
+                    Vec4::direction(p.x, T::zero(), p.z),
+                    tangent,
+                    (u, T::one() - p.y),
+                )
+            }
+            Part::Bottom | Part::Top => {
+                let up = if let Part::Top = part {
+                    T::one()
+                } else {
+                    -T::one()
+                };
+                (
+                    Vec4::direction(T::zero(), up, T::zero()),
+                    Vec4::direction(T::one(), T::zero(), T::zero()),
+                    ((p.x + T::one()) * half, (p.z + T::one()) * half),
+                )
+            }
+        };
+
+        let normal = self.normal_to_world(&normal);
+        HitRecord::new(
+            &ray.direction,
+            t,
+            point,
+            &normal,
+            &normal,
+            &self.direction_to_world(&tangent),
+            uv,
+            &self.material,
+        )
+    }
+}
+
+impl<T> Intersectable<T> for Cylinder<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        match self.roots(ray).into_iter().find(|(t, _)| ray.contains(*t)) {
+            Some((t, part)) => IntersectResult::Intersect(self.hit(ray, t, part)),
+            None => IntersectResult::NoIntersect,
+        }
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        self.roots(ray)
+            .into_iter()
+            .filter(|(t, _)| ray.contains(*t))
+            .map(|(t, part)| self.hit(ray, t, part))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cylinder(capped: bool) -> Cylinder<f64> {
+        Cylinder::new(Vec4::position(0.0, -1.0, 0.0), 1.0, 2.0, capped).unwrap()
+    }
+
+    #[test]
+    fn cylinder_intersect() {
+        let c = cylinder(true);
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+
+        match c.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(9.0, hit.t);
+                assert_eq!(Vec4::direction(0.0, 0.0, -1.0), hit.geometric_normal);
+                assert!(hit.front_face);
+            }
+            _ => panic!("expected an intersection"),
+        }
+        assert_eq!(2, c.crossings(&ray).len());
+    }
+
+    #[test]
+    fn cylinder_misses_beyond_ends() {
+        let c = cylinder(true);
+        let ray = Ray::new(
+            Vec4::position(0.0, 1.5, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        assert!(matches!(c.intersect(&ray), IntersectResult::NoIntersect));
+    }
+
+    #[test]
+    fn cylinder_caps() {
+        let down = Ray::new(
+            Vec4::position(0.5, 10.0, 0.0),
+            Vec4::direction(0.0, -1.0, 0.0),
+        );
+
+        match cylinder(true).intersect(&down) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(9.0, hit.t);
+                assert_eq!(Vec4::direction(0.0, 1.0, 0.0), hit.geometric_normal);
+            }
+            _ => panic!("expected an intersection"),
+        }
+
+        // Without caps, the ray goes straight down the tube
+        assert!(matches!(
+            cylinder(false).intersect(&down),
+            IntersectResult::NoIntersect
+        ));
+    }
+
+    #[test]
+    fn cylinder_from_inside() {
+        let c = cylinder(false);
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::direction(1.0, 0.0, 0.0),
+        );
+
+        match c.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(1.0, hit.t);
+                assert!(!hit.front_face);
+                assert_eq!(Vec4::direction(-1.0, 0.0, 0.0), hit.geometric_normal);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn cylinder_uv() {
+        let c = cylinder(false);
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, 10.0),
+            Vec4::direction(0.0, 0.0, -1.0),
+        );
+
+        match c.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!((0.5, 0.5), hit.uv);
+                assert_eq!(Vec4::direction(1.0, 0.0, 0.0), hit.tangent);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+}
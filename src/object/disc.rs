@@ -0,0 +1,159 @@
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::material::Material;
+use crate::matrix::Mat4;
+use crate::ray::Ray;
+use crate::vector::Vec4;
+
+/// A flat round surface. In object space it has radius 1, on the y = 0 plane
+/// facing +y.
+#[derive(Debug)]
+pub struct Disc<T: Float> {
+    object: Mat4<T>,
+    object_inverse: Mat4<T>,
+    material: Material<T>,
+}
+
+impl<T: Float> WorldObject<T> for Disc<T> {
+    fn object_matrix(&self) -> &Mat4<T> {
+        &self.object
+    }
+
+    fn object_matrix_inv(&self) -> &Mat4<T> {
+        &self.object_inverse
+    }
+}
+
+impl<T> Disc<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    /// The disc around `centre`, facing along `normal`
+    pub fn new(centre: Vec4<T>, normal: Vec4<T>, radius: T) -> Result<Disc<T>> {
+        check_positive(radius, "disc radius")?;
+        check_positive(normal.mag(), "disc normal length")?;
+
+        let normal = Vec4::direction(normal.x, normal.y, normal.z).normalized();
+        let (tangent, bitangent) = normal.basis();
+
+        let scale = Mat4::scale(&Vec4::direction(radius, radius, radius));
+        let placement = Mat4::camera(&bitangent, &tangent, &normal, &centre);
+        let object_matrix = &scale * &placement;
+
+        Ok(Disc {
+            object_inverse: object_matrix.inverse()?,
+            object: object_matrix,
+            material: Material::default(),
+        })
+    }
+
+    pub fn set_material(&mut self, material: Material<T>) {
+        self.material = material;
+    }
+}
+
+impl<T> Intersectable<T> for Disc<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        let local = ray.transformed(self.object_matrix_inv());
+
+        if local.direction.y == T::zero() {
+            return IntersectResult::NoIntersect;
+        }
+
+        let t = -local.origin.y / local.direction.y;
+        let p = local.at(t);
+        if !ray.contains(t) || p.x * p.x + p.z * p.z > T::one() {
+            return IntersectResult::NoIntersect;
+        }
+
+        // Mapped as a square with the disc inscribed in it
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        let uv = ((p.x + T::one()) * half, (p.z + T::one()) * half);
+
+        let normal = self.normal_to_world(&Vec4::direction(T::zero(), T::one(), T::zero()));
+        let tangent = self.direction_to_world(&Vec4::direction(T::one(), T::zero(), T::zero()));
+        IntersectResult::Intersect(HitRecord::new(
+            &ray.direction,
+            t,
+            ray.at(t),
+            &normal,
+            &normal,
+            &tangent,
+            uv,
+            &self.material,
+        ))
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        match self.intersect(ray) {
+            IntersectResult::Intersect(hit) => vec![hit],
+            IntersectResult::NoIntersect => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facing_z() -> Disc<f64> {
+        Disc::new(
+            Vec4::position(0.0, 0.0, 5.0),
+            Vec4::direction(0.0, 0.0, -1.0),
+            2.0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn disc_intersect() {
+        let d = facing_z();
+        let ray = Ray::new(
+            Vec4::position(1.0, 1.0, 0.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+
+        match d.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 5.0).abs() < 1e-12);
+                assert!((&hit.geometric_normal - &Vec4::direction(0.0, 0.0, -1.0)).mag() < 1e-12);
+                assert!(hit.front_face);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn disc_edge() {
+        let d = facing_z();
+        let outside = Ray::new(
+            Vec4::position(1.5, 1.5, 0.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        assert!(matches!(
+            d.intersect(&outside),
+            IntersectResult::NoIntersect
+        ));
+    }
+
+    #[test]
+    fn disc_from_behind() {
+        let d = facing_z();
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, 10.0),
+            Vec4::direction(0.0, 0.0, -1.0),
+        );
+
+        match d.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert!(!hit.front_face);
+                assert!((hit.uv.0 - 0.5).abs() < 1e-12 && (hit.uv.1 - 0.5).abs() < 1e-12);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::cmp::Ordering;
+
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::matrix::Mat4;
+use crate::ray::Ray;
+
+/// A node in the scene graph: a set of objects (which may themselves be groups)
+/// moved about together by the group's transform
+pub struct Group<T: Float> {
+    object: Mat4<T>,
+    object_inverse: Mat4<T>,
+    children: Vec<Box<dyn Intersectable<T>>>,
+    /// Which of the children glow, and so are sampled as lights
+    emitters: Vec<usize>,
+}
+
+impl<T: Float> WorldObject<T> for Group<T> {
+    fn object_matrix(&self) -> &Mat4<T> {
+        &self.object
+    }
+
+    fn object_matrix_inv(&self) -> &Mat4<T> {
+        &self.object_inverse
+    }
+}
+
+impl<T> Group<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// `transform` takes the children's coordinates to those of whatever the
+    /// group is placed in
+    pub fn new(transform: Mat4<T>) -> Result<Group<T>> {
+        Ok(Group {
+            object_inverse: transform.inverse()?,
+            object: transform,
+            children: vec![],
+            emitters: vec![],
+        })
+    }
+
+    pub fn add_object(&mut self, object: Box<dyn Intersectable<T>>) {
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        if object.sample_emission(half, (half, half), None).is_some() {
+            self.emitters.push(self.children.len());
+        }
+        self.children.push(object);
+    }
+}
+
+impl<T> Intersectable<T> for Group<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        // Each hit cuts the ray short, so anything found afterwards is nearer
+        let mut local = ray.transformed(self.object_matrix_inv());
+        let mut nearest = None;
+
+        for child in self.children.iter() {
+            if let IntersectResult::Intersect(hit) = child.intersect(&local) {
+                local = local.clipped(hit.t);
+                nearest = Some(hit);
+            }
+        }
+
+        match nearest {
+            Some(hit) => IntersectResult::Intersect(
+                hit.transformed(self.object_matrix(), self.object_matrix_inv()),
+            ),
+            None => IntersectResult::NoIntersect,
+        }
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        let local = ray.transformed(self.object_matrix_inv());
+        let mut crossings: Vec<_> = self
+            .children
+            .iter()
+            .flat_map(|child| child.crossings(&local))
+            .map(|hit| hit.transformed(self.object_matrix(), self.object_matrix_inv()))
+            .collect();
+        crossings.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(Ordering::Equal));
+        crossings
+    }
+
+    /// One of the glowing children, each as likely as the others, and a point
+    /// on it
+    fn sample_emission(&self, choice: T, u: (T, T), time: Option<T>) -> Option<SurfaceSample<T>> {
+        if self.emitters.is_empty() {
+            return None;
+        }
+        let count: T = FromPrimitive::from_usize(self.emitters.len()).unwrap();
+        let pick = (choice * count)
+            .to_usize()
+            .unwrap_or(0)
+            .min(self.emitters.len() - 1);
+        // What's left of the choice picks the part of the child
+        let rest = choice * count - FromPrimitive::from_usize(pick).unwrap();
+
+        let sample = self.children[self.emitters[pick]].sample_emission(rest, u, time)?;
+        let sample = SurfaceSample {
+            pdf: sample.pdf / count,
+            ..sample
+        };
+        Some(sample.transformed(self.object_matrix(), self.object_matrix_inv()))
+    }
+
+    fn emission_pdf(&self, ray: &Ray<T>) -> T {
+        let local = ray.transformed(self.object_matrix_inv());
+        let mut clipped = local;
+        let mut nearest = None;
+        for (i, child) in self.children.iter().enumerate() {
+            if let IntersectResult::Intersect(hit) = child.intersect(&clipped) {
+                clipped = clipped.clipped(hit.t);
+                nearest = Some((i, hit.geometric_normal));
+            }
+        }
+
+        match nearest {
+            Some((i, normal)) if self.emitters.contains(&i) => {
+                let count: T = FromPrimitive::from_usize(self.emitters.len()).unwrap();
+                self.children[i].emission_pdf(&local)
+                    / (count * area_scale(self.object_matrix(), &normal))
+            }
+            _ => T::zero(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::sphere::Sphere;
+    use crate::object::tests::{glowing_sphere, towards_z};
+
+    #[test]
+    fn nearest_child() {
+        let mut group = Group::new(Mat4::i()).unwrap();
+        group.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 5.0), 1.0).unwrap(),
+        ));
+        group.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap(),
+        ));
+
+        match group.intersect(&towards_z(0.0, 0.0)) {
+            IntersectResult::Intersect(hit) => assert_eq!(9.0, hit.t),
+            _ => panic!("expected an intersection"),
+        }
+        assert_eq!(4, group.crossings(&towards_z(0.0, 0.0)).len());
+    }
+
+    #[test]
+    fn nested_transforms() {
+        let mut inner = Group::new(Mat4::translation(&Vec4::direction(0.0, 2.0, 0.0))).unwrap();
+        inner.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap(),
+        ));
+
+        let mut outer = Group::new(Mat4::translation(&Vec4::direction(3.0, 0.0, 0.0))).unwrap();
+        outer.add_object(Box::new(inner));
+
+        match outer.intersect(&towards_z(3.0, 2.0)) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(9.0, hit.t);
+                assert_eq!(Vec4::position(3.0, 2.0, -1.0), hit.point);
+                assert_eq!(Vec4::direction(0.0, 0.0, -1.0), hit.geometric_normal);
+            }
+            _ => panic!("expected an intersection"),
+        }
+        assert!(matches!(
+            outer.intersect(&towards_z(0.0, 0.0)),
+            IntersectResult::NoIntersect
+        ));
+    }
+
+    #[test]
+    fn emission_from_the_glowing_children() {
+        let glowing =
+            |x: f64, radius: f64| Box::new(glowing_sphere(Vec4::position(x, 0.0, 0.0), radius));
+        let mut group = Group::new(Mat4::translation(&Vec4::direction(0.0, 5.0, 0.0))).unwrap();
+        group.add_object(glowing(-10.0, 1.0));
+        group.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap(),
+        ));
+        group.add_object(glowing(10.0, 2.0));
+
+        let n = 100;
+        let mut area = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let choice = (i as f64 + 0.5) / n as f64;
+                let u = ((j as f64 + 0.5) / n as f64, (i * j % n) as f64 / n as f64);
+                let s = group.sample_emission(choice, u, None).unwrap();
+                // Never on the sphere that doesn't glow
+                assert!(s.point.x.abs() > 5.0, "{:?}", s.point);
+                area += 1.0 / s.pdf;
+
+                let ray = Ray::new(&s.point + &(&s.normal * 3.0), s.normal.reverse());
+                assert!((group.emission_pdf(&ray) - s.pdf).abs() < 1e-9);
+            }
+        }
+
+        // Both glowing spheres' surfaces, and nothing else
+        let expected = 4.0 * std::f64::consts::PI * (1.0 + 4.0);
+        assert!((area / (n * n) as f64 - expected).abs() < 1e-6, "{}", area);
+        assert_eq!(0.0, group.emission_pdf(&towards_z(0.0, 5.0)));
+    }
+}
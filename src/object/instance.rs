@@ -0,0 +1,182 @@
+use std::rc::Rc;
+
+use num::Float;
+
+use super::*;
+use crate::matrix::Mat4;
+use crate::ray::Ray;
+
+/// A placement of some shared geometry. Many instances can refer to the same
+/// object (a mesh, say) without copying it, each putting it somewhere different.
+pub struct Instance<T: Float> {
+    geometry: Rc<dyn Intersectable<T>>,
+    object: Mat4<T>,
+    object_inverse: Mat4<T>,
+}
+
+impl<T: Float> WorldObject<T> for Instance<T> {
+    fn object_matrix(&self) -> &Mat4<T> {
+        &self.object
+    }
+
+    fn object_matrix_inv(&self) -> &Mat4<T> {
+        &self.object_inverse
+    }
+}
+
+impl<T> Instance<T>
+where
+    T: Float,
+{
+    /// `transform` takes the geometry's own coordinates to those of whatever the
+    /// instance is placed in
+    pub fn new(geometry: Rc<dyn Intersectable<T>>, transform: Mat4<T>) -> Result<Instance<T>> {
+        Ok(Instance {
+            geometry,
+            object_inverse: transform.inverse()?,
+            object: transform,
+        })
+    }
+}
+
+impl<T> Intersectable<T> for Instance<T>
+where
+    T: Float,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        let local = ray.transformed(self.object_matrix_inv());
+        match self.geometry.intersect(&local) {
+            IntersectResult::Intersect(hit) => IntersectResult::Intersect(
+                hit.transformed(self.object_matrix(), self.object_matrix_inv()),
+            ),
+            IntersectResult::NoIntersect => IntersectResult::NoIntersect,
+        }
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        let local = ray.transformed(self.object_matrix_inv());
+        self.geometry
+            .crossings(&local)
+            .into_iter()
+            .map(|hit| hit.transformed(self.object_matrix(), self.object_matrix_inv()))
+            .collect()
+    }
+
+    fn sample_emission(&self, choice: T, u: (T, T), time: Option<T>) -> Option<SurfaceSample<T>> {
+        self.geometry
+            .sample_emission(choice, u, time)
+            .map(|sample| sample.transformed(self.object_matrix(), self.object_matrix_inv()))
+    }
+
+    fn emission_pdf(&self, ray: &Ray<T>) -> T {
+        let local = ray.transformed(self.object_matrix_inv());
+        match self.geometry.intersect(&local) {
+            IntersectResult::Intersect(hit) => {
+                self.geometry.emission_pdf(&local)
+                    / area_scale(self.object_matrix(), &hit.geometric_normal)
+            }
+            IntersectResult::NoIntersect => T::zero(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::mesh::Mesh;
+    use crate::object::sphere::Sphere;
+    use crate::object::tests::{glowing_sphere, towards_z};
+
+    #[test]
+    fn shared_geometry() {
+        let sphere: Rc<dyn Intersectable<f64>> =
+            Rc::new(Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap());
+        let left = Instance::new(
+            sphere.clone(),
+            Mat4::translation(&Vec4::direction(-5.0, 0.0, 0.0)),
+        )
+        .unwrap();
+        let right =
+            Instance::new(sphere, Mat4::translation(&Vec4::direction(5.0, 0.0, 0.0))).unwrap();
+
+        match left.intersect(&towards_z(-5.0, 0.0)) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(9.0, hit.t);
+                assert_eq!(Vec4::position(-5.0, 0.0, -1.0), hit.point);
+            }
+            _ => panic!("expected an intersection"),
+        }
+        assert!(matches!(
+            left.intersect(&towards_z(5.0, 0.0)),
+            IntersectResult::NoIntersect
+        ));
+        assert!(matches!(
+            right.intersect(&towards_z(5.0, 0.0)),
+            IntersectResult::Intersect(_)
+        ));
+    }
+
+    #[test]
+    fn stretched_normals() {
+        // A unit triangle in the z = 0 plane, tilted 45 degrees by shearing it
+        let triangle: Rc<dyn Intersectable<f64>> = Rc::new(
+            Mesh::new(
+                vec![
+                    Vec4::position(0.0, 0.0, 0.0),
+                    Vec4::position(0.0, 1.0, 0.0),
+                    Vec4::position(1.0, 0.0, 0.0),
+                ],
+                vec![[0, 1, 2]],
+            )
+            .unwrap(),
+        );
+        let mut shear = Mat4::i();
+        shear[(0, 2)] = 1.0;
+        let instance = Instance::new(triangle, shear).unwrap();
+
+        match instance.intersect(&towards_z(0.25, 0.25)) {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 10.25).abs() < 1e-12);
+                let expected = Vec4::direction(1.0, 0.0, -1.0).normalized();
+                assert!((&hit.geometric_normal - &expected).mag() < 1e-12);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn emission_through_a_stretch() {
+        // A glowing unit sphere stretched to twice as long along x
+        let geometry: Rc<dyn Intersectable<f64>> =
+            Rc::new(glowing_sphere(Vec4::position(0.0, 0.0, 0.0), 1.0));
+        let instance =
+            Instance::new(geometry, Mat4::scale(&Vec4::direction(2.0, 1.0, 1.0))).unwrap();
+
+        let n = 200;
+        let mut area = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let u = ((i as f64 + 0.5) / n as f64, (j as f64 + 0.5) / n as f64);
+                let s = instance.sample_emission(0.5, u, None).unwrap();
+                let p = &s.point;
+                assert!((p.x * p.x / 4.0 + p.y * p.y + p.z * p.z - 1.0).abs() < 1e-9);
+                area += 1.0 / s.pdf;
+
+                // Found again by a ray coming straight at it from outside
+                let ray = Ray::new(p + &(&s.normal * 3.0), s.normal.reverse());
+                assert!((instance.emission_pdf(&ray) - s.pdf).abs() < 1e-9 * s.pdf);
+            }
+        }
+
+        // The surface area of the stretched sphere
+        let e = 0.75f64.sqrt();
+        let expected = 2.0 * std::f64::consts::PI * (1.0 + 2.0 / e * e.asin());
+        assert!((area / (n * n) as f64 - expected).abs() < 0.01, "{}", area);
+
+        let miss = Ray::new(
+            Vec4::position(0.0, 5.0, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        assert_eq!(0.0, instance.emission_pdf(&miss));
+    }
+}
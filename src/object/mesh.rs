@@ -0,0 +1,427 @@
+use std::cmp::Ordering;
+
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::Vec4;
+
+/// A triangle mesh, with optional per-vertex normals and texture coordinates.
+/// Vertices are given directly in world space.
+#[derive(Debug)]
+pub struct Mesh<T: Float> {
+    positions: Vec<Vec4<T>>,
+    normals: Option<Vec<Vec4<T>>>,
+    uvs: Option<Vec<(T, T)>>,
+    triangles: Vec<[usize; 3]>,
+    /// Running total of the triangles' areas, for picking them in proportion
+    areas: Vec<T>,
+    material: Material<T>,
+}
+
+impl<T> Mesh<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    /// `triangles` index into `positions`, wound anticlockwise when seen from the
+    /// front
+    pub fn new(positions: Vec<Vec4<T>>, triangles: Vec<[usize; 3]>) -> Result<Mesh<T>> {
+        if let Some(i) = triangles.iter().flatten().find(|i| **i >= positions.len()) {
+            return Err(Error::InvalidScene(format!(
+                "mesh triangle uses vertex {} of {}",
+                i,
+                positions.len()
+            )));
+        }
+
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        let mut total = T::zero();
+        let areas = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                let (a, b, c) = (&positions[*a], &positions[*b], &positions[*c]);
+                total = total + (b - a).cross_product(&(c - a)).mag() * half;
+                total
+            })
+            .collect();
+
+        Ok(Mesh {
+            positions,
+            normals: None,
+            uvs: None,
+            triangles,
+            areas,
+            material: Material::default(),
+        })
+    }
+
+    fn check_per_vertex(&self, count: usize, what: &str) -> Result<()> {
+        if count == self.positions.len() {
+            Ok(())
+        } else {
+            Err(Error::InvalidScene(format!(
+                "mesh has {} vertices but {} {}",
+                self.positions.len(),
+                count,
+                what
+            )))
+        }
+    }
+
+    /// Per-vertex normals, interpolated across each triangle for smooth shading
+    pub fn set_normals(&mut self, normals: Vec<Vec4<T>>) -> Result<()> {
+        self.check_per_vertex(normals.len(), "normals")?;
+        self.normals = Some(
+            normals
+                .iter()
+                .map(|n| Vec4::direction(n.x, n.y, n.z).normalized())
+                .collect(),
+        );
+        Ok(())
+    }
+
+    /// Per-vertex texture coordinates, interpolated across each triangle
+    pub fn set_uvs(&mut self, uvs: Vec<(T, T)>) -> Result<()> {
+        self.check_per_vertex(uvs.len(), "texture coordinates")?;
+        self.uvs = Some(uvs);
+        Ok(())
+    }
+
+    pub fn set_material(&mut self, material: Material<T>) {
+        self.material = material;
+    }
+}
+
+impl<T> Mesh<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn vertices(&self, triangle: usize) -> [&Vec4<T>; 3] {
+        let [a, b, c] = self.triangles[triangle];
+        [&self.positions[a], &self.positions[b], &self.positions[c]]
+    }
+
+    fn face_normal(&self, triangle: usize) -> Vec4<T> {
+        let [a, b, c] = self.vertices(triangle);
+        (b - a).cross_product(&(c - a)).normalized()
+    }
+
+    /// Möller-Trumbore; returns the distance along the ray and the barycentric
+    /// weights of the hit
+    fn intersect_triangle(&self, triangle: usize, ray: &Ray<T>) -> Option<(T, [T; 3])> {
+        let (origin, direction) = (&ray.origin, &ray.direction);
+        let [a, b, c] = self.vertices(triangle);
+        let edge1 = b - a;
+        let edge2 = c - a;
+
+        let p = direction.cross_product(&edge2);
+        let det = edge1.dot_product(&p);
+        if det == T::zero() {
+            return None;
+        }
+        let inv_det = T::one() / det;
+
+        let s = origin - a;
+        let u = s.dot_product(&p) * inv_det;
+        if u < T::zero() || u > T::one() {
+            return None;
+        }
+
+        let q = s.cross_product(&edge1);
+        let v = direction.dot_product(&q) * inv_det;
+        if v < T::zero() || u + v > T::one() {
+            return None;
+        }
+
+        let t = edge2.dot_product(&q) * inv_det;
+        if !ray.contains(t) {
+            return None;
+        }
+
+        Some((t, [T::one() - u - v, u, v]))
+    }
+
+    fn smooth_normal(&self, triangle: usize, weights: &[T; 3]) -> Vec4<T> {
+        match &self.normals {
+            Some(normals) => {
+                let [a, b, c] = self.triangles[triangle];
+                let n = &(&(&normals[a] * weights[0]) + &(&normals[b] * weights[1]))
+                    + &(&normals[c] * weights[2]);
+                n.normalized()
+            }
+            None => self.face_normal(triangle),
+        }
+    }
+
+    fn uv(&self, triangle: usize, weights: &[T; 3]) -> (T, T) {
+        match &self.uvs {
+            Some(uvs) => {
+                let [a, b, c] = self.triangles[triangle];
+                (
+                    uvs[a].0 * weights[0] + uvs[b].0 * weights[1] + uvs[c].0 * weights[2],
+                    uvs[a].1 * weights[0] + uvs[b].1 * weights[1] + uvs[c].1 * weights[2],
+                )
+            }
+            None => (weights[1], weights[2]),
+        }
+    }
+
+    fn tangent(&self, triangle: usize) -> Vec4<T> {
+        let [a, b, c] = self.vertices(triangle);
+        let edge1 = b - a;
+        let edge2 = c - a;
+
+        if let Some(uvs) = &self.uvs {
+            // Solve for the direction in which u increases across the triangle
+            let [ia, ib, ic] = self.triangles[triangle];
+            let (du1, dv1) = (uvs[ib].0 - uvs[ia].0, uvs[ib].1 - uvs[ia].1);
+            let (du2, dv2) = (uvs[ic].0 - uvs[ia].0, uvs[ic].1 - uvs[ia].1);
+            let det = du1 * dv2 - du2 * dv1;
+
+            if det != T::zero() {
+                return (&(&(&edge1 * dv2) - &(&edge2 * dv1)) * (T::one() / det)).normalized();
+            }
+        }
+
+        edge1.normalized()
+    }
+
+    fn hit(&self, ray: &Ray<T>, triangle: usize, t: T, weights: &[T; 3]) -> HitRecord<'_, T> {
+        HitRecord::new(
+            &ray.direction,
+            t,
+            ray.at(t),
+            &self.face_normal(triangle),
+            &self.smooth_normal(triangle, weights),
+            &self.tangent(triangle),
+            self.uv(triangle, weights),
+            &self.material,
+        )
+    }
+}
+
+impl<T> Intersectable<T> for Mesh<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        // Each hit shortens the ray, so only nearer triangles are found after it
+        let mut ray = *ray;
+        let mut nearest = None;
+        for i in 0..self.triangles.len() {
+            if let Some((t, weights)) = self.intersect_triangle(i, &ray) {
+                ray = ray.clipped(t);
+                nearest = Some((i, t, weights));
+            }
+        }
+
+        match nearest {
+            Some((triangle, t, weights)) => {
+                IntersectResult::Intersect(self.hit(&ray, triangle, t, &weights))
+            }
+            None => IntersectResult::NoIntersect,
+        }
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        let mut crossings: Vec<_> = (0..self.triangles.len())
+            .filter_map(|i| {
+                let (t, weights) = self.intersect_triangle(i, ray)?;
+                Some(self.hit(ray, i, t, &weights))
+            })
+            .collect();
+        crossings.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(Ordering::Equal));
+        crossings
+    }
+
+    fn sample_emission(&self, choice: T, u: (T, T), _: Option<T>) -> Option<SurfaceSample<T>> {
+        let total = *self.areas.last()?;
+        if !self.material.is_emissive() || total <= T::zero() {
+            return None;
+        }
+
+        // Bigger triangles are picked more often, so every bit of the surface
+        // is equally likely
+        let target = choice * total;
+        let triangle = self
+            .areas
+            .partition_point(|a| *a <= target)
+            .min(self.triangles.len() - 1);
+
+        // Uniformly over the triangle
+        let [a, b, c] = self.vertices(triangle);
+        let s = u.0.sqrt();
+        let (wb, wc) = (s * (T::one() - u.1), s * u.1);
+        let point = &(a + &(&(b - a) * wb)) + &(&(c - a) * wc);
+
+        Some(SurfaceSample {
+            point,
+            normal: self.face_normal(triangle),
+            emission: self.material.emission(),
+            pdf: T::one() / total,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::tests::glowing;
+
+    /// A unit square in the z = 0 plane, facing -z
+    fn quad() -> Mesh<f64> {
+        let mut m = Mesh::new(
+            vec![
+                Vec4::position(0.0, 0.0, 0.0),
+                Vec4::position(0.0, 1.0, 0.0),
+                Vec4::position(1.0, 1.0, 0.0),
+                Vec4::position(1.0, 0.0, 0.0),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+        .unwrap();
+        m.set_uvs(vec![(0.0, 1.0), (0.0, 0.0), (1.0, 0.0), (1.0, 1.0)])
+            .unwrap();
+        m
+    }
+
+    fn hit(m: &Mesh<f64>, x: f64, y: f64) -> HitRecord<'_, f64> {
+        let d = Vec4::direction(0.0, 0.0, 1.0);
+        match m.intersect(&Ray::new(Vec4::position(x, y, -2.0), d)) {
+            IntersectResult::Intersect(hit) => hit,
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn mesh_invalid() {
+        let positions = vec![
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::position(0.0, 1.0, 0.0),
+            Vec4::position(1.0, 0.0, 0.0),
+        ];
+        assert!(matches!(
+            Mesh::<f64>::new(positions.clone(), vec![[0, 1, 3]]),
+            Err(Error::InvalidScene(_))
+        ));
+
+        let mut m = Mesh::<f64>::new(positions, vec![[0, 1, 2]]).unwrap();
+        assert!(matches!(
+            m.set_uvs(vec![(0.0, 0.0)]),
+            Err(Error::InvalidScene(_))
+        ));
+    }
+
+    #[test]
+    fn mesh_intersect() {
+        let m = quad();
+
+        assert!((hit(&m, 0.25, 0.75).t - 2.0).abs() < 1e-12);
+        assert!((hit(&m, 0.75, 0.25).t - 2.0).abs() < 1e-12);
+
+        let d = Vec4::direction(0.0, 0.0, 1.0);
+        match m.intersect(&Ray::new(Vec4::position(1.5, 0.5, -2.0), d)) {
+            IntersectResult::NoIntersect => (),
+            _ => panic!("expected no intersection"),
+        }
+    }
+
+    #[test]
+    fn mesh_face_normal() {
+        let m = quad();
+        let h = hit(&m, 0.5, 0.5);
+        assert_eq!(Vec4::direction(0.0, 0.0, -1.0), h.geometric_normal);
+        assert!(h.front_face);
+    }
+
+    #[test]
+    fn mesh_back_face() {
+        let m = quad();
+        let d = Vec4::direction(0.0, 0.0, -1.0);
+        match m.intersect(&Ray::new(Vec4::position(0.5, 0.5, 2.0), d)) {
+            IntersectResult::Intersect(h) => {
+                assert!(!h.front_face);
+                assert_eq!(Vec4::direction(0.0, 0.0, 1.0), h.geometric_normal);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn mesh_interpolated_normal() {
+        let mut m = quad();
+        m.set_normals(vec![
+            Vec4::direction(-1.0, 0.0, -1.0),
+            Vec4::direction(-1.0, 0.0, -1.0),
+            Vec4::direction(1.0, 0.0, -1.0),
+            Vec4::direction(1.0, 0.0, -1.0),
+        ])
+        .unwrap();
+
+        let h = hit(&m, 0.5, 0.5);
+        assert!(h.shading_normal().x.abs() < 1e-12);
+        // The geometric normal is still the flat face
+        assert_eq!(Vec4::direction(0.0, 0.0, -1.0), h.geometric_normal);
+
+        let h = hit(&m, 0.9, 0.5);
+        assert!(h.shading_normal().x > 0.0);
+    }
+
+    #[test]
+    fn mesh_tangent() {
+        let m = quad();
+        let t = hit(&m, 0.25, 0.75).tangent;
+        assert!((t.x - 1.0).abs() < 1e-12);
+        assert!(t.y.abs() < 1e-12);
+    }
+
+    #[test]
+    fn mesh_uv() {
+        let m = quad();
+        let (u, v) = hit(&m, 0.25, 0.75).uv;
+        assert!((u - 0.25).abs() < 1e-12);
+        assert!((v - 0.25).abs() < 1e-12);
+
+        let (u, v) = hit(&m, 0.75, 0.25).uv;
+        assert!((u - 0.75).abs() < 1e-12);
+        assert!((v - 0.75).abs() < 1e-12);
+    }
+
+    #[test]
+    fn mesh_emission_samples() {
+        // A small triangle and one three times its size, side by side
+        let mut m = Mesh::new(
+            vec![
+                Vec4::position(0.0, 0.0, 0.0),
+                Vec4::position(0.0, 1.0, 0.0),
+                Vec4::position(1.0, 0.0, 0.0),
+                Vec4::position(1.0, 3.0, 0.0),
+                Vec4::position(2.0, 0.0, 0.0),
+            ],
+            vec![[0, 1, 2], [2, 3, 4]],
+        )
+        .unwrap();
+        assert!(m.sample_emission(0.5, (0.5, 0.5), None).is_none());
+
+        m.set_material(glowing());
+
+        let n = 100;
+        let mut small = 0;
+        for i in 0..n {
+            for j in 0..n {
+                let choice = (i as f64 + 0.5) / n as f64;
+                let u = ((j as f64 + 0.5) / n as f64, (i * j % n) as f64 / n as f64);
+                let s = m.sample_emission(choice, u, None).unwrap();
+                assert_eq!(0.5, s.pdf);
+                assert_eq!(Vec4::direction(0.0, 0.0, -1.0), s.normal);
+                if s.point.x < 1.0 {
+                    // Inside the small triangle
+                    assert!(s.point.y <= 1.0 - s.point.x + 1e-12, "{:?}", s.point);
+                    small += 1;
+                }
+            }
+        }
+        assert_eq!(n * n / 4, small);
+    }
+}
@@ -1,34 +1,320 @@
-use num::Float;
+use std::cell::Cell;
 
+use num::{Float, FromPrimitive};
+
+use crate::error::{Error, Result};
+use crate::material::Material;
 use crate::matrix::Mat4;
+use crate::ray::Ray;
 use crate::vector::Vec4;
 
+pub mod cone;
+pub mod csg;
+pub mod cylinder;
+pub mod disc;
+pub mod group;
+pub mod instance;
+pub mod mesh;
+pub mod moving;
+pub mod plane;
 pub mod sphere;
+pub mod torus;
+
+/// Everything about where a ray struck a surface
+#[derive(Debug)]
+pub struct HitRecord<'a, T: Float> {
+    /// Distance along the ray
+    pub t: T,
+    pub point: Vec4<T>,
+    /// Normal of the true surface, on the side the ray arrived from
+    pub geometric_normal: Vec4<T>,
+    /// Normal of the surface as modelled, on the same side: interpolated across
+    /// meshes, but before any normal or bump mapping
+    pub normal: Vec4<T>,
+    /// Unit direction along the surface in which u increases
+    pub tangent: Vec4<T>,
+    /// Surface coordinates, for texture lookups
+    pub uv: (T, T),
+    /// Whether the ray arrived from outside the surface
+    pub front_face: bool,
+    pub material: &'a Material<T>,
+    /// The normal to light with, once something has asked for it
+    shading_normal: Cell<Option<Vec4<T>>>,
+}
+
+impl<'a, T> HitRecord<'a, T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Normals are given facing out of the surface, and are turned around if the
+    /// ray came from inside
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        direction: &Vec4<T>,
+        t: T,
+        point: Vec4<T>,
+        outward_normal: &Vec4<T>,
+        smooth_normal: &Vec4<T>,
+        tangent: &Vec4<T>,
+        uv: (T, T),
+        material: &'a Material<T>,
+    ) -> HitRecord<'a, T> {
+        let direction_of = |v: &Vec4<T>| Vec4::direction(v.x, v.y, v.z).normalized();
+        let mut geometric_normal = direction_of(outward_normal);
+        let mut smooth_normal = direction_of(smooth_normal);
+        let tangent = direction_of(tangent);
+
+        let front_face = direction.dot_product(&geometric_normal) <= T::zero();
+        if !front_face {
+            geometric_normal = geometric_normal.reverse();
+            smooth_normal = smooth_normal.reverse();
+        }
+
+        HitRecord {
+            t,
+            point,
+            geometric_normal,
+            normal: smooth_normal,
+            tangent,
+            uv,
+            front_face,
+            material,
+            shading_normal: Cell::new(None),
+        }
+    }
+
+    /// The normal to light with: `normal` with any normal or bump mapping from
+    /// the material applied. Worked out the first time it's needed, since most
+    /// hits found along the way are passed over for nearer ones.
+    pub fn shading_normal(&self) -> Vec4<T> {
+        if let Some(normal) = self.shading_normal.get() {
+            return normal;
+        }
+        let normal =
+            self.material
+                .shading_normal(&self.normal, &self.tangent, &self.point, self.uv);
+        self.shading_normal.set(Some(normal));
+        normal
+    }
+}
+
+impl<'a, T> HitRecord<'a, T>
+where
+    T: Float,
+{
+    /// The same hit, found in an object's own space, brought back out into the
+    /// space containing it. Distances along the ray are unchanged since rays aren't
+    /// renormalised when transformed.
+    pub fn transformed(self, object: &Mat4<T>, object_inverse: &Mat4<T>) -> HitRecord<'a, T> {
+        // Normals are carried by the inverse transpose, so they stay perpendicular
+        // to surfaces that have been stretched
+        let normal_matrix = object_inverse.transpose();
+        let carry = |m: &Mat4<T>, v: &Vec4<T>| {
+            let v = m * v;
+            Vec4::direction(v.x, v.y, v.z).normalized()
+        };
 
-pub enum IntersectResult<T: Float> {
+        HitRecord {
+            point: object * &self.point,
+            geometric_normal: carry(&normal_matrix, &self.geometric_normal),
+            normal: carry(&normal_matrix, &self.normal),
+            tangent: carry(object, &self.tangent),
+            shading_normal: Cell::new(None),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+impl<'a> HitRecord<'a, f64> {
+    /// A hit on a surface facing along `normal`, for testing lights
+    pub fn at(point: Vec4<f64>, normal: Vec4<f64>, material: &'a Material<f64>) -> Self {
+        let (tangent, _) = normal.basis();
+        HitRecord::new(
+            &normal.reverse(),
+            0.0,
+            point,
+            &normal,
+            &normal,
+            &tangent,
+            (0.0, 0.0),
+            material,
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::sphere::Sphere;
+    use super::*;
+
+    /// Heading along +z from z = -10, towards whatever's near the origin
+    pub(crate) fn towards_z(x: f64, y: f64) -> Ray<f64> {
+        Ray::new(Vec4::position(x, y, -10.0), Vec4::direction(0.0, 0.0, 1.0))
+    }
+
+    /// A material glowing plain white
+    pub(crate) fn glowing() -> Material<f64> {
+        let mut material = Material::default();
+        material.set_emission(image::Rgb([255, 255, 255]), 1.0);
+        material
+    }
+
+    /// A sphere made of `glowing`
+    pub(crate) fn glowing_sphere(centre: Vec4<f64>, radius: f64) -> Sphere<f64> {
+        let mut sphere = Sphere::new(centre, radius).unwrap();
+        sphere.set_material(glowing());
+        sphere
+    }
+}
+
+/// A size given when building an object, which has to be positive for the
+/// object to have any inside
+fn check_positive<T: Float>(value: T, what: &str) -> Result<()> {
+    if value > T::zero() && value.is_finite() {
+        Ok(())
+    } else {
+        Err(Error::InvalidScene(format!("{} must be positive", what)))
+    }
+}
+
+/// How far round the y axis an object-space point is, as a texture coordinate
+/// running from 0 to 1 starting from -z, and the direction in which that increases.
+/// Anything horizontal will do for the direction on the axis itself.
+fn around_y_axis<T>(point: &Vec4<T>) -> (T, Vec4<T>)
+where
+    T: Float + FromPrimitive,
+{
+    let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+    let two: T = FromPrimitive::from_f64(2.0).unwrap();
+    let half: T = FromPrimitive::from_f64(0.5).unwrap();
+
+    let u = half + T::atan2(point.x, point.z) / (two * pi);
+    let tangent = if point.x == T::zero() && point.z == T::zero() {
+        Vec4::direction(T::one(), T::zero(), T::zero())
+    } else {
+        Vec4::direction(point.z, T::zero(), -point.x)
+    };
+    (u, tangent)
+}
+
+pub enum IntersectResult<'a, T: Float> {
     NoIntersect,
-    Intersect(T),
+    Intersect(HitRecord<'a, T>),
+}
+
+/// A point on a glowing surface, picked to light something else with
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SurfaceSample<T: Float> {
+    pub point: Vec4<T>,
+    /// Facing out of the surface, the side that glows
+    pub normal: Vec4<T>,
+    pub emission: [T; 3],
+    /// Density per unit area at the point
+    pub pdf: T,
+}
+
+impl<T> SurfaceSample<T>
+where
+    T: Float,
+{
+    /// The same sample, picked in an object's own space, brought back out into
+    /// the space containing it. The density thins out where the transform
+    /// stretches the surface.
+    pub fn transformed(self, object: &Mat4<T>, object_inverse: &Mat4<T>) -> SurfaceSample<T> {
+        let normal = &object_inverse.transpose() * &self.normal;
+        SurfaceSample {
+            point: object * &self.point,
+            normal: Vec4::direction(normal.x, normal.y, normal.z).normalized(),
+            pdf: self.pdf / area_scale(object, &self.normal),
+            ..self
+        }
+    }
+}
+
+/// How many times larger `object` makes a small patch of surface facing along
+/// the unit vector `normal`
+fn area_scale<T: Float>(object: &Mat4<T>, normal: &Vec4<T>) -> T {
+    let (tangent, bitangent) = normal.basis();
+    (object * &tangent)
+        .cross_product(&(object * &bitangent))
+        .mag()
 }
 
 pub trait Intersectable<T: Float> {
-    fn intersect(&self, origin: &Vec4<T>, direction: &Vec4<T>) -> IntersectResult<T>;
-    fn normal(&self, intersect_point: &Vec4<T>) -> Vec4<T>;
+    /// The nearest hit within the ray's interval
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T>;
+
+    /// Every hit within the ray's interval, nearest first. Solids are entered
+    /// through front faces and left through back faces.
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>>;
+
+    /// For objects with an emissive material, a point picked uniformly by area
+    /// over the surface in its own space, wherever it is at `time`, so they can
+    /// be sampled as lights. `choice` picks a part of the surface, such as a
+    /// triangle, and `u` a point on it. None for objects that don't glow, or
+    /// don't know how to pick points; those still glow wherever rays happen to
+    /// find them.
+    fn sample_emission(
+        &self,
+        _choice: T,
+        _u: (T, T),
+        _time: Option<T>,
+    ) -> Option<SurfaceSample<T>> {
+        None
+    }
+
+    /// The density per unit area with which `sample_emission` would have picked
+    /// the point where `ray` first hits the object, at the ray's time. Zero if
+    /// the ray misses, or the object isn't sampled.
+    ///
+    /// By default every point is taken to be as likely as any other, which
+    /// holds for surfaces sampled uniformly by area in the space the ray is in.
+    fn emission_pdf(&self, ray: &Ray<T>) -> T {
+        let half = T::one() / (T::one() + T::one());
+        if let IntersectResult::NoIntersect = self.intersect(ray) {
+            return T::zero();
+        }
+        self.sample_emission(half, (half, half), ray.time)
+            .map_or(T::zero(), |sample| sample.pdf)
+    }
 }
 
 impl<T> Intersectable<T> for Box<dyn Intersectable<T>>
 where
     T: Float,
 {
-    fn intersect(&self, origin: &Vec4<T>, direction: &Vec4<T>) -> IntersectResult<T> {
-        self.as_ref().intersect(origin, direction)
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        self.as_ref().intersect(ray)
     }
 
-    fn normal(&self, intersect_point: &Vec4<T>) -> Vec4<T> {
-        self.as_ref().normal(intersect_point)
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        self.as_ref().crossings(ray)
+    }
+
+    fn sample_emission(&self, choice: T, u: (T, T), time: Option<T>) -> Option<SurfaceSample<T>> {
+        self.as_ref().sample_emission(choice, u, time)
+    }
+
+    fn emission_pdf(&self, ray: &Ray<T>) -> T {
+        self.as_ref().emission_pdf(ray)
     }
 }
 
 pub trait WorldObject<T: Float> {
     fn object_matrix(&self) -> &Mat4<T>;
     fn object_matrix_inv(&self) -> &Mat4<T>;
+
+    /// Carry a surface normal out of object space, keeping it perpendicular to the
+    /// surface however the object has been stretched
+    fn normal_to_world(&self, normal: &Vec4<T>) -> Vec4<T> {
+        let n = &self.object_matrix_inv().transpose() * normal;
+        Vec4::direction(n.x, n.y, n.z).normalized()
+    }
+
+    /// Carry a direction (along a surface, say) out of object space
+    fn direction_to_world(&self, direction: &Vec4<T>) -> Vec4<T> {
+        let d = self.object_matrix() * direction;
+        Vec4::direction(d.x, d.y, d.z).normalized()
+    }
 }
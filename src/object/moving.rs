@@ -0,0 +1,172 @@
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::motion::Motion;
+use crate::ray::Ray;
+
+/// Something in motion while the shutter is open. Each ray sees the object
+/// wherever it was at the ray's time, so averaging many rays blurs it along its
+/// path. Rays without a time see it where it starts.
+pub struct Moving<T: Float> {
+    geometry: Box<dyn Intersectable<T>>,
+    motion: Motion<T>,
+}
+
+impl<T> Moving<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// `motion` takes the geometry's own coordinates to the world's
+    pub fn new(geometry: Box<dyn Intersectable<T>>, motion: Motion<T>) -> Moving<T> {
+        Moving { geometry, motion }
+    }
+
+    fn transforms(&self, ray: &Ray<T>) -> (Mat4<T>, Mat4<T>) {
+        let time = ray.time.unwrap_or_else(T::zero);
+        (self.motion.at(time), self.motion.inverse_at(time))
+    }
+}
+
+impl<T> Intersectable<T> for Moving<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        let (object, object_inverse) = self.transforms(ray);
+        match self.geometry.intersect(&ray.transformed(&object_inverse)) {
+            IntersectResult::Intersect(hit) => {
+                IntersectResult::Intersect(hit.transformed(&object, &object_inverse))
+            }
+            IntersectResult::NoIntersect => IntersectResult::NoIntersect,
+        }
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        let (object, object_inverse) = self.transforms(ray);
+        self.geometry
+            .crossings(&ray.transformed(&object_inverse))
+            .into_iter()
+            .map(|hit| hit.transformed(&object, &object_inverse))
+            .collect()
+    }
+
+    fn sample_emission(&self, choice: T, u: (T, T), time: Option<T>) -> Option<SurfaceSample<T>> {
+        let at = time.unwrap_or_else(T::zero);
+        self.geometry
+            .sample_emission(choice, u, time)
+            .map(|sample| sample.transformed(&self.motion.at(at), &self.motion.inverse_at(at)))
+    }
+
+    fn emission_pdf(&self, ray: &Ray<T>) -> T {
+        let (object, object_inverse) = self.transforms(ray);
+        let local = ray.transformed(&object_inverse);
+        match self.geometry.intersect(&local) {
+            IntersectResult::Intersect(hit) => {
+                self.geometry.emission_pdf(&local) / area_scale(&object, &hit.geometric_normal)
+            }
+            IntersectResult::NoIntersect => T::zero(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motion::Pose;
+    use crate::object::sphere::Sphere;
+    use crate::object::tests::{glowing_sphere, towards_z};
+
+    fn rolling() -> Moving<f64> {
+        // Rolls a quarter turn along x, from -2 to 2
+        let axis = Vec4::direction(0.0, 0.0, 1.0);
+        Moving::new(
+            Box::new(Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap()),
+            Motion::new(
+                Pose::new(Vec4::direction(-2.0, 0.0, 0.0), &axis, 0.0),
+                Pose::new(
+                    Vec4::direction(2.0, 0.0, 0.0),
+                    &axis,
+                    -std::f64::consts::FRAC_PI_2,
+                ),
+            ),
+        )
+    }
+
+    #[test]
+    fn moves_with_time() {
+        let m = rolling();
+
+        match m.intersect(&towards_z(-2.0, 0.0).with_time(0.0)) {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 9.0).abs() < 1e-12);
+                assert!((&hit.point - &Vec4::position(-2.0, 0.0, -1.0)).mag() < 1e-12);
+            }
+            _ => panic!("expected an intersection"),
+        }
+        assert!(matches!(
+            m.intersect(&towards_z(-2.0, 0.0).with_time(1.0)),
+            IntersectResult::NoIntersect
+        ));
+        assert!(matches!(
+            m.intersect(&towards_z(2.0, 0.0).with_time(1.0)),
+            IntersectResult::Intersect(_)
+        ));
+        assert!(matches!(
+            m.intersect(&towards_z(0.0, 0.0).with_time(0.5)),
+            IntersectResult::Intersect(_)
+        ));
+    }
+
+    #[test]
+    fn untimed_rays_see_the_start() {
+        let m = rolling();
+        assert!(matches!(
+            m.intersect(&towards_z(-2.0, 0.0)),
+            IntersectResult::Intersect(_)
+        ));
+        assert!(matches!(
+            m.intersect(&towards_z(2.0, 0.0)),
+            IntersectResult::NoIntersect
+        ));
+    }
+
+    #[test]
+    fn rotated_tangent() {
+        // Halfway through, the sphere has turned an eighth of a turn about z
+        match rolling().intersect(&towards_z(0.0, 0.0).with_time(0.5)) {
+            IntersectResult::Intersect(hit) => {
+                let unrolled = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+                let still = match unrolled.intersect(&towards_z(0.0, 0.0)) {
+                    IntersectResult::Intersect(hit) => hit.tangent,
+                    _ => panic!("expected an intersection"),
+                };
+                let expected = &Mat4::rotation(
+                    &Vec4::direction(0.0, 0.0, 1.0),
+                    -std::f64::consts::FRAC_PI_4,
+                ) * &still;
+                assert!((&hit.tangent - &expected).mag() < 1e-9);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn emission_where_it_is_at_the_time() {
+        let m = Moving::new(
+            Box::new(glowing_sphere(Vec4::position(0.0, 0.0, 0.0), 1.0)),
+            Motion::new(
+                Pose::translation(Vec4::direction(-2.0, 0.0, 0.0)),
+                Pose::translation(Vec4::direction(2.0, 0.0, 0.0)),
+            ),
+        );
+
+        for (time, x) in [(None, -2.0), (Some(0.0), -2.0), (Some(1.0), 2.0)] {
+            let s = m.sample_emission(0.5, (0.3, 0.7), time).unwrap();
+            assert!(((&s.point - &Vec4::position(x, 0.0, 0.0)).mag() - 1.0).abs() < 1e-12);
+            assert!((s.pdf - 0.25 / std::f64::consts::PI).abs() < 1e-12);
+        }
+
+        assert!(m.emission_pdf(&towards_z(2.0, 0.0).with_time(1.0)) > 0.0);
+        assert_eq!(0.0, m.emission_pdf(&towards_z(2.0, 0.0).with_time(0.0)));
+    }
+}
@@ -0,0 +1,224 @@
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::material::Material;
+use crate::matrix::Mat4;
+use crate::ray::Ray;
+use crate::vector::Vec4;
+
+/// An infinite flat surface. In object space it is the y = 0 plane, facing +y.
+#[derive(Debug)]
+pub struct Plane<T: Float> {
+    object: Mat4<T>,
+    object_inverse: Mat4<T>,
+    normal: Vec4<T>,
+    material: Material<T>,
+}
+
+impl<T: Float> WorldObject<T> for Plane<T> {
+    fn object_matrix(&self) -> &Mat4<T> {
+        &self.object
+    }
+
+    fn object_matrix_inv(&self) -> &Mat4<T> {
+        &self.object_inverse
+    }
+}
+
+impl<T> Intersectable<T> for Plane<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        let local = ray.transformed(self.object_matrix_inv());
+
+        if local.direction.y == T::zero() {
+            return IntersectResult::NoIntersect;
+        }
+
+        let t = -local.origin.y / local.direction.y;
+        if !ray.contains(t) {
+            return IntersectResult::NoIntersect;
+        }
+
+        let point = ray.at(t);
+        IntersectResult::Intersect(HitRecord::new(
+            &ray.direction,
+            t,
+            point,
+            &self.normal,
+            &self.normal,
+            &self.tangent(),
+            self.uv(&point),
+            &self.material,
+        ))
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        match self.intersect(ray) {
+            IntersectResult::Intersect(hit) => vec![hit],
+            IntersectResult::NoIntersect => vec![],
+        }
+    }
+}
+
+impl<T> Plane<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn uv(&self, intersect_point: &Vec4<T>) -> (T, T) {
+        // One unit in the world is one repeat of the texture
+        let p = self.object_matrix_inv() * intersect_point;
+        (p.x, p.z)
+    }
+
+    fn tangent(&self) -> Vec4<T> {
+        let x = Vec4::direction(T::one(), T::zero(), T::zero());
+        (self.object_matrix() * &x).normalized()
+    }
+}
+
+impl<T> Plane<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    /// The plane through `origin`, facing along `normal`
+    pub fn new(origin: Vec4<T>, normal: Vec4<T>) -> Result<Plane<T>> {
+        check_positive(normal.mag(), "plane normal length")?;
+        let normal = Vec4::direction(normal.x, normal.y, normal.z).normalized();
+        let (tangent, bitangent) = normal.basis();
+
+        let object_matrix = Mat4::camera(&bitangent, &tangent, &normal, &origin);
+        let object_matrix_inverse = object_matrix.inverse()?;
+
+        Ok(Plane {
+            object: object_matrix,
+            object_inverse: object_matrix_inverse,
+            normal,
+            material: Material::default(),
+        })
+    }
+
+    pub fn set_material(&mut self, material: Material<T>) {
+        self.material = material;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plane_intersect() {
+        let p = Plane::new(
+            Vec4::position(0.0, -1.0, 0.0),
+            Vec4::direction(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+
+        let result = p.intersect(&Ray::new(
+            Vec4::position(0.0, 4.0, 0.0),
+            Vec4::direction(0.0, -1.0, 0.0),
+        ));
+        match result {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 5.0).abs() < 1e-12);
+                assert_eq!(Vec4::position(0.0, -1.0, 0.0), hit.point);
+                assert!(hit.front_face);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn plane_from_below() {
+        let p = Plane::new(
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::direction(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+
+        let result = p.intersect(&Ray::new(
+            Vec4::position(0.0, -1.0, 0.0),
+            Vec4::direction(0.0, 1.0, 0.0),
+        ));
+        match result {
+            IntersectResult::Intersect(hit) => {
+                assert!(!hit.front_face);
+                assert_eq!(Vec4::direction(0.0, -1.0, 0.0), hit.geometric_normal);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn plane_parallel_or_behind() {
+        let p = Plane::new(
+            Vec4::position(0.0, -1.0, 0.0),
+            Vec4::direction(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        let origin = Vec4::position(0.0, 4.0, 0.0);
+
+        match p.intersect(&Ray::new(origin, Vec4::direction(1.0, 0.0, 0.0))) {
+            IntersectResult::NoIntersect => (),
+            _ => panic!("expected no intersection"),
+        }
+        match p.intersect(&Ray::new(origin, Vec4::direction(0.0, 1.0, 0.0))) {
+            IntersectResult::NoIntersect => (),
+            _ => panic!("expected no intersection"),
+        }
+
+        // Out of the ray's range
+        let ray = Ray::segment(origin, Vec4::direction(0.0, -1.0, 0.0), 0.0, 4.0);
+        match p.intersect(&ray) {
+            IntersectResult::NoIntersect => (),
+            _ => panic!("expected no intersection"),
+        }
+    }
+
+    #[test]
+    fn plane_tilted() {
+        let n = Vec4::direction(1.0, 1.0, 0.0);
+        let p = Plane::new(Vec4::position(0.0, 0.0, 0.0), n).unwrap();
+
+        let result = p.intersect(&Ray::new(
+            Vec4::position(2.0, 0.0, 0.0),
+            Vec4::direction(-1.0, 0.0, 0.0),
+        ));
+        match result {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 2.0).abs() < 1e-12);
+                assert!((&hit.geometric_normal - &n.normalized()).mag() < 1e-12);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn plane_uv() {
+        let p = Plane::new(
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::direction(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+        let (u0, v0) = p.uv(&Vec4::position(0.0, 0.0, 0.0));
+        let (u1, v1) = p.uv(&Vec4::position(3.0, 0.0, 4.0));
+
+        assert_eq!((0.0, 0.0), (u0, v0));
+        assert!(((u1 - u0).powi(2) + (v1 - v0).powi(2) - 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn plane_tangent() {
+        let origin = Vec4::position(0.0, 0.0, 0.0);
+        let p = Plane::new(origin, Vec4::direction(0.0, 1.0, 1.0)).unwrap();
+        let t = p.tangent();
+
+        assert!(t.dot_product(&p.normal).abs() < 1e-12);
+
+        let (u0, _) = p.uv(&origin);
+        let (u1, _) = p.uv(&(&origin + &t));
+        assert!((u1 - u0 - 1.0).abs() < 1e-12);
+    }
+}
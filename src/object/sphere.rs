@@ -3,13 +3,16 @@ use std::mem;
 use num::{Float, FromPrimitive};
 
 use super::*;
+use crate::material::Material;
 use crate::matrix::Mat4;
+use crate::ray::Ray;
 use crate::vector::Vec4;
 
 #[derive(Debug)]
 pub struct Sphere<T: Float> {
     object: Mat4<T>,
     object_inverse: Mat4<T>,
+    material: Material<T>,
 }
 
 impl<T: Float> WorldObject<T> for Sphere<T> {
@@ -26,9 +29,68 @@ impl<T> Intersectable<T> for Sphere<T>
 where
     T: Float + FromPrimitive,
 {
-    fn intersect(&self, origin: &Vec4<T>, direction: &Vec4<T>) -> IntersectResult<T> {
-        let transformed_origin = self.object_matrix_inv() * origin;
-        let transformed_direction = self.object_matrix_inv() * direction;
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        let (t0, t1) = match self.roots(ray) {
+            Some(roots) => roots,
+            None => return IntersectResult::NoIntersect,
+        };
+
+        // If the near hit is out of range, the far one may not be (when the ray
+        // starts inside the sphere, say)
+        if ray.contains(t0) {
+            IntersectResult::Intersect(self.hit(ray, t0))
+        } else if ray.contains(t1) {
+            IntersectResult::Intersect(self.hit(ray, t1))
+        } else {
+            IntersectResult::NoIntersect
+        }
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        match self.roots(ray) {
+            Some((t0, t1)) => [t0, t1]
+                .iter()
+                .filter(|t| ray.contains(**t))
+                .map(|t| self.hit(ray, *t))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn sample_emission(&self, _: T, u: (T, T), _: Option<T>) -> Option<SurfaceSample<T>> {
+        if !self.material.is_emissive() {
+            return None;
+        }
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let four: T = FromPrimitive::from_f64(4.0).unwrap();
+
+        // Uniformly over the unit sphere, which stays uniform when scaled up
+        let z = T::one() - two * u.0;
+        let r = (T::one() - z * z).max(T::zero()).sqrt();
+        let phi = two * pi * u.1;
+        let local = Vec4::position(r * phi.cos(), r * phi.sin(), z);
+
+        let radius =
+            (self.object_matrix() * &Vec4::direction(T::one(), T::zero(), T::zero())).mag();
+        Some(SurfaceSample {
+            point: self.object_matrix() * &local,
+            normal: self.normal_to_world(&Vec4::direction(local.x, local.y, local.z)),
+            emission: self.material.emission(),
+            pdf: T::one() / (four * pi * radius * radius),
+        })
+    }
+}
+
+impl<T> Sphere<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Where the line through the ray enters and leaves the sphere, nearest first
+    fn roots(&self, ray: &Ray<T>) -> Option<(T, T)> {
+        let local = ray.transformed(self.object_matrix_inv());
+        let transformed_origin = local.origin;
+        let transformed_direction = local.direction;
 
         let two = FromPrimitive::from_f64(2.0).unwrap();
         let four: T = FromPrimitive::from_f64(4.0).unwrap();
@@ -39,7 +101,7 @@ where
 
         let discriminant = (b * b) - (four * a * c);
         if discriminant < T::zero() {
-            return IntersectResult::NoIntersect;
+            return None;
         }
 
         let sqrt_discriminant = T::sqrt(discriminant);
@@ -51,76 +113,184 @@ where
             mem::swap(&mut t0, &mut t1);
         }
 
-        // if t1 is < 0, sphere is in the ray's negative dirction
-        if t1 < T::zero() {
-            return IntersectResult::NoIntersect;
-        }
-
-        // if t0 < 0, intersection is at t1 (and we are inside the sphere)
-        if t0 < T::zero() {
-            return IntersectResult::Intersect(t1);
-        }
+        Some((t0, t1))
+    }
 
-        IntersectResult::Intersect(t0)
+    fn hit(&self, ray: &Ray<T>, t: T) -> HitRecord<'_, T> {
+        let point = ray.at(t);
+        let normal = self.normal(&point);
+        HitRecord::new(
+            &ray.direction,
+            t,
+            point,
+            &normal,
+            &normal,
+            &self.tangent(&point),
+            self.uv(&point),
+            &self.material,
+        )
     }
 
     fn normal(&self, intersect_point: &Vec4<T>) -> Vec4<T> {
         let v = self.object_matrix_inv() * intersect_point;
         v.normalized()
     }
+
+    fn uv(&self, intersect_point: &Vec4<T>) -> (T, T) {
+        // Longitude and latitude: u runs around the equator from -z, v from the
+        // north (+y) pole to the south
+        let v = (self.object_matrix_inv() * intersect_point).normalized();
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+
+        let (u, _) = around_y_axis(&v);
+        let v = T::acos(T::max(-T::one(), T::min(T::one(), v.y))) / pi;
+        (u, v)
+    }
+
+    fn tangent(&self, intersect_point: &Vec4<T>) -> Vec4<T> {
+        // Eastwards, around the y axis
+        let v = (self.object_matrix_inv() * intersect_point).normalized();
+        let (_, t) = around_y_axis(&v);
+        (self.object_matrix() * &t).normalized()
+    }
 }
 
-impl<T: Float> Sphere<T> {
-    pub fn new(origin: Vec4<T>, radius: T) -> Sphere<T> {
+impl<T> Sphere<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    pub fn new(origin: Vec4<T>, radius: T) -> Result<Sphere<T>> {
+        check_positive(radius, "sphere radius")?;
+
         let o: Mat4<T> = Mat4::translation(&origin);
         let scale_vec = Vec4::direction(radius, radius, radius);
         let scale = Mat4::scale(&scale_vec);
 
         let object_matrix = &scale * &o;
-        let object_matrix_inverse = object_matrix.inverse();
+        let object_matrix_inverse = object_matrix.inverse()?;
 
-        Sphere {
+        Ok(Sphere {
             object: object_matrix,
             object_inverse: object_matrix_inverse,
-        }
+            material: Material::default(),
+        })
+    }
+
+    pub fn set_material(&mut self, material: Material<T>) {
+        self.material = material;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::texture::Texture;
+    use std::cell::Cell;
+    use std::rc::Rc;
 
     #[test]
     fn construct_sphere() {
         let o: Vec4<f64> = Vec4::position(0.0, 2.0, 0.0);
-        let s: Sphere<f64> = Sphere::new(o, 2.0);
+        let s: Sphere<f64> = Sphere::new(o, 2.0).unwrap();
 
         println!("{}", s.object_matrix())
     }
 
     #[test]
+    fn invalid_radius() {
+        let o: Vec4<f64> = Vec4::position(0.0, 0.0, 0.0);
+        assert!(matches!(Sphere::new(o, 0.0), Err(Error::InvalidScene(_))));
+        assert!(matches!(Sphere::new(o, -1.0), Err(Error::InvalidScene(_))));
+        assert!(matches!(
+            Sphere::new(o, f64::NAN),
+            Err(Error::InvalidScene(_))
+        ));
+    }
+
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn sphere_intersect() {
         let o = Vec4::position(0.0, 0.0, 0.0);
-        let s = Sphere::new(o, 1.0);
+        let s = Sphere::new(o, 1.0).unwrap();
         let ray_origin = Vec4::position(0.0, 0.0, -10.0);
         let ray_direction = Vec4::direction(0.0, 0.0, 1.0);
 
-        let result = s.intersect(&ray_origin, &ray_direction);
+        let result = s.intersect(&Ray::new(ray_origin, ray_direction));
 
         match result {
-            IntersectResult::Intersect(t) => assert_eq!(9.0, t),
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(9.0, hit.t);
+                assert_eq!(Vec4::position(0.0, 0.0, -1.0), hit.point);
+                assert_eq!(Vec4::direction(0.0, 0.0, -1.0), hit.geometric_normal);
+                assert_eq!(hit.geometric_normal, hit.shading_normal());
+                assert!(hit.front_face);
+            }
             _ => assert!(false),
         }
     }
 
     #[test]
+    fn sphere_intersect_from_inside() {
+        let s = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+        let ray_origin = Vec4::position(0.0, 0.0, 0.0);
+        let ray_direction = Vec4::direction(0.0, 0.0, 1.0);
+
+        match s.intersect(&Ray::new(ray_origin, ray_direction)) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(1.0, hit.t);
+                assert!(!hit.front_face);
+                // Normals face back towards the ray
+                assert_eq!(Vec4::direction(0.0, 0.0, -1.0), hit.geometric_normal);
+                assert_eq!((0.5, 0.5), hit.uv);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn sphere_intersect_within_interval() {
+        let s = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+        let origin = Vec4::position(0.0, 0.0, -10.0);
+        let direction = Vec4::direction(0.0, 0.0, 1.0);
+
+        // Starting past the near side finds the far side
+        match s.intersect(&Ray::segment(origin, direction, 10.0, 20.0)) {
+            IntersectResult::Intersect(hit) => assert_eq!(11.0, hit.t),
+            _ => panic!("expected an intersection"),
+        }
+
+        // Stopping short finds nothing
+        match s.intersect(&Ray::segment(origin, direction, 0.0, 8.0)) {
+            IntersectResult::NoIntersect => (),
+            _ => panic!("expected no intersection"),
+        }
+    }
+
+    #[test]
+    fn sphere_crossings() {
+        let s = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+        let origin = Vec4::position(0.0, 0.0, -10.0);
+        let direction = Vec4::direction(0.0, 0.0, 1.0);
+
+        let crossings = s.crossings(&Ray::new(origin, direction));
+        assert_eq!(2, crossings.len());
+        assert_eq!((9.0, true), (crossings[0].t, crossings[0].front_face));
+        assert_eq!((11.0, false), (crossings[1].t, crossings[1].front_face));
+
+        let crossings = s.crossings(&Ray::segment(origin, direction, 10.0, 20.0));
+        assert_eq!(1, crossings.len());
+        assert!(!crossings[0].front_face);
+    }
+
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn sphere_intersect_translated() {
         let o = Vec4::position(0.0, 2.0, 0.0);
-        let s = Sphere::new(o, 1.0);
+        let s = Sphere::new(o, 1.0).unwrap();
         let ray_origin = Vec4::position(0.0, 0.0, -10.0);
         let ray_direction = Vec4::direction(0.0, 0.0, 1.0);
 
-        let result = s.intersect(&ray_origin, &ray_direction);
+        let result = s.intersect(&Ray::new(ray_origin, ray_direction));
 
         match result {
             IntersectResult::NoIntersect => assert!(true),
@@ -131,7 +301,7 @@ mod tests {
     #[test]
     fn sphere_norm() {
         let o = Vec4::position(0.0, 0.0, 0.0);
-        let s = Sphere::new(o, 1.0);
+        let s = Sphere::new(o, 1.0).unwrap();
         // Sphere at origin, normal at any point should be the same as point vector
 
         let p = Vec4::position(0.0, 1.0, 0.0);
@@ -139,4 +309,87 @@ mod tests {
 
         assert_eq!(p, n);
     }
+
+    #[test]
+    fn sphere_tangent() {
+        let s = Sphere::new(Vec4::position(0.0, 2.0, 0.0), 2.0).unwrap();
+
+        let p = Vec4::position(0.0, 2.0, 2.0);
+        let t = s.tangent(&p);
+        assert_eq!(Vec4::direction(1.0, 0.0, 0.0), t);
+
+        // Moving along the tangent increases u
+        let (u0, _) = s.uv(&p);
+        let (u1, _) = s.uv(&(&p + &(&t * 0.01)));
+        assert!(u1 > u0);
+    }
+
+    #[test]
+    fn emission_samples() {
+        let mut s = Sphere::new(Vec4::position(0.0, 2.0, 0.0), 2.0).unwrap();
+        assert!(s.sample_emission(0.5, (0.3, 0.6), None).is_none());
+
+        let mut m = Material::default();
+        m.set_emission(image::Rgb([255, 255, 255]), 2.0);
+        s.set_material(m);
+        for (u, v) in [(0.0, 0.0), (0.3, 0.6), (0.9, 0.1)] {
+            let sample = s.sample_emission(0.5, (u, v), None).unwrap();
+            let out = &sample.point - &Vec4::position(0.0, 2.0, 0.0);
+            assert!((out.mag() - 2.0).abs() < 1e-12);
+            assert!((&sample.normal - &out.normalized()).mag() < 1e-12);
+            assert_eq!([2.0; 3], sample.emission);
+            assert!((sample.pdf - 1.0 / (16.0 * std::f64::consts::PI)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn sphere_uv() {
+        let s = Sphere::new(Vec4::position(0.0, 2.0, 0.0), 2.0).unwrap();
+
+        let (_, v) = s.uv(&Vec4::position(0.0, 4.0, 0.0));
+        assert_eq!(0.0, v);
+
+        let (u, v) = s.uv(&Vec4::position(0.0, 2.0, 2.0));
+        assert_eq!((0.5, 0.5), (u, v));
+
+        let (u, _) = s.uv(&Vec4::position(2.0, 2.0, 0.0));
+        assert_eq!(0.75, u);
+    }
+
+    /// Counts how often it's looked up
+    struct Counting(Rc<Cell<u32>>);
+
+    impl Texture<f64> for Counting {
+        fn colour(&self, _: &Vec4<f64>, _: (f64, f64)) -> [f64; 3] {
+            self.0.set(self.0.get() + 1);
+            [0.5; 3]
+        }
+    }
+
+    #[test]
+    fn shading_normal_when_asked() {
+        let lookups = Rc::new(Cell::new(0));
+        let mut material = Material::default();
+        material.set_bump_map(Box::new(Counting(lookups.clone())), 1.0);
+        let mut s = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+        s.set_material(material);
+
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        match s.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert_eq!(0, lookups.get());
+                let normal = hit.shading_normal();
+                let looked_up = lookups.get();
+                assert!(looked_up > 0);
+
+                // Only worked out once
+                assert_eq!(normal, hit.shading_normal());
+                assert_eq!(looked_up, lookups.get());
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
 }
@@ -0,0 +1,220 @@
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::material::Material;
+use crate::matrix::Mat4;
+use crate::polynomial::solve_quartic;
+use crate::ray::Ray;
+use crate::vector::Vec4;
+
+/// A ring doughnut. In object space, the middle of the tube runs round a circle of
+/// radius 1 on the y = 0 plane, and `tube` is the radius of the tube itself.
+#[derive(Debug)]
+pub struct Torus<T: Float> {
+    object: Mat4<T>,
+    object_inverse: Mat4<T>,
+    tube: T,
+    material: Material<T>,
+}
+
+impl<T: Float> WorldObject<T> for Torus<T> {
+    fn object_matrix(&self) -> &Mat4<T> {
+        &self.object
+    }
+
+    fn object_matrix_inv(&self) -> &Mat4<T> {
+        &self.object_inverse
+    }
+}
+
+impl<T> Torus<T>
+where
+    T: Float + FromPrimitive + 'static,
+{
+    /// Lying flat around `centre`. `major_radius` is to the middle of the tube and
+    /// `minor_radius` the thickness of the tube.
+    pub fn new(centre: Vec4<T>, major_radius: T, minor_radius: T) -> Result<Torus<T>> {
+        check_positive(major_radius, "torus major radius")?;
+        check_positive(minor_radius, "torus minor radius")?;
+
+        let scale = Mat4::scale(&Vec4::direction(major_radius, major_radius, major_radius));
+        let object_matrix = &scale * &Mat4::translation(&centre);
+
+        Ok(Torus {
+            object_inverse: object_matrix.inverse()?,
+            object: object_matrix,
+            tube: minor_radius / major_radius,
+            material: Material::default(),
+        })
+    }
+
+    pub fn set_material(&mut self, material: Material<T>) {
+        self.material = material;
+    }
+}
+
+impl<T> Torus<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Everywhere the line through the ray meets the surface, nearest first
+    fn roots(&self, ray: &Ray<T>) -> Vec<T> {
+        let local = ray.transformed(self.object_matrix_inv());
+
+        // The quartic is badly conditioned for rays starting far away, so solve it
+        // from the point nearest the centre, along a unit direction
+        let length = local.direction.mag();
+        let d = local.direction.normalized();
+        let shift = -local.origin.dot_product(&d);
+        let o = local.at(shift / length);
+
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let four: T = FromPrimitive::from_f64(4.0).unwrap();
+        let eight: T = FromPrimitive::from_f64(8.0).unwrap();
+
+        let od = o.dot_product(&d);
+        let k = o.dot_product(&o) + T::one() - self.tube * self.tube;
+        let horizontal_dd = d.x * d.x + d.z * d.z;
+        let horizontal_od = o.x * d.x + o.z * d.z;
+        let horizontal_oo = o.x * o.x + o.z * o.z;
+
+        solve_quartic(
+            T::one(),
+            four * od,
+            four * od * od + two * k - four * horizontal_dd,
+            four * od * k - eight * horizontal_od,
+            k * k - four * horizontal_oo,
+        )
+        .into_iter()
+        .map(|s| (shift + s) / length)
+        .collect()
+    }
+
+    fn hit(&self, ray: &Ray<T>, t: T) -> HitRecord<'_, T> {
+        let point = ray.at(t);
+        let p = self.object_matrix_inv() * &point;
+        let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+        let two: T = FromPrimitive::from_f64(2.0).unwrap();
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+
+        // Gradient of (|p|^2 + 1 - tube^2)^2 - 4 (x^2 + z^2)
+        let m = p.x * p.x + p.y * p.y + p.z * p.z + T::one() - self.tube * self.tube;
+        let normal = Vec4::direction(p.x * (m - two), p.y * m, p.z * (m - two));
+
+        // u runs round the ring, v round the tube
+        let (u, tangent) = around_y_axis(&p);
+        let from_ring = (p.x * p.x + p.z * p.z).sqrt() - T::one();
+        let v = half + T::atan2(p.y, from_ring) / (two * pi);
+
+        let normal = self.normal_to_world(&normal);
+        HitRecord::new(
+            &ray.direction,
+            t,
+            point,
+            &normal,
+            &normal,
+            &self.direction_to_world(&tangent),
+            (u, v),
+            &self.material,
+        )
+    }
+}
+
+impl<T> Intersectable<T> for Torus<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn intersect(&self, ray: &Ray<T>) -> IntersectResult<'_, T> {
+        match self.roots(ray).into_iter().find(|t| ray.contains(*t)) {
+            Some(t) => IntersectResult::Intersect(self.hit(ray, t)),
+            None => IntersectResult::NoIntersect,
+        }
+    }
+
+    fn crossings(&self, ray: &Ray<T>) -> Vec<HitRecord<'_, T>> {
+        self.roots(ray)
+            .into_iter()
+            .filter(|t| ray.contains(*t))
+            .map(|t| self.hit(ray, t))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torus() -> Torus<f64> {
+        Torus::new(Vec4::position(0.0, 0.0, 0.0), 2.0, 0.5).unwrap()
+    }
+
+    #[test]
+    fn torus_intersect() {
+        let t = torus();
+        let ray = Ray::new(
+            Vec4::position(0.0, 0.0, -10.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+
+        match t.intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 7.5).abs() < 1e-9);
+                assert!((&hit.geometric_normal - &Vec4::direction(0.0, 0.0, -1.0)).mag() < 1e-9);
+                assert!(hit.front_face);
+            }
+            _ => panic!("expected an intersection"),
+        }
+
+        // In and out of each side of the ring
+        let ts: Vec<f64> = t.crossings(&ray).iter().map(|hit| hit.t).collect();
+        let expected = [7.5, 8.5, 11.5, 12.5];
+        assert_eq!(4, ts.len());
+        for (a, b) in ts.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1e-9, "{:?}", ts);
+        }
+    }
+
+    #[test]
+    fn torus_hole() {
+        let ray = Ray::new(
+            Vec4::position(0.0, 10.0, 0.0),
+            Vec4::direction(0.0, -1.0, 0.0),
+        );
+        assert!(matches!(
+            torus().intersect(&ray),
+            IntersectResult::NoIntersect
+        ));
+    }
+
+    #[test]
+    fn torus_top() {
+        let ray = Ray::new(
+            Vec4::position(2.0, 10.0, 0.0),
+            Vec4::direction(0.0, -1.0, 0.0),
+        );
+
+        match torus().intersect(&ray) {
+            IntersectResult::Intersect(hit) => {
+                assert!((hit.t - 9.5).abs() < 1e-9);
+                assert!((&hit.geometric_normal - &Vec4::direction(0.0, 1.0, 0.0)).mag() < 1e-9);
+                assert!((hit.uv.1 - 0.75).abs() < 1e-9);
+            }
+            _ => panic!("expected an intersection"),
+        }
+    }
+
+    #[test]
+    fn torus_from_a_distance() {
+        // Far away along a diagonal, where the quartic is at its least well behaved
+        let away = 1000.0 / 2.0f64.sqrt();
+        let ray = Ray::new(
+            Vec4::position(-away, 0.0, -away),
+            Vec4::direction(1.0, 0.0, 1.0).normalized(),
+        );
+
+        match torus().intersect(&ray) {
+            IntersectResult::Intersect(hit) => assert!((hit.t - 997.5).abs() < 1e-6),
+            _ => panic!("expected an intersection"),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use num::{Float, FromPrimitive};
+
+/// Real roots of a x^2 + b x + c, smallest first
+pub fn solve_quadratic<T>(a: T, b: T, c: T) -> Vec<T>
+where
+    T: Float + FromPrimitive,
+{
+    if a == T::zero() {
+        if b == T::zero() {
+            return vec![];
+        }
+        return vec![-c / b];
+    }
+
+    let four: T = FromPrimitive::from_f64(4.0).unwrap();
+    let half: T = FromPrimitive::from_f64(0.5).unwrap();
+
+    let discriminant = b * b - four * a * c;
+    if discriminant < T::zero() {
+        return vec![];
+    }
+
+    // Avoid subtracting nearly equal numbers when b is large
+    let q = -half * (b + b.signum() * discriminant.sqrt());
+    let mut roots = if q == T::zero() {
+        vec![T::zero(), T::zero()]
+    } else {
+        vec![q / a, c / q]
+    };
+    sort(&mut roots);
+    roots
+}
+
+/// Real roots of a x^3 + b x^2 + c x + d, smallest first
+pub fn solve_cubic<T>(a: T, b: T, c: T, d: T) -> Vec<T>
+where
+    T: Float + FromPrimitive,
+{
+    if a == T::zero() {
+        return solve_quadratic(b, c, d);
+    }
+
+    let two: T = FromPrimitive::from_f64(2.0).unwrap();
+    let three: T = FromPrimitive::from_f64(3.0).unwrap();
+    let nine: T = FromPrimitive::from_f64(9.0).unwrap();
+    let twenty_seven: T = FromPrimitive::from_f64(27.0).unwrap();
+    let pi: T = FromPrimitive::from_f64(std::f64::consts::PI).unwrap();
+
+    let (b, c, d) = (b / a, c / a, d / a);
+
+    // Substitute x = y - b / 3 for y^3 + 3 p y + 2 q = 0
+    let shift = b / three;
+    let p = (three * c - b * b) / nine;
+    let q = (two * b * b * b - nine * b * c + twenty_seven * d) / (two * twenty_seven);
+
+    let discriminant = q * q + p * p * p;
+    let mut roots = if discriminant > T::zero() {
+        // One real root
+        let s = discriminant.sqrt();
+        vec![(-q + s).cbrt() + (-q - s).cbrt() - shift]
+    } else if p == T::zero() {
+        vec![-shift]
+    } else {
+        // Three real roots, from the trigonometric form
+        let r = (-p).sqrt();
+        let theta = (-q / (r * r * r)).max(-T::one()).min(T::one()).acos() / three;
+        (0..3)
+            .map(|k| {
+                let k: T = FromPrimitive::from_u32(k).unwrap();
+                two * r * (theta - two * pi * k / three).cos() - shift
+            })
+            .collect()
+    };
+    sort(&mut roots);
+    roots
+}
+
+/// Real roots of a x^4 + b x^3 + c x^2 + d x + e, smallest first
+pub fn solve_quartic<T>(a: T, b: T, c: T, d: T, e: T) -> Vec<T>
+where
+    T: Float + FromPrimitive,
+{
+    if a == T::zero() {
+        return solve_cubic(b, c, d, e);
+    }
+
+    let two: T = FromPrimitive::from_f64(2.0).unwrap();
+    let three: T = FromPrimitive::from_f64(3.0).unwrap();
+    let four: T = FromPrimitive::from_f64(4.0).unwrap();
+    let eight: T = FromPrimitive::from_f64(8.0).unwrap();
+    let sixteen: T = FromPrimitive::from_f64(16.0).unwrap();
+    let two_five_six: T = FromPrimitive::from_f64(256.0).unwrap();
+    let tiny: T = FromPrimitive::from_f64(1e-12).unwrap();
+
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+
+    // Substitute x = y - b / 4 for y^4 + p y^2 + q y + r = 0
+    let shift = b / four;
+    let b2 = b * b;
+    let p = c - three * b2 / eight;
+    let q = d - b * c / two + b2 * b / eight;
+    let r = e - b * d / four + b2 * c / sixteen - three * b2 * b2 / two_five_six;
+
+    let ys = if q.abs() < tiny {
+        // Biquadratic: a quadratic in y^2
+        solve_quadratic(T::one(), p, r)
+            .into_iter()
+            .filter(|z| *z >= T::zero())
+            .flat_map(|z| vec![-z.sqrt(), z.sqrt()])
+            .collect()
+    } else {
+        // Ferrari: choose m so the quartic is a difference of two squares, and so
+        // factors into two quadratics. There's always a positive m when q != 0.
+        let m = solve_cubic(T::one(), p, p * p / four - r, -q * q / eight)
+            .into_iter()
+            .fold(T::zero(), T::max);
+        if m <= T::zero() {
+            return vec![];
+        }
+        let s = (two * m).sqrt();
+        let mut ys = solve_quadratic(T::one(), s, p / two + m - q / (two * s));
+        ys.extend(solve_quadratic(T::one(), -s, p / two + m + q / (two * s)));
+        ys
+    };
+
+    let mut roots: Vec<T> = ys
+        .into_iter()
+        .map(|y| polish(y - shift, &[T::one(), b, c, d, e]))
+        .collect();
+    sort(&mut roots);
+    roots
+}
+
+/// A couple of Newton steps to tidy up the rounding errors that pile up when
+/// solving in closed form
+fn polish<T: Float>(mut x: T, coefficients: &[T]) -> T {
+    for _ in 0..2 {
+        let (mut value, mut slope) = (T::zero(), T::zero());
+        for c in coefficients {
+            slope = slope * x + value;
+            value = value * x + *c;
+        }
+        if slope == T::zero() {
+            break;
+        }
+        x = x - value / slope;
+    }
+    x
+}
+
+fn sort<T: Float>(roots: &mut [T]) {
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(expected: &[f64], actual: Vec<f64>) {
+        assert_eq!(expected.len(), actual.len(), "{:?}", actual);
+        for (e, a) in expected.iter().zip(actual.iter()) {
+            assert!((e - a).abs() < 1e-9, "{:?}", actual);
+        }
+    }
+
+    #[test]
+    fn quadratic() {
+        assert_roots(&[-3.0, 2.0], solve_quadratic(1.0, 1.0, -6.0));
+        assert_roots(&[], solve_quadratic(1.0, 0.0, 1.0));
+        assert_roots(&[2.0], solve_quadratic(0.0, 2.0, -4.0));
+    }
+
+    #[test]
+    fn cubic() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(&[1.0, 2.0, 3.0], solve_cubic(1.0, -6.0, 11.0, -6.0));
+        // (x - 2)(x^2 + 1)
+        assert_roots(&[2.0], solve_cubic(2.0, -4.0, 2.0, -4.0));
+    }
+
+    #[test]
+    fn quartic() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(
+            &[1.0, 2.0, 3.0, 4.0],
+            solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0),
+        );
+        // (x^2 - 1)(x^2 - 4), which is biquadratic
+        assert_roots(
+            &[-2.0, -1.0, 1.0, 2.0],
+            solve_quartic(1.0, 0.0, -5.0, 0.0, 4.0),
+        );
+        // (x^2 + 1)(x^2 + 4) has no real roots
+        assert_roots(&[], solve_quartic(1.0, 0.0, 5.0, 0.0, 4.0));
+        // (x + 0.5)(x - 7)(x^2 + 1), scaled
+        assert_roots(&[-0.5, 7.0], solve_quartic(3.0, -19.5, -7.5, -19.5, -10.5));
+    }
+}
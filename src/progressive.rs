@@ -0,0 +1,701 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use image::{Rgb, RgbImage};
+use num::{Float, FromPrimitive};
+
+use crate::checkpoint::{self, Checkpoint};
+use crate::colour;
+use crate::denoise::FloatImage;
+use crate::engine::{Engine, Film};
+use crate::error::{Error, Result};
+use crate::random::Rng;
+use crate::sampler::independent::Independent;
+use crate::sampler::Sampler;
+use crate::session::CancelToken;
+
+/// Pixels are judged converged a square tile at a time, so that a tile with any
+/// noise left in it gets refined as a whole
+const ADAPTIVE_TILE: u32 = 8;
+
+/// Running totals of the samples taken for every pixel of an image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator<T: Float> {
+    width: u32,
+    height: u32,
+    sum: Vec<[T; 3]>,
+    count: Vec<u32>,
+    /// Sum of squared differences from the mean of the samples' luminance, kept
+    /// up to date as each sample comes in (Welford's method)
+    m2: Vec<T>,
+}
+
+impl<T> Accumulator<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn new(width: u32, height: u32) -> Accumulator<T> {
+        let pixels = width as usize * height as usize;
+        Accumulator {
+            width,
+            height,
+            sum: vec![[T::zero(); 3]; pixels],
+            count: vec![0; pixels],
+            m2: vec![T::zero(); pixels],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn add(&mut self, x: u32, y: u32, colour: [T; 3]) {
+        let i = self.index(x, y);
+        let before = self.mean_luminance(i);
+
+        for (sum, c) in self.sum[i].iter_mut().zip(colour.iter()) {
+            *sum = *sum + *c;
+        }
+        self.count[i] += 1;
+
+        let l = colour::luminance(&colour);
+        // Rounding can leave a tiny negative sum when every sample is the same
+        let m2 = self.m2[i] + (l - before) * (l - self.mean_luminance(i));
+        self.m2[i] = m2.max(T::zero());
+    }
+
+    /// Samples taken so far at the pixel
+    pub fn count(&self, x: u32, y: u32) -> u32 {
+        self.count[self.index(x, y)]
+    }
+
+    /// The total of the samples at the pixel
+    pub fn sum(&self, x: u32, y: u32) -> [T; 3] {
+        self.sum[self.index(x, y)]
+    }
+
+    /// Sum of squared differences from the mean luminance, from which the
+    /// variance follows
+    pub fn m2(&self, x: u32, y: u32) -> T {
+        self.m2[self.index(x, y)]
+    }
+
+    /// Put back a pixel's totals, as saved in a checkpoint
+    pub(crate) fn restore(&mut self, x: u32, y: u32, sum: [T; 3], count: u32, m2: T) {
+        let i = self.index(x, y);
+        self.sum[i] = sum;
+        self.count[i] = count;
+        self.m2[i] = m2;
+    }
+
+    /// How far the pixel's mean luminance is likely to be from where it would
+    /// settle with endless samples. Unknown, so infinite, until there are two.
+    pub fn standard_error(&self, x: u32, y: u32) -> T {
+        let i = self.index(x, y);
+        if self.count[i] < 2 {
+            return T::infinity();
+        }
+        let n: T = FromPrimitive::from_u32(self.count[i]).unwrap();
+        let variance = self.m2[i] / (n - T::one());
+        (variance / n).sqrt()
+    }
+
+    /// The average of the samples at the pixel, or black if there aren't any yet
+    pub fn mean(&self, x: u32, y: u32) -> [T; 3] {
+        let i = self.index(x, y);
+        if self.count[i] == 0 {
+            return [T::zero(); 3];
+        }
+        let n: T = FromPrimitive::from_u32(self.count[i]).unwrap();
+        [self.sum[i][0] / n, self.sum[i][1] / n, self.sum[i][2] / n]
+    }
+
+    pub fn to_image(&self) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            colour::to_rgb(self.mean(x, y))
+        })
+    }
+
+    /// The means unclamped, ready for denoising
+    pub fn to_float_image(&self) -> FloatImage<T> {
+        FloatImage::from_fn(self.width, self.height, |x, y| self.mean(x, y))
+    }
+
+    /// Samples per pixel, from black for the fewest through blue and red to
+    /// yellow for the most
+    pub fn sample_heatmap(&self) -> RgbImage {
+        let most = self.count.iter().copied().max().unwrap_or(0).max(1);
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            heat(self.count(x, y) as f64 / most as f64)
+        })
+    }
+
+    fn mean_luminance(&self, i: usize) -> T {
+        if self.count[i] == 0 {
+            return T::zero();
+        }
+        let n: T = FromPrimitive::from_u32(self.count[i]).unwrap();
+        colour::luminance(&self.sum[i]) / n
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+}
+
+/// What a progressive render had reached when it stopped
+#[derive(Debug)]
+pub struct Refined<T: Float> {
+    pub image: RgbImage,
+    pub accumulator: Accumulator<T>,
+    /// Complete passes over the image. Without adaptive sampling, every pixel
+    /// has this many samples.
+    pub passes: u32,
+    /// Rows of the next pass already done, if it stopped part way through one
+    pub row: u32,
+    pub elapsed: Duration,
+}
+
+impl<T> Refined<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// Where to carry on from, to refine the image further
+    pub fn checkpoint(&self) -> Checkpoint<T> {
+        Checkpoint {
+            accumulator: self.accumulator.clone(),
+            passes: self.passes,
+            row: self.row,
+        }
+    }
+}
+
+/// A render that goes over the whole image again and again, one sample per
+/// pixel each time, so there's a rough image quickly and a better one the
+/// longer it runs. It stops on reaching the sample target, or when the time
+/// budget runs out; the first pass is always finished, so the image is complete.
+///
+/// With adaptive sampling, later passes skip tiles whose pixels have settled
+/// down, spending the time on the noisy parts of the image instead, and the
+/// render stops early once everything has settled.
+pub struct ProgressiveRender<'a, T: Float> {
+    engine: &'a Engine<T>,
+    width: u32,
+    height: u32,
+    target_samples: Option<u32>,
+    time_budget: Option<Duration>,
+    snapshots: Option<(PathBuf, Duration)>,
+    checkpoints: Option<(PathBuf, Duration)>,
+    resume: Option<Checkpoint<T>>,
+    adaptive: Option<(T, u32)>,
+    sampler: Option<Box<dyn Sampler<T> + 'a>>,
+    cancel: CancelToken,
+}
+
+impl<'a, T> ProgressiveRender<'a, T>
+where
+    T: Float + FromPrimitive + std::fmt::Debug,
+{
+    pub fn new(engine: &'a Engine<T>, width: u32, height: u32) -> ProgressiveRender<'a, T> {
+        ProgressiveRender {
+            engine,
+            width,
+            height,
+            target_samples: None,
+            time_budget: None,
+            snapshots: None,
+            checkpoints: None,
+            resume: None,
+            adaptive: None,
+            sampler: None,
+            cancel: CancelToken::new(),
+        }
+    }
+
+    /// Stop once every pixel has this many samples. With adaptive sampling, this
+    /// is the most any pixel gets.
+    pub fn target_samples(mut self, samples: u32) -> Self {
+        self.target_samples = Some(samples.max(1));
+        self
+    }
+
+    /// Stop once this much time has passed
+    pub fn time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Save the image so far to `path` every `interval`, and once more at the end
+    pub fn snapshots<P: AsRef<Path>>(mut self, path: P, interval: Duration) -> Self {
+        self.snapshots = Some((path.as_ref().to_path_buf(), interval));
+        self
+    }
+
+    /// Save a checkpoint to `path` every `interval`, and once more at the end
+    pub fn checkpoints<P: AsRef<Path>>(mut self, path: P, interval: Duration) -> Self {
+        self.checkpoints = Some((path.as_ref().to_path_buf(), interval));
+        self
+    }
+
+    /// Carry on from an earlier render of the same scene. The sample target
+    /// counts the passes done before, but the time budget starts afresh.
+    pub fn resume(mut self, checkpoint: Checkpoint<T>) -> Self {
+        self.resume = Some(checkpoint);
+        self
+    }
+
+    /// Only take more samples in tiles where some pixel's standard error, in
+    /// luminance from 0 to 1, is still above `threshold`. Every pixel gets at
+    /// least `min_samples` (and at least two) first, to have a variance to go on.
+    pub fn adaptive(mut self, threshold: T, min_samples: u32) -> Self {
+        self.adaptive = Some((threshold, min_samples.max(2)));
+        self
+    }
+
+    /// Where the numbers for each sample's random choices come from; plain
+    /// random numbers unless set. A render carried on from a checkpoint needs
+    /// the same sampler to come out as if it had never stopped.
+    pub fn sampler(mut self, sampler: Box<dyn Sampler<T> + 'a>) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Stops the render as if the time budget had run out
+    pub fn cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn run(mut self) -> Result<Refined<T>> {
+        if self.target_samples.is_none() && self.time_budget.is_none() {
+            return Err(Error::InvalidScene(
+                "a progressive render needs a sample target or a time budget".to_string(),
+            ));
+        }
+
+        let film = self.engine.film(self.width, self.height)?;
+        let (mut accumulator, mut passes, mut row) = match self.resume.take() {
+            Some(checkpoint) => {
+                let a = &checkpoint.accumulator;
+                if (a.width(), a.height()) != (self.width, self.height) {
+                    return Err(Error::InvalidCheckpoint(format!(
+                        "checkpoint is {}x{}, but the render is {}x{}",
+                        a.width(),
+                        a.height(),
+                        self.width,
+                        self.height
+                    )));
+                }
+                (checkpoint.accumulator, checkpoint.passes, checkpoint.row)
+            }
+            None => (Accumulator::new(self.width, self.height), 0, 0),
+        };
+
+        let mut sampler = self
+            .sampler
+            .take()
+            .unwrap_or_else(|| Box::new(Independent::new(0)));
+
+        let start = Instant::now();
+        let mut last_snapshot = start;
+        let mut last_checkpoint = start;
+
+        // Adaptive decisions are made a band of tiles at a time, which is never
+        // split by stopping, so that resuming makes the same decisions
+        let band = if self.adaptive.is_some() {
+            ADAPTIVE_TILE
+        } else {
+            1
+        };
+
+        'passes: while self.target_samples.map_or(true, |target| passes < target) {
+            if row == 0 && !self.any_tile_needs_samples(&accumulator) {
+                break;
+            }
+
+            while row < self.height {
+                // Checked between rows, but never before the first pass is done
+                if passes > 0 && self.out_of_time(start) {
+                    break 'passes;
+                }
+                if let Some((path, interval)) = &self.snapshots {
+                    if last_snapshot.elapsed() >= *interval {
+                        save_snapshot(&accumulator, path)?;
+                        last_snapshot = Instant::now();
+                    }
+                }
+                if let Some((path, interval)) = &self.checkpoints {
+                    if last_checkpoint.elapsed() >= *interval {
+                        checkpoint::save(&accumulator, passes, row, path)?;
+                        last_checkpoint = Instant::now();
+                    }
+                }
+
+                let rows = row..(row + band).min(self.height);
+                for x0 in (0..self.width).step_by(band as usize) {
+                    let columns = x0..(x0 + band).min(self.width);
+                    if !self.needs_samples(&accumulator, columns.clone(), rows.clone()) {
+                        continue;
+                    }
+                    for y in rows.clone() {
+                        for x in columns.clone() {
+                            let colour =
+                                self.sample(&film, sampler.as_mut(), &accumulator, x, y)?;
+                            accumulator.add(x, y, colour);
+                        }
+                    }
+                }
+                row = rows.end;
+            }
+            row = 0;
+            passes += 1;
+
+            if self.out_of_time(start) {
+                break;
+            }
+        }
+
+        if let Some((path, _)) = &self.snapshots {
+            save_snapshot(&accumulator, path)?;
+        }
+        if let Some((path, _)) = &self.checkpoints {
+            checkpoint::save(&accumulator, passes, row, path)?;
+        }
+
+        Ok(Refined {
+            image: accumulator.to_image(),
+            accumulator,
+            passes,
+            row,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// The next sample for a pixel. Each draws from its own generator, and its
+    /// own sample of `sampler`, picked by the pixel and how many samples it
+    /// already has, so the image doesn't depend on the order pixels are done in
+    /// or on where a render was resumed.
+    fn sample(
+        &self,
+        film: &Film<T>,
+        sampler: &mut dyn Sampler<T>,
+        accumulator: &Accumulator<T>,
+        x: u32,
+        y: u32,
+    ) -> Result<[T; 3]> {
+        let n = accumulator.count(x, y);
+        let mut rng = Rng::new(sample_seed(x, y, n));
+        sampler.start_sample(x, y, n);
+
+        // The first sample looks through the middle of the pixel, just as a
+        // single-sample render does
+        let colour = self
+            .engine
+            .render_sample(film, x, y, &mut rng, sampler, n > 0);
+        if colour.iter().any(|c| c.is_nan()) {
+            return Err(Error::NanRadiance { x, y });
+        }
+        Ok(colour)
+    }
+
+    fn needs_samples(
+        &self,
+        accumulator: &Accumulator<T>,
+        columns: Range<u32>,
+        rows: Range<u32>,
+    ) -> bool {
+        let (threshold, min_samples) = match self.adaptive {
+            Some(adaptive) => adaptive,
+            None => return true,
+        };
+        rows.flat_map(|y| columns.clone().map(move |x| (x, y)))
+            .any(|(x, y)| {
+                accumulator.count(x, y) < min_samples
+                    || accumulator.standard_error(x, y) > threshold
+            })
+    }
+
+    fn any_tile_needs_samples(&self, accumulator: &Accumulator<T>) -> bool {
+        self.adaptive.is_none() || self.needs_samples(accumulator, 0..self.width, 0..self.height)
+    }
+
+    fn out_of_time(&self, start: Instant) -> bool {
+        self.cancel.is_cancelled()
+            || self
+                .time_budget
+                .is_some_and(|budget| start.elapsed() >= budget)
+    }
+}
+
+/// A different, but repeatable, random sequence for every sample of every pixel
+fn sample_seed(x: u32, y: u32, sample: u32) -> u64 {
+    let pixel = ((y as u64) << 32) | x as u64;
+    pixel
+        ^ (sample as u64)
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            .rotate_left(17)
+}
+
+/// A colour ramp from black, through blue and red, to yellow, for `t` from 0 to 1
+fn heat(t: f64) -> Rgb<u8> {
+    const STOPS: [[f64; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+    ];
+    let t = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let i = (t.floor() as usize).min(STOPS.len() - 2);
+    let f = t - i as f64;
+
+    let mut c = [0.0; 3];
+    for (k, channel) in c.iter_mut().enumerate() {
+        *channel = STOPS[i][k] * (1.0 - f) + STOPS[i + 1][k] * f;
+    }
+    colour::to_rgb(c)
+}
+
+/// Written alongside and then renamed over the old snapshot, so anyone watching
+/// the file never sees half an image
+fn save_snapshot<T>(accumulator: &Accumulator<T>, path: &Path) -> Result<()>
+where
+    T: Float + FromPrimitive,
+{
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+
+    let format = image::ImageFormat::from_path(path)?;
+    accumulator.to_image().save_with_format(&partial, format)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::ambientlight::AmbientLight;
+    use crate::matrix::Mat4;
+    use crate::object::sphere::Sphere;
+    use crate::vector::Vec4;
+
+    fn engine() -> Engine<f64> {
+        let mut engine = Engine::new(Mat4::look(
+            &Vec4::position(0.0, 0.0, -10.0),
+            &Vec4::position(0.0, 0.0, 0.0),
+        ));
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 0.0), 5.0).unwrap(),
+        ));
+        engine.add_light(Box::new(AmbientLight::new(image::Rgb([255, 255, 255]))));
+        engine
+    }
+
+    #[test]
+    fn accumulator() {
+        let mut a: Accumulator<f64> = Accumulator::new(2, 1);
+        a.add(1, 0, [1.0, 0.0, 0.5]);
+        a.add(1, 0, [0.0, 0.0, 0.5]);
+
+        assert_eq!(0, a.count(0, 0));
+        assert_eq!([0.0, 0.0, 0.0], a.mean(0, 0));
+        assert_eq!(2, a.count(1, 0));
+        assert_eq!([0.5, 0.0, 0.5], a.mean(1, 0));
+    }
+
+    #[test]
+    fn variance() {
+        let mut a: Accumulator<f64> = Accumulator::new(1, 1);
+        a.add(0, 0, [0.0, 0.0, 0.0]);
+        assert_eq!(f64::INFINITY, a.standard_error(0, 0));
+
+        // Luminance 0 and 1: a variance of 0.5 over two samples
+        a.add(0, 0, [1.0, 1.0, 1.0]);
+        assert!((a.m2(0, 0) - 0.5).abs() < 1e-12);
+        assert!((a.standard_error(0, 0) - 0.5).abs() < 1e-12);
+
+        a.add(0, 0, [0.5, 0.5, 0.5]);
+        assert!((a.m2(0, 0) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn stops_at_target() {
+        let engine = engine();
+        let refined = ProgressiveRender::new(&engine, 8, 6)
+            .target_samples(3)
+            .run()
+            .unwrap();
+
+        assert_eq!(3, refined.passes);
+        assert_eq!(3, refined.accumulator.count(0, 0));
+        assert_eq!(3, refined.accumulator.count(7, 5));
+    }
+
+    #[test]
+    fn first_pass_matches_a_single_sample_render() {
+        let engine = engine();
+        let refined = ProgressiveRender::new(&engine, 9, 7)
+            .target_samples(1)
+            .run()
+            .unwrap();
+        assert_eq!(
+            engine.render(9, 7).unwrap().into_raw(),
+            refined.image.into_raw()
+        );
+    }
+
+    #[test]
+    fn stops_when_out_of_time() {
+        let engine = engine();
+        // Even with no time at all, the first pass is finished
+        let refined = ProgressiveRender::new(&engine, 8, 6)
+            .time_budget(Duration::from_secs(0))
+            .run()
+            .unwrap();
+
+        assert_eq!(1, refined.passes);
+        assert_eq!(1, refined.accumulator.count(7, 5));
+    }
+
+    #[test]
+    fn needs_a_stopping_point() {
+        let engine = engine();
+        assert!(matches!(
+            ProgressiveRender::new(&engine, 8, 6).run(),
+            Err(Error::InvalidScene(_))
+        ));
+    }
+
+    #[test]
+    fn writes_snapshots() {
+        let engine = engine();
+        let path = std::env::temp_dir().join(format!(
+            "tracer-rs-progressive-test-{}.png",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        ProgressiveRender::new(&engine, 8, 6)
+            .target_samples(2)
+            .snapshots(&path, Duration::from_secs(0))
+            .run()
+            .unwrap();
+
+        let snapshot = image::open(&path).unwrap().to_rgb8();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((8, 6), snapshot.dimensions());
+    }
+
+    #[test]
+    fn resume_matches_uninterrupted() {
+        let engine = engine();
+        let whole = ProgressiveRender::new(&engine, 8, 6)
+            .target_samples(4)
+            .run()
+            .unwrap();
+
+        // Stopped after the first pass, then carried on from the checkpoint file
+        let path = std::env::temp_dir().join(format!(
+            "tracer-rs-resume-test-{}.checkpoint",
+            std::process::id()
+        ));
+        let first = ProgressiveRender::new(&engine, 8, 6)
+            .target_samples(4)
+            .time_budget(Duration::from_secs(0))
+            .checkpoints(&path, Duration::from_secs(3600))
+            .run()
+            .unwrap();
+        assert_eq!(1, first.passes);
+
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let resumed = ProgressiveRender::new(&engine, 8, 6)
+            .target_samples(4)
+            .resume(checkpoint)
+            .run()
+            .unwrap();
+
+        assert_eq!(4, resumed.passes);
+        assert_eq!(whole.accumulator, resumed.accumulator);
+        assert_eq!(whole.image.into_raw(), resumed.image.into_raw());
+    }
+
+    #[test]
+    fn resume_needs_the_same_size() {
+        let engine = engine();
+        let first = ProgressiveRender::new(&engine, 8, 6)
+            .target_samples(1)
+            .run()
+            .unwrap();
+
+        assert!(matches!(
+            ProgressiveRender::new(&engine, 6, 8)
+                .target_samples(2)
+                .resume(first.checkpoint())
+                .run(),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+    }
+
+    #[test]
+    fn adaptive_sampling() {
+        let engine = engine();
+        let refined = ProgressiveRender::new(&engine, 48, 48)
+            .target_samples(16)
+            .adaptive(0.01, 4)
+            .run()
+            .unwrap();
+        let a = &refined.accumulator;
+
+        // Tiles of flat background settle straight away, but the jittered samples
+        // along the sphere's edge keep disagreeing
+        assert_eq!(4, a.count(0, 0));
+        assert_eq!(4, a.count(40, 24));
+        assert_eq!(16, a.count(16, 24));
+        // Rounding must not leave identical samples with a NaN spread
+        assert!(a.standard_error(24, 24) < 1e-12);
+        let most = (0..48)
+            .flat_map(|y| (0..48).map(move |x| (x, y)))
+            .map(|(x, y)| a.count(x, y))
+            .max()
+            .unwrap();
+        assert_eq!(16, most);
+
+        // The most sampled pixels are the hottest, and the rest a quarter of the way
+        let heatmap = a.sample_heatmap();
+        assert_eq!(&Rgb([255, 255, 0]), heatmap.get_pixel(16, 24));
+        assert_eq!(&Rgb([0, 0, 191]), heatmap.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn adaptive_stops_when_settled() {
+        // Nothing to see, so nothing is ever noisy
+        let engine: Engine<f64> = Engine::new(Mat4::i());
+        let refined = ProgressiveRender::new(&engine, 8, 8)
+            .target_samples(100)
+            .adaptive(0.01, 3)
+            .run()
+            .unwrap();
+        assert_eq!(3, refined.passes);
+    }
+
+    #[test]
+    fn adaptive_resume_matches_uninterrupted() {
+        let engine = engine();
+        let render = || {
+            ProgressiveRender::new(&engine, 20, 20)
+                .target_samples(8)
+                .adaptive(0.01, 2)
+        };
+        let whole = render().run().unwrap();
+
+        let first = render().time_budget(Duration::from_secs(0)).run().unwrap();
+        let resumed = render().resume(first.checkpoint()).run().unwrap();
+        assert_eq!(whole.accumulator, resumed.accumulator);
+    }
+}
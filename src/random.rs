@@ -0,0 +1,93 @@
+use num::{Float, FromPrimitive};
+
+use crate::vector::Vec4;
+
+/// A small xorshift* generator. Not remotely cryptographic, but fast, seedable and
+/// plenty good enough for scattering sample rays.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck on a zero state, so mix the seed and make sure it isn't
+        let state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ 0xd1b5_4a32_d192_ed03;
+        Rng {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniformly distributed in [0, 1)
+    pub fn next_float<T>(&mut self) -> T
+    where
+        T: Float + FromPrimitive,
+    {
+        // Top 53 bits gives every representable f64 in [0, 1) an equal chance
+        let f = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        FromPrimitive::from_f64(f).unwrap()
+    }
+}
+
+/// Map a pair of uniform samples in [0, 1) onto a cosine-weighted direction in the
+/// hemisphere around `normal`
+pub fn cosine_hemisphere<T>(normal: &Vec4<T>, u: T, v: T) -> Vec4<T>
+where
+    T: Float + FromPrimitive,
+{
+    let two_pi: T = FromPrimitive::from_f64(std::f64::consts::PI * 2.0).unwrap();
+
+    let r = T::sqrt(u);
+    let phi = two_pi * v;
+    let x = r * T::cos(phi);
+    let y = r * T::sin(phi);
+    let z = T::sqrt(T::max(T::zero(), T::one() - u));
+
+    let n = Vec4::direction(normal.x, normal.y, normal.z).normalized();
+    let (tangent, bitangent) = n.basis();
+
+    let d = &(&(&tangent * x) + &(&bitangent * y)) + &(&n * z);
+    d.normalized()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn float_range() {
+        let mut r = Rng::new(0);
+        for _ in 0..1000 {
+            let f: f64 = r.next_float();
+            assert!((0.0..1.0).contains(&f));
+        }
+    }
+
+    #[test]
+    fn hemisphere() {
+        let mut r = Rng::new(7);
+        let n = Vec4::direction(0.0, 1.0, 0.0);
+        for _ in 0..1000 {
+            let d = cosine_hemisphere(&n, r.next_float(), r.next_float());
+            assert!(d.dot_product(&n) >= 0.0);
+            assert!((d.mag() - 1.0).abs() < 1e-9);
+            assert_eq!(0.0, d.w);
+        }
+    }
+}
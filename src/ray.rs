@@ -0,0 +1,127 @@
+use num::Float;
+
+use crate::matrix::Mat4;
+use crate::vector::Vec4;
+
+/// A half-line from `origin` along `direction`, of which only the part between
+/// `t_min` and `t_max` counts when looking for hits
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Ray<T: Float> {
+    pub origin: Vec4<T>,
+    pub direction: Vec4<T>,
+    pub t_min: T,
+    pub t_max: T,
+    /// When in the exposure the ray was cast, for anything that moves
+    pub time: Option<T>,
+    /// The single wavelength the ray carries, in nanometres, for anything dispersive
+    pub wavelength: Option<T>,
+}
+
+impl<T> Ray<T>
+where
+    T: Float,
+{
+    /// Everything in front of the origin
+    pub fn new(origin: Vec4<T>, direction: Vec4<T>) -> Ray<T> {
+        Ray::segment(origin, direction, T::zero(), T::infinity())
+    }
+
+    /// Only the part of the ray between `t_min` and `t_max`
+    pub fn segment(origin: Vec4<T>, direction: Vec4<T>, t_min: T, t_max: T) -> Ray<T> {
+        Ray {
+            origin,
+            direction,
+            t_min,
+            t_max,
+            time: None,
+            wavelength: None,
+        }
+    }
+
+    pub fn with_time(self, time: T) -> Ray<T> {
+        Ray {
+            time: Some(time),
+            ..self
+        }
+    }
+
+    pub fn with_wavelength(self, wavelength: T) -> Ray<T> {
+        Ray {
+            wavelength: Some(wavelength),
+            ..self
+        }
+    }
+
+    /// The point `t` along the ray
+    pub fn at(&self, t: T) -> Vec4<T> {
+        &self.origin + &(&self.direction * t)
+    }
+
+    /// Whether a hit at `t` lies within the ray's interval
+    pub fn contains(&self, t: T) -> bool {
+        t >= self.t_min && t <= self.t_max
+    }
+
+    /// The same ray cut short at `t`, for finding anything nearer than a hit
+    pub fn clipped(&self, t: T) -> Ray<T> {
+        Ray {
+            t_max: T::min(self.t_max, t),
+            ..*self
+        }
+    }
+
+    /// The ray in another coordinate system. Directions aren't renormalised, so
+    /// distances along the ray are the same in both.
+    pub fn transformed(&self, matrix: &Mat4<T>) -> Ray<T> {
+        Ray {
+            origin: matrix * &self.origin,
+            direction: matrix * &self.direction,
+            ..*self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn at() {
+        let r = Ray::new(
+            Vec4::position(1.0, 0.0, 0.0),
+            Vec4::direction(0.0, 2.0, 0.0),
+        );
+        assert_eq!(Vec4::position(1.0, 3.0, 0.0), r.at(1.5));
+    }
+
+    #[test]
+    fn contains() {
+        let r = Ray::new(
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::direction(0.0, 0.0, 1.0),
+        );
+        assert!(r.contains(0.0));
+        assert!(r.contains(1e9));
+        assert!(!r.contains(-1.0));
+
+        let s = r.clipped(5.0);
+        assert!(s.contains(5.0));
+        assert!(!s.contains(5.1));
+    }
+
+    #[test]
+    fn transformed_keeps_distances() {
+        let r = Ray::segment(
+            Vec4::position(0.0, 0.0, 0.0),
+            Vec4::direction(1.0, 0.0, 0.0),
+            1.0,
+            2.0,
+        )
+        .with_time(0.5);
+        let m = Mat4::scale(&Vec4::direction(2.0, 2.0, 2.0));
+        let t = r.transformed(&m);
+
+        assert_eq!(Vec4::position(4.0, 0.0, 0.0), t.at(2.0));
+        assert_eq!((1.0, 2.0, Some(0.5)), (t.t_min, t.t_max, t.time));
+    }
+}
@@ -0,0 +1,241 @@
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::random::Rng;
+
+/// Side of the square of blue noise tiled across the image
+const TILE: usize = 32;
+
+/// Spread of the filter used to find clusters and voids in the tile, in pixels
+const SIGMA: f64 = 1.5;
+
+/// Golden-ratio steps, which spread successive samples of a pixel evenly in one
+/// dimension (R1) and in two (R2)
+const R1: f64 = 0.618_033_988_749_894_8;
+const R2: [f64; 2] = [0.754_877_666_246_692_7, 0.569_840_290_998_053_3];
+
+/// Values that vary from pixel to pixel like blue noise: neighbouring pixels
+/// get very different values, so what error is left shows up as a fine grain
+/// the eye barely notices, rather than blotches. Each dimension looks up its
+/// own part of a tile of blue noise, and successive samples of a pixel step on
+/// by the golden ratio so they're spread out too.
+#[derive(Debug, Clone)]
+pub struct BlueNoise<T: Float> {
+    seed: u64,
+    /// Each pixel's rank, scaled into [0, 1)
+    tile: &'static [f64],
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    precision: PhantomData<T>,
+}
+
+impl<T> BlueNoise<T>
+where
+    T: Float,
+{
+    pub fn new(seed: u64) -> BlueNoise<T> {
+        BlueNoise {
+            seed,
+            tile: tile(),
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            precision: PhantomData,
+        }
+    }
+
+    /// The tile's value under the pixel, with the tile shifted by an amount
+    /// picked for this dimension and `channel`
+    fn lookup(&self, channel: u64) -> f64 {
+        let offset = hash(&[self.seed, self.dimension as u64, channel]);
+        let x = (self.pixel.0 as usize + (offset as usize % TILE)) % TILE;
+        let y = (self.pixel.1 as usize + ((offset >> 32) as usize % TILE)) % TILE;
+        self.tile[y * TILE + x]
+    }
+}
+
+/// The tile is the same for every sampler, so it's only worked out once
+fn tile() -> &'static [f64] {
+    static TILE_RANKS: OnceLock<Vec<f64>> = OnceLock::new();
+    TILE_RANKS.get_or_init(void_and_cluster)
+}
+
+/// Ulichney's void-and-cluster method: rank every pixel of the tile so that the
+/// pixels up to any rank are spread as evenly as they can be
+fn void_and_cluster() -> Vec<f64> {
+    let pixels = TILE * TILE;
+
+    // How much a pixel is crowded by each other pixel, depending only on the
+    // offset between them, with the tile wrapping round
+    let mut filter = vec![0.0; pixels];
+    for dy in 0..TILE {
+        for dx in 0..TILE {
+            let wrap = |d: usize| d.min(TILE - d) as f64;
+            let (x, y) = (wrap(dx), wrap(dy));
+            filter[dy * TILE + dx] = (-(x * x + y * y) / (2.0 * SIGMA * SIGMA)).exp();
+        }
+    }
+
+    let mut crowding = vec![0.0; pixels];
+    let mut chosen = vec![false; pixels];
+    let toggle = |crowding: &mut Vec<f64>, chosen: &mut Vec<bool>, p: usize, on: bool| {
+        chosen[p] = on;
+        let sign = if on { 1.0 } else { -1.0 };
+        let (px, py) = (p % TILE, p / TILE);
+        for (q, c) in crowding.iter_mut().enumerate() {
+            let dx = (q % TILE + TILE - px) % TILE;
+            let dy = (q / TILE + TILE - py) % TILE;
+            *c += sign * filter[dy * TILE + dx];
+        }
+    };
+    // The most crowded chosen pixel, or the emptiest unchosen one
+    let tightest = |crowding: &[f64], chosen: &[bool], want: bool| -> usize {
+        let candidates = (0..pixels).filter(|&p| chosen[p] == want);
+        if want {
+            candidates
+                .max_by(|a, b| crowding[*a].partial_cmp(&crowding[*b]).unwrap())
+                .unwrap()
+        } else {
+            candidates
+                .min_by(|a, b| crowding[*a].partial_cmp(&crowding[*b]).unwrap())
+                .unwrap()
+        }
+    };
+
+    // Start from a random tenth of the pixels, then move the most crowded into
+    // the biggest gap until there's nothing better to do
+    let mut rng = Rng::new(0);
+    let initial = pixels / 10;
+    let mut placed = 0;
+    while placed < initial {
+        let p = (rng.next_u64() % pixels as u64) as usize;
+        if !chosen[p] {
+            toggle(&mut crowding, &mut chosen, p, true);
+            placed += 1;
+        }
+    }
+    loop {
+        let cluster = tightest(&crowding, &chosen, true);
+        toggle(&mut crowding, &mut chosen, cluster, false);
+        let void = tightest(&crowding, &chosen, false);
+        if void == cluster {
+            toggle(&mut crowding, &mut chosen, cluster, true);
+            break;
+        }
+        toggle(&mut crowding, &mut chosen, void, true);
+    }
+
+    let mut rank = vec![0; pixels];
+
+    // Rank the starting pixels by taking away the most crowded first...
+    let (mut start_crowding, mut start_chosen) = (crowding.clone(), chosen.clone());
+    for r in (0..initial).rev() {
+        let cluster = tightest(&start_crowding, &start_chosen, true);
+        toggle(&mut start_crowding, &mut start_chosen, cluster, false);
+        rank[cluster] = r;
+    }
+
+    // ...and the rest by filling the biggest gap each time
+    for r in initial..pixels {
+        let void = tightest(&crowding, &chosen, false);
+        toggle(&mut crowding, &mut chosen, void, true);
+        rank[void] = r;
+    }
+
+    rank.iter()
+        .map(|r| (*r as f64 + 0.5) / pixels as f64)
+        .collect()
+}
+
+impl<T> Sampler<T> for BlueNoise<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> T {
+        let v = (self.lookup(0) + self.index as f64 * R1).fract();
+        self.dimension += 1;
+        to_float(v)
+    }
+
+    fn next_2d(&mut self) -> (T, T) {
+        let u = (self.lookup(0) + self.index as f64 * R2[0]).fract();
+        let v = (self.lookup(1) + self.index as f64 * R2[1]).fract();
+        self.dimension += 1;
+        (to_float(u), to_float(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_rank_once() {
+        let mut ranks: Vec<usize> = tile()
+            .iter()
+            .map(|v| (v * (TILE * TILE) as f64) as usize)
+            .collect();
+        ranks.sort_unstable();
+        assert_eq!((0..TILE * TILE).collect::<Vec<_>>(), ranks);
+    }
+
+    /// How much the averages of 4x4 blocks of pixels vary
+    fn blotchiness(value: impl Fn(u32, u32) -> f64) -> f64 {
+        let blocks: Vec<f64> = (0..8)
+            .flat_map(|by| (0..8).map(move |bx| (bx, by)))
+            .map(|(bx, by)| {
+                let mut total = 0.0;
+                for y in 0..4 {
+                    for x in 0..4 {
+                        total += value(bx * 4 + x, by * 4 + y);
+                    }
+                }
+                total / 16.0
+            })
+            .collect();
+        let mean = blocks.iter().sum::<f64>() / blocks.len() as f64;
+        blocks.iter().map(|b| (b - mean) * (b - mean)).sum::<f64>() / blocks.len() as f64
+    }
+
+    #[test]
+    fn finer_grain_than_white_noise() {
+        let mut s = BlueNoise::new(0);
+        let mut samples: Vec<f64> = vec![];
+        for y in 0..32 {
+            for x in 0..32 {
+                s.start_sample(x, y, 0);
+                samples.push(s.next_1d());
+            }
+        }
+        let blue = blotchiness(|x, y| samples[(y * 32 + x) as usize]);
+
+        // White noise averaged over 16 pixels varies by 1/12/16
+        let white = 1.0 / 12.0 / 16.0;
+        assert!(blue < white / 2.0, "{} vs {}", blue, white);
+    }
+
+    #[test]
+    fn dimensions_and_samples_differ() {
+        let mut s = BlueNoise::new(0);
+        s.start_sample(3, 3, 0);
+        let a: f64 = s.next_1d();
+        let b: f64 = s.next_1d();
+        s.start_sample(3, 3, 1);
+        let c: f64 = s.next_1d();
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+
+        s.start_sample(3, 3, 0);
+        assert_eq!(a, s.next_1d());
+    }
+}
@@ -0,0 +1,143 @@
+use std::marker::PhantomData;
+
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::random::Rng;
+
+/// Enough bases for a few bounces' worth of dimensions; past them the numbers
+/// are plain random
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// The Halton sequence: each dimension counts the samples in a different prime
+/// base and reads the digits backwards after the point. The digits are shuffled
+/// per pixel and dimension, which keeps the spread but stops neighbouring
+/// pixels, and the higher dimensions, from lining up with each other.
+#[derive(Debug, Clone)]
+pub struct Halton<T: Float> {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    precision: PhantomData<T>,
+}
+
+impl<T> Halton<T>
+where
+    T: Float,
+{
+    pub fn new(seed: u64) -> Halton<T> {
+        Halton {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            precision: PhantomData,
+        }
+    }
+
+    fn next(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let seed = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            dimension as u64,
+        ]);
+
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => radical_inverse(self.index, base, seed),
+            None => Rng::new(hash(&[seed, self.index as u64])).next_float(),
+        }
+    }
+}
+
+/// The digits of `index` in `base`, shuffled and mirrored about the point
+fn radical_inverse(mut index: u32, base: u32, seed: u64) -> f64 {
+    let inverse_base = 1.0 / base as f64;
+    let mut scale = inverse_base;
+    let mut result = 0.0;
+
+    // Shuffled zeros aren't zero, so carry on past the last digit of the index
+    // until nothing more would show in the result
+    let mut position = 0;
+    while scale > 1e-17 {
+        let digit = index % base;
+        index /= base;
+        let shuffle = hash(&[seed, position]) as u32;
+        result += permute(digit, base, shuffle) as f64 * scale;
+        scale *= inverse_base;
+        position += 1;
+    }
+    result
+}
+
+impl<T> Sampler<T> for Halton<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> T {
+        to_float(self.next())
+    }
+
+    fn next_2d(&mut self) -> (T, T) {
+        let u = self.next();
+        (to_float(u), to_float(self.next()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spread() {
+        // However the digits are shuffled, every block of base-many samples
+        // has one in each base-th of the dimension
+        let mut s = Halton::new(0);
+        for &(dimension, base) in &[(0, 2), (1, 3), (2, 5)] {
+            for block in 0..3 {
+                let mut seen = vec![0; base];
+                for i in 0..base as u32 {
+                    s.start_sample(7, 1, block * base as u32 + i);
+                    let values: Vec<f64> = (0..=dimension).map(|_| s.next_1d()).collect();
+                    seen[(values[dimension] * base as f64) as usize] += 1;
+                }
+                assert_eq!(vec![1; base], seen);
+            }
+        }
+    }
+
+    #[test]
+    fn pixels_differ() {
+        let mut s = Halton::new(0);
+        s.start_sample(0, 0, 1);
+        let a: (f64, f64) = s.next_2d();
+        s.start_sample(1, 0, 1);
+        let b: (f64, f64) = s.next_2d();
+        assert_ne!(a, b);
+
+        s.start_sample(0, 0, 1);
+        assert_eq!(a, s.next_2d());
+    }
+
+    #[test]
+    fn beyond_the_primes() {
+        let mut s = Halton::new(0);
+        s.start_sample(0, 0, 0);
+        for _ in 0..100 {
+            let v: f64 = s.next_1d();
+            assert!((0.0..1.0).contains(&v));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::marker::PhantomData;
+
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::random::Rng;
+
+/// Plain random numbers, with nothing to spread them out. The baseline the
+/// other samplers are measured against.
+#[derive(Debug, Clone)]
+pub struct Independent<T: Float> {
+    seed: u64,
+    rng: Rng,
+    precision: PhantomData<T>,
+}
+
+impl<T> Independent<T>
+where
+    T: Float,
+{
+    pub fn new(seed: u64) -> Independent<T> {
+        Independent {
+            seed,
+            rng: Rng::new(seed),
+            precision: PhantomData,
+        }
+    }
+}
+
+impl<T> Sampler<T> for Independent<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.rng = Rng::new(hash(&[self.seed, x as u64, y as u64, index as u64]));
+    }
+
+    fn next_1d(&mut self) -> T {
+        self.rng.next_float()
+    }
+
+    fn next_2d(&mut self) -> (T, T) {
+        (self.rng.next_float(), self.rng.next_float())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(s: &mut Independent<f64>, x: u32, y: u32, index: u32) -> Vec<f64> {
+        s.start_sample(x, y, index);
+        (0..4).map(|_| s.next_1d()).collect()
+    }
+
+    #[test]
+    fn repeatable() {
+        let mut a = Independent::new(1);
+        let mut b = Independent::new(1);
+
+        let first = draw(&mut a, 3, 4, 5);
+        // Whatever came before
+        draw(&mut b, 0, 0, 0);
+        assert_eq!(first, draw(&mut b, 3, 4, 5));
+
+        assert_ne!(first, draw(&mut a, 3, 4, 6));
+        assert_ne!(first, draw(&mut Independent::new(2), 3, 4, 5));
+        assert!(first.iter().all(|v| (0.0..1.0).contains(v)));
+    }
+}
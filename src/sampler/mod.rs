@@ -0,0 +1,144 @@
+use num::{Float, FromPrimitive};
+
+pub mod bluenoise;
+pub mod halton;
+pub mod independent;
+pub mod sobol;
+pub mod stratified;
+
+/// A source of the numbers that drive every random choice in a sample: where in
+/// the pixel it goes, when in the exposure, which way light bounces. Each call
+/// moves on to a new dimension, and values in the same dimension are spread out
+/// across a pixel's samples far more evenly than plain random numbers would be.
+///
+/// What comes out depends only on the seed, the pixel, the sample and the
+/// dimension, never on what was drawn before for other pixels, so a render is
+/// the same bit for bit whatever order its pixels are done in, on however many
+/// threads. Give one to a `RenderSession` or `ProgressiveRender` to use it.
+pub trait Sampler<T: Float> {
+    /// Start on sample `index` of the pixel at (`x`, `y`), back at the first
+    /// dimension
+    fn start_sample(&mut self, x: u32, y: u32, index: u32);
+
+    /// The next value, in [0, 1)
+    fn next_1d(&mut self) -> T;
+
+    /// The next pair of values, in [0, 1) each, spread evenly over the square
+    /// together rather than just one at a time
+    fn next_2d(&mut self) -> (T, T);
+}
+
+impl<T> Sampler<T> for Box<dyn Sampler<T>>
+where
+    T: Float,
+{
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.as_mut().start_sample(x, y, index)
+    }
+
+    fn next_1d(&mut self) -> T {
+        self.as_mut().next_1d()
+    }
+
+    fn next_2d(&mut self) -> (T, T) {
+        self.as_mut().next_2d()
+    }
+}
+
+/// Mix a handful of values into one well-scrambled seed, so that neighbouring
+/// pixels, samples and dimensions get unrelated numbers
+pub(crate) fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x243f_6a88_85a3_08d3, |h, v| mix(h ^ mix(*v)))
+}
+
+/// The splitmix64 finaliser
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce5_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A value in [0, 1), making sure rounding to a less precise float doesn't
+/// bring it up to 1
+pub(crate) fn to_float<T>(f: f64) -> T
+where
+    T: Float + FromPrimitive,
+{
+    let f: T = FromPrimitive::from_f64(f).unwrap();
+    f.min(T::one() - T::epsilon())
+}
+
+/// Where `i` lands in a shuffle of 0..`length` picked by `seed`, worked out
+/// without building the whole shuffle (Kensler, "Correlated Multi-Jittered
+/// Sampling", 2013)
+pub(crate) fn permute(mut i: u32, length: u32, seed: u32) -> u32 {
+    let mut w = length - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // Shuffle within the next power of two up, and go again until it lands in
+    // range
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < length {
+            break;
+        }
+    }
+    (i.wrapping_add(seed)) % length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permutations() {
+        for &length in &[1, 2, 5, 16, 100] {
+            for &seed in &[0, 1, 0xdead_beef] {
+                let mut seen: Vec<u32> = (0..length).map(|i| permute(i, length, seed)).collect();
+                seen.sort_unstable();
+                assert_eq!((0..length).collect::<Vec<_>>(), seen);
+            }
+        }
+        assert_ne!(
+            (0..8).map(|i| permute(i, 8, 1)).collect::<Vec<_>>(),
+            (0..8).map(|i| permute(i, 8, 2)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn floats_stay_below_one() {
+        assert_eq!(0.5, to_float::<f64>(0.5));
+        assert!(to_float::<f32>(1.0 - 1e-12) < 1.0);
+    }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(hash(&[1, 2, 3]), hash(&[1, 2, 3]));
+        assert_ne!(hash(&[1, 2, 3]), hash(&[1, 3, 2]));
+        assert_ne!(hash(&[0]), hash(&[0, 0]));
+    }
+}
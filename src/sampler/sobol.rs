@@ -0,0 +1,186 @@
+use std::marker::PhantomData;
+
+use num::{Float, FromPrimitive};
+
+use super::*;
+
+/// The first two dimensions of the Sobol sequence, Owen scrambled, following
+/// Burley's "Practical Hash-based Owen Scrambling" (2020). Every call shuffles
+/// the order of the samples and scrambles the points afresh, so each dimension
+/// or pair of dimensions gets its own well spread set, unrelated to the others
+/// and to neighbouring pixels. Owen scrambling keeps the Sobol points' spread
+/// while making them random, so averages converge quickly but without any
+/// pattern showing.
+#[derive(Debug, Clone)]
+pub struct Sobol<T: Float> {
+    seed: u64,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    precision: PhantomData<T>,
+}
+
+impl<T> Sobol<T>
+where
+    T: Float,
+{
+    pub fn new(seed: u64) -> Sobol<T> {
+        Sobol {
+            seed,
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            precision: PhantomData,
+        }
+    }
+
+    /// The index shuffled for this dimension, and the seed to scramble the
+    /// point with
+    fn next_seed(&mut self) -> (u32, u64) {
+        let seed = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+        ]);
+        self.dimension += 1;
+        (owen_scramble(self.index, seed as u32), seed)
+    }
+}
+
+/// Dimension 0 or 1 of the Sobol sequence, as 32 bits after the point
+fn sobol(index: u32, dimension: u32) -> u32 {
+    if dimension == 0 {
+        // The van der Corput sequence: the bits of the index mirrored
+        return index.reverse_bits();
+    }
+
+    // Direction numbers from the primitive polynomial x + 1
+    let mut m: u64 = 1;
+    let mut result = 0;
+    for bit in 0..32 {
+        if index >> bit & 1 == 1 {
+            result ^= (m << (31 - bit)) as u32;
+        }
+        m ^= m << 1;
+    }
+    result
+}
+
+/// The Laine-Karras hash, which only lets each bit be changed by the bits below
+/// it
+fn laine_karras(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+/// Nested uniform scrambling: each bit is flipped or not depending on all the
+/// bits above it. Done to a point it randomises it while keeping the set spread
+/// out; done to an index it shuffles the order without breaking up the blocks
+/// that are spread out together.
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn to_unit<T>(bits: u32) -> T
+where
+    T: Float + FromPrimitive,
+{
+    to_float(bits as f64 / (1u64 << 32) as f64)
+}
+
+impl<T> Sampler<T> for Sobol<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> T {
+        let (index, seed) = self.next_seed();
+        to_unit(owen_scramble(sobol(index, 0), seed as u32))
+    }
+
+    fn next_2d(&mut self) -> (T, T) {
+        let (index, seed) = self.next_seed();
+        (
+            to_unit(owen_scramble(sobol(index, 0), seed as u32)),
+            to_unit(owen_scramble(sobol(index, 1), (seed >> 32) as u32)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence() {
+        let points: Vec<(u32, u32)> = (0..4).map(|i| (sobol(i, 0), sobol(i, 1))).collect();
+        let half = 1 << 31;
+        let quarter = 1 << 30;
+        assert_eq!(
+            vec![
+                (0, 0),
+                (half, half),
+                (quarter, half + quarter),
+                (half + quarter, quarter)
+            ],
+            points
+        );
+    }
+
+    #[test]
+    fn scrambling_keeps_the_spread() {
+        // Any power of two of samples, shuffled and scrambled, still has one in
+        // each column, each row and each square of a grid that size
+        let mut s = Sobol::new(3);
+        let points: Vec<(f64, f64)> = (0..16)
+            .map(|i| {
+                s.start_sample(4, 2, i);
+                let _: f64 = s.next_1d();
+                s.next_2d()
+            })
+            .collect();
+
+        let count = |cell: &dyn Fn(f64, f64) -> usize| {
+            let mut seen = vec![0; 16];
+            for (u, v) in points.iter() {
+                seen[cell(*u, *v)] += 1;
+            }
+            seen
+        };
+        assert_eq!(vec![1; 16], count(&|u, _| (u * 16.0) as usize));
+        assert_eq!(vec![1; 16], count(&|_, v| (v * 16.0) as usize));
+        assert_eq!(
+            vec![1; 16],
+            count(&|u, v| (v * 4.0) as usize * 4 + (u * 4.0) as usize)
+        );
+        assert_eq!(
+            vec![1; 16],
+            count(&|u, v| (v * 2.0) as usize * 8 + (u * 8.0) as usize)
+        );
+    }
+
+    #[test]
+    fn pixels_differ() {
+        let mut s = Sobol::new(0);
+        s.start_sample(0, 0, 0);
+        let a: (f64, f64) = s.next_2d();
+        s.start_sample(0, 1, 0);
+        let b: (f64, f64) = s.next_2d();
+        assert_ne!(a, b);
+        // The first point isn't stuck at the origin
+        assert_ne!((0.0, 0.0), a);
+
+        s.start_sample(0, 0, 0);
+        assert_eq!(a, s.next_2d());
+    }
+}
@@ -0,0 +1,135 @@
+use std::marker::PhantomData;
+
+use num::{Float, FromPrimitive};
+
+use super::*;
+use crate::random::Rng;
+
+/// Splits each dimension into as many equal strata as there are samples in a
+/// pixel, or a grid of them for pairs, and puts one sample at a random point in
+/// each. Every dimension visits the strata in its own shuffled order, so they
+/// don't line up with each other. Past the samples it was made for, it starts
+/// over with a fresh shuffle.
+#[derive(Debug, Clone)]
+pub struct Stratified<T: Float> {
+    seed: u64,
+    samples_per_pixel: u32,
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    precision: PhantomData<T>,
+}
+
+impl<T> Stratified<T>
+where
+    T: Float,
+{
+    pub fn new(samples_per_pixel: u32, seed: u64) -> Stratified<T> {
+        Stratified {
+            seed,
+            samples_per_pixel: samples_per_pixel.max(1),
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            precision: PhantomData,
+        }
+    }
+
+    /// Which of `strata` this sample falls in, in this dimension, and a random
+    /// source for where within it
+    fn stratum(&mut self, strata: u32) -> (u32, Rng) {
+        let (x, y) = (self.pixel.0 as u64, self.pixel.1 as u64);
+        let round = (self.index / self.samples_per_pixel) as u64;
+        let dimension = self.dimension as u64;
+        self.dimension += 1;
+
+        let shuffle = hash(&[self.seed, x, y, dimension, round]) as u32;
+        let stratum = permute(self.index % self.samples_per_pixel, strata, shuffle);
+        let rng = Rng::new(hash(&[self.seed, x, y, dimension, self.index as u64]));
+        (stratum, rng)
+    }
+}
+
+impl<T> Sampler<T> for Stratified<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = (x, y);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> T {
+        let n = self.samples_per_pixel;
+        let (stratum, mut rng) = self.stratum(n);
+        to_float((stratum as f64 + rng.next_float::<f64>()) / n as f64)
+    }
+
+    fn next_2d(&mut self) -> (T, T) {
+        // A grid at least as big as the number of samples, as near square as it
+        // can be; with a count that isn't square, a few cells go unused
+        let n = self.samples_per_pixel;
+        let columns = (n as f64).sqrt().ceil() as u32;
+        let rows = (n + columns - 1) / columns;
+
+        let (stratum, mut rng) = self.stratum(columns * rows);
+        let (column, row) = (stratum % columns, stratum / columns);
+        (
+            to_float((column as f64 + rng.next_float::<f64>()) / columns as f64),
+            to_float((row as f64 + rng.next_float::<f64>()) / rows as f64),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_per_stratum() {
+        let mut s = Stratified::new(16, 0);
+        let mut seen_1d = vec![0; 16];
+        let mut seen_2d = vec![0; 16];
+
+        for i in 0..16 {
+            s.start_sample(2, 3, i);
+            let v: f64 = s.next_1d();
+            seen_1d[(v * 16.0) as usize] += 1;
+            let (u, v): (f64, f64) = s.next_2d();
+            seen_2d[(v * 4.0) as usize * 4 + (u * 4.0) as usize] += 1;
+        }
+
+        assert_eq!(vec![1; 16], seen_1d);
+        assert_eq!(vec![1; 16], seen_2d);
+    }
+
+    #[test]
+    fn dimensions_differ() {
+        let mut s = Stratified::new(4, 0);
+        let order = |s: &mut Stratified<f64>, dimension: usize| -> Vec<u32> {
+            (0..4)
+                .map(|i| {
+                    s.start_sample(0, 0, i);
+                    let values: Vec<f64> = (0..=dimension).map(|_| s.next_1d()).collect();
+                    (values[dimension] * 4.0) as u32
+                })
+                .collect()
+        };
+        let orders: Vec<_> = (0..4).map(|d| order(&mut s, d)).collect();
+        assert!(orders.iter().any(|o| *o != orders[0]));
+    }
+
+    #[test]
+    fn repeatable() {
+        let mut a = Stratified::new(4, 9);
+        let mut b = Stratified::new(4, 9);
+        a.start_sample(5, 6, 2);
+        b.start_sample(0, 0, 0);
+        let _: f64 = b.next_1d();
+        b.start_sample(5, 6, 2);
+
+        let pa: (f64, f64) = a.next_2d();
+        assert_eq!(pa, b.next_2d());
+    }
+}
@@ -0,0 +1,33 @@
+use num::Float;
+
+use crate::ray::Ray;
+
+/// What lights get to see of the world they're lighting
+pub trait Scene<T: Float> {
+    /// Does anything lie along the ray, within its interval?
+    fn occluded(&self, ray: &Ray<T>) -> bool;
+}
+
+/// Scenes for testing lights in, without having to build a world
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Nothing is in the way of any ray
+    pub(crate) struct Open;
+
+    impl Scene<f64> for Open {
+        fn occluded(&self, _: &Ray<f64>) -> bool {
+            false
+        }
+    }
+
+    /// Something is in the way of every ray
+    pub(crate) struct Enclosed;
+
+    impl Scene<f64> for Enclosed {
+        fn occluded(&self, _: &Ray<f64>) -> bool {
+            true
+        }
+    }
+}
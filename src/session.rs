@@ -0,0 +1,283 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use image::RgbImage;
+use num::{Float, FromPrimitive};
+
+use crate::colour;
+use crate::engine::Engine;
+use crate::error::{Error, Result};
+use crate::sampler::independent::Independent;
+use crate::sampler::Sampler;
+
+/// Asks a render to stop. Clones share the same flag, so one can be handed to
+/// another thread, or a Ctrl-C handler, while the render runs.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// How far a render has got, as reported after each tile
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub tiles_done: u32,
+    pub tiles_total: u32,
+    pub pixels_done: u64,
+    pub pixels_total: u64,
+    /// Camera and shadow rays traced so far in this render
+    pub rays_cast: u64,
+    pub elapsed: Duration,
+    /// Estimated from the rate so far, once there's a rate to go on
+    pub eta: Option<Duration>,
+}
+
+impl Progress {
+    /// How much is done, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        self.pixels_done as f64 / self.pixels_total as f64
+    }
+}
+
+type ProgressCallback<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+/// A render broken into square tiles, with a report after each one and the
+/// chance to stop between them
+pub struct RenderSession<'a, T: Float> {
+    engine: &'a Engine<T>,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    sampler: Box<dyn Sampler<T> + 'a>,
+    cancel: CancelToken,
+    on_progress: Option<ProgressCallback<'a>>,
+}
+
+impl<'a, T> RenderSession<'a, T>
+where
+    T: Float + FromPrimitive + std::fmt::Debug,
+{
+    pub fn new(engine: &'a Engine<T>, width: u32, height: u32) -> RenderSession<'a, T> {
+        RenderSession {
+            engine,
+            width,
+            height,
+            tile_size: 32,
+            sampler: Box::new(Independent::new(0)),
+            cancel: CancelToken::new(),
+            on_progress: None,
+        }
+    }
+
+    /// Width and height of each tile, in pixels
+    pub fn tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
+
+    /// Where the numbers for each sample's random choices come from; plain
+    /// random numbers unless set
+    pub fn sampler(mut self, sampler: Box<dyn Sampler<T> + 'a>) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Checked before each tile; once cancelled, `run` stops with
+    /// `Error::Cancelled`
+    pub fn cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Called after each tile is finished
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&Progress) + 'a,
+    {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    pub fn run(mut self) -> Result<RgbImage> {
+        let film = self.engine.film(self.width, self.height)?;
+        let mut image = RgbImage::new(self.width, self.height);
+
+        let tiles_across = (self.width + self.tile_size - 1) / self.tile_size;
+        let tiles_down = (self.height + self.tile_size - 1) / self.tile_size;
+        let mut progress = Progress {
+            tiles_done: 0,
+            tiles_total: tiles_across * tiles_down,
+            pixels_done: 0,
+            pixels_total: self.width as u64 * self.height as u64,
+            rays_cast: 0,
+            elapsed: Duration::from_secs(0),
+            eta: None,
+        };
+        let start = Instant::now();
+        let rays_before = self.engine.rays_cast();
+
+        for tile_y in 0..tiles_down {
+            for tile_x in 0..tiles_across {
+                if self.cancel.is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+
+                let x0 = tile_x * self.tile_size;
+                let y0 = tile_y * self.tile_size;
+                let x1 = (x0 + self.tile_size).min(self.width);
+                let y1 = (y0 + self.tile_size).min(self.height);
+
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        let colour =
+                            self.engine
+                                .render_pixel(&film, self.sampler.as_mut(), x, y)?;
+                        image.put_pixel(x, y, colour::to_rgb(colour));
+                    }
+                }
+
+                progress.tiles_done += 1;
+                progress.pixels_done += (x1 - x0) as u64 * (y1 - y0) as u64;
+                progress.rays_cast = self.engine.rays_cast() - rays_before;
+                progress.elapsed = start.elapsed();
+                progress.eta = estimate(&progress);
+                if let Some(callback) = self.on_progress.as_mut() {
+                    callback(&progress);
+                }
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+/// Time left, assuming the rest goes at the same rate as what's done so far
+fn estimate(progress: &Progress) -> Option<Duration> {
+    if progress.pixels_done == 0 {
+        return None;
+    }
+    let remaining = progress.pixels_total - progress.pixels_done;
+    Some(
+        progress
+            .elapsed
+            .mul_f64(remaining as f64 / progress.pixels_done as f64),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::arealight::AreaLight;
+    use crate::matrix::Mat4;
+    use crate::object::sphere::Sphere;
+    use crate::sampler::sobol::Sobol;
+    use crate::vector::Vec4;
+
+    fn engine() -> Engine<f64> {
+        let mut engine = Engine::new(Mat4::look(
+            &Vec4::position(0.0, 0.0, -10.0),
+            &Vec4::position(0.0, 0.0, 0.0),
+        ));
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 0.0), 5.0).unwrap(),
+        ));
+        engine.add_light(Box::new(
+            AreaLight::sphere(
+                Vec4::position(-10.0, 10.0, -10.0),
+                1.0,
+                image::Rgb([255, 255, 255]),
+                2,
+            )
+            .unwrap(),
+        ));
+        engine
+    }
+
+    #[test]
+    fn reports_each_tile() {
+        let engine = engine();
+        let mut reports = vec![];
+        RenderSession::new(&engine, 20, 10)
+            .tile_size(8)
+            .on_progress(|p| reports.push(p.clone()))
+            .run()
+            .unwrap();
+
+        // Three tiles across and two down, the last of each cut short
+        assert_eq!(6, reports.len());
+        assert!(reports.iter().all(|p| p.tiles_total == 6));
+        assert_eq!(64, reports[0].pixels_done);
+        assert_eq!(200, reports[5].pixels_done);
+        assert_eq!(1.0, reports[5].fraction());
+        assert_eq!(Some(Duration::from_secs(0)), reports[5].eta);
+
+        // At least one camera ray per pixel, and shadow rays where the sphere is
+        assert!(reports[5].rays_cast > 200);
+        assert!(reports
+            .windows(2)
+            .all(|w| w[0].rays_cast <= w[1].rays_cast && w[0].elapsed <= w[1].elapsed));
+    }
+
+    #[test]
+    fn same_image_whatever_the_tiles() {
+        let engine = engine();
+        let whole = engine.render(17, 13).unwrap();
+        let tiled = RenderSession::new(&engine, 17, 13)
+            .tile_size(4)
+            .run()
+            .unwrap();
+        assert_eq!(whole.into_raw(), tiled.into_raw());
+    }
+
+    #[test]
+    fn sampler_picks_the_samples() {
+        let mut engine = engine();
+        engine.set_samples(4);
+        let render = |sampler: Box<dyn Sampler<f64>>| {
+            RenderSession::new(&engine, 12, 12)
+                .sampler(sampler)
+                .run()
+                .unwrap()
+                .into_raw()
+        };
+
+        let sobol = render(Box::new(Sobol::new(1)));
+        assert_eq!(sobol, render(Box::new(Sobol::new(1))));
+        // Different points in the pixels along the sphere's edge
+        assert_ne!(sobol, render(Box::new(Independent::new(1))));
+    }
+
+    #[test]
+    fn cancel() {
+        let engine = engine();
+        let token = CancelToken::new();
+        let canceller = token.clone();
+        let mut tiles = 0;
+
+        let result = RenderSession::new(&engine, 16, 16)
+            .tile_size(4)
+            .cancel_token(token)
+            .on_progress(|_| {
+                tiles += 1;
+                canceller.cancel();
+            })
+            .run();
+
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(1, tiles);
+    }
+}
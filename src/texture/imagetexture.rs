@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use image::RgbImage;
+use num::{Float, FromPrimitive};
+
+use crate::colour;
+use crate::error::{Error, Result};
+use crate::vector::Vec4;
+
+use super::Texture;
+
+/// What happens to texture coordinates outside [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Tile the image
+    Repeat,
+    /// Tile the image, flipping every other copy so the edges always meet
+    Mirror,
+    /// Stretch the edge pixels out forever
+    Clamp,
+}
+
+impl WrapMode {
+    fn apply(self, i: i64, size: u32) -> u32 {
+        let size = size as i64;
+        let wrapped = match self {
+            WrapMode::Repeat => i.rem_euclid(size),
+            WrapMode::Mirror => {
+                let period = i.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+            WrapMode::Clamp => i.max(0).min(size - 1),
+        };
+        wrapped as u32
+    }
+}
+
+/// A texture looked up from an image by the surface's UV coordinates. u runs left
+/// to right across the image and v top to bottom; lookups are bilinearly filtered.
+pub struct ImageTexture {
+    image: RgbImage,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    /// Fails if the image has no pixels to look up
+    pub fn new(image: RgbImage, wrap: WrapMode) -> Result<ImageTexture> {
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::InvalidImage(format!(
+                "can't texture with a {}x{} image",
+                image.width(),
+                image.height()
+            )));
+        }
+        Ok(ImageTexture { image, wrap })
+    }
+
+    /// Load a PNG, JPEG or anything else the `image` crate understands
+    pub fn load<P: AsRef<Path>>(path: P, wrap: WrapMode) -> Result<ImageTexture> {
+        ImageTexture::new(image::open(path)?.to_rgb8(), wrap)
+    }
+
+    fn texel<T>(&self, x: i64, y: i64) -> [T; 3]
+    where
+        T: Float + FromPrimitive,
+    {
+        let x = self.wrap.apply(x, self.image.width());
+        let y = self.wrap.apply(y, self.image.height());
+        colour::from_rgb(*self.image.get_pixel(x, y))
+    }
+}
+
+impl<T> Texture<T> for ImageTexture
+where
+    T: Float + FromPrimitive,
+{
+    fn colour(&self, _: &Vec4<T>, uv: (T, T)) -> [T; 3] {
+        let (u, v) = uv;
+        let half: T = FromPrimitive::from_f64(0.5).unwrap();
+        let width: T = FromPrimitive::from_u32(self.image.width()).unwrap();
+        let height: T = FromPrimitive::from_u32(self.image.height()).unwrap();
+
+        // Pixel centres sit at half-integer coordinates
+        let x = u * width - half;
+        let y = v * height - half;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+        let (x0, y0) = (x0.to_i64().unwrap_or(0), y0.to_i64().unwrap_or(0));
+
+        let c00: [T; 3] = self.texel(x0, y0);
+        let c10: [T; 3] = self.texel(x0 + 1, y0);
+        let c01: [T; 3] = self.texel(x0, y0 + 1);
+        let c11: [T; 3] = self.texel(x0 + 1, y0 + 1);
+
+        let mut result = [T::zero(); 3];
+        for (i, channel) in result.iter_mut().enumerate() {
+            let top = c00[i] + (c10[i] - c00[i]) * fx;
+            let bottom = c01[i] + (c11[i] - c01[i]) * fx;
+            *channel = top + (bottom - top) * fy;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    /// Black on the left, white on the right
+    fn two_pixels(wrap: WrapMode) -> ImageTexture {
+        let mut image = RgbImage::new(2, 1);
+        image.put_pixel(0, 0, Rgb([0, 0, 0]));
+        image.put_pixel(1, 0, Rgb([255, 255, 255]));
+        ImageTexture::new(image, wrap).unwrap()
+    }
+
+    fn at(texture: &ImageTexture, u: f64, v: f64) -> f64 {
+        let origin = Vec4::position(0.0, 0.0, 0.0);
+        texture.colour(&origin, (u, v))[0]
+    }
+
+    #[test]
+    fn pixel_centres() {
+        let t = two_pixels(WrapMode::Clamp);
+        assert_eq!(0.0, at(&t, 0.25, 0.5));
+        assert_eq!(1.0, at(&t, 0.75, 0.5));
+    }
+
+    #[test]
+    fn bilinear() {
+        let t = two_pixels(WrapMode::Clamp);
+        assert!((at(&t, 0.5, 0.5) - 0.5).abs() < 1e-12);
+        assert!((at(&t, 0.375, 0.5) - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn clamp() {
+        let t = two_pixels(WrapMode::Clamp);
+        assert_eq!(0.0, at(&t, -3.0, 0.5));
+        assert_eq!(1.0, at(&t, 3.0, 0.5));
+    }
+
+    #[test]
+    fn repeat() {
+        let t = two_pixels(WrapMode::Repeat);
+        assert_eq!(0.0, at(&t, 1.25, 0.5));
+        assert_eq!(1.0, at(&t, -0.25, 0.5));
+        // Between the last pixel and the first pixel of the next repeat
+        assert!((at(&t, 1.0, 0.5) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn mirror() {
+        let t = two_pixels(WrapMode::Mirror);
+        assert_eq!(1.0, at(&t, 1.25, 0.5));
+        assert_eq!(0.0, at(&t, 1.75, 0.5));
+        assert_eq!(1.0, at(&t, 1.0, 0.5));
+    }
+
+    #[test]
+    fn empty_image() {
+        assert!(matches!(
+            ImageTexture::new(RgbImage::new(0, 0), WrapMode::Repeat),
+            Err(Error::InvalidImage(_))
+        ));
+    }
+
+    #[test]
+    fn wrap_indices() {
+        assert_eq!(1, WrapMode::Repeat.apply(-1, 2));
+        assert_eq!(0, WrapMode::Mirror.apply(-1, 2));
+        assert_eq!(1, WrapMode::Mirror.apply(2, 2));
+        assert_eq!(0, WrapMode::Clamp.apply(-5, 2));
+    }
+}
@@ -0,0 +1,49 @@
+use image::Rgb;
+use num::{Float, FromPrimitive};
+
+use crate::colour;
+use crate::vector::Vec4;
+
+pub mod imagetexture;
+pub mod perlin;
+pub mod procedural;
+
+/// Something that varies a material's colour over a surface. Textures can work
+/// from the position of the point in the world, or from its surface coordinates.
+pub trait Texture<T: Float> {
+    fn colour(&self, point: &Vec4<T>, uv: (T, T)) -> [T; 3];
+}
+
+impl<T> Texture<T> for Box<dyn Texture<T>>
+where
+    T: Float,
+{
+    fn colour(&self, point: &Vec4<T>, uv: (T, T)) -> [T; 3] {
+        self.as_ref().colour(point, uv)
+    }
+}
+
+/// The same colour everywhere
+pub struct Constant<T: Float> {
+    colour: [T; 3],
+}
+
+impl<T> Constant<T>
+where
+    T: Float + FromPrimitive,
+{
+    pub fn new(colour: Rgb<u8>) -> Constant<T> {
+        Constant {
+            colour: colour::from_rgb(colour),
+        }
+    }
+}
+
+impl<T> Texture<T> for Constant<T>
+where
+    T: Float,
+{
+    fn colour(&self, _: &Vec4<T>, _: (T, T)) -> [T; 3] {
+        self.colour
+    }
+}
@@ -0,0 +1,173 @@
+use image::Rgb;
+use num::{Float, FromPrimitive};
+
+use crate::colour;
+use crate::error::Result;
+use crate::matrix::Mat4;
+use crate::vector::Vec4;
+
+use super::perlin;
+use super::Texture;
+
+/// The patterns a `Procedural` texture can make. All are laid out in texture space
+/// with a feature size of about one unit; use the texture's transform to move,
+/// scale and rotate them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Alternating unit cubes
+    Checker,
+    /// Unit-wide bands along x
+    Stripes,
+    /// Linear blend along x, from 0 to 1
+    Gradient,
+    /// Perlin noise
+    Noise,
+    /// Stripes along x, disturbed by turbulence
+    Marble,
+    /// Concentric rings around the y axis, disturbed by noise
+    Wood,
+}
+
+impl Pattern {
+    /// How far between the two colours the point is, in [0, 1]
+    fn evaluate(self, x: f64, y: f64, z: f64) -> f64 {
+        match self {
+            Pattern::Checker => {
+                let sum = x.floor() + y.floor() + z.floor();
+                if sum.rem_euclid(2.0) < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+            Pattern::Stripes => {
+                if x.floor().rem_euclid(2.0) < 1.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+            Pattern::Gradient => x.clamp(0.0, 1.0),
+            Pattern::Noise => (0.5 * (perlin::noise(x, y, z) + 1.0)).clamp(0.0, 1.0),
+            Pattern::Marble => {
+                let turbulence = perlin::turbulence(x, y, z, 6);
+                0.5 * (1.0 + (std::f64::consts::PI * (x + 4.0 * turbulence)).sin())
+            }
+            Pattern::Wood => {
+                let r = (x * x + z * z).sqrt() + 0.25 * perlin::noise(x, y, z);
+                r - r.floor()
+            }
+        }
+    }
+}
+
+/// A pattern blending between two colours, computed from the hit point rather than
+/// looked up in an image
+pub struct Procedural<T: Float> {
+    pattern: Pattern,
+    a: [T; 3],
+    b: [T; 3],
+    /// World space to texture space
+    transform_inverse: Mat4<T>,
+}
+
+impl<T> Procedural<T>
+where
+    T: Float + FromPrimitive,
+{
+    /// `transform` places texture space in the world, in the same way as an object
+    /// matrix does for an object
+    pub fn new(
+        pattern: Pattern,
+        a: Rgb<u8>,
+        b: Rgb<u8>,
+        transform: Mat4<T>,
+    ) -> Result<Procedural<T>> {
+        Ok(Procedural {
+            pattern,
+            a: colour::from_rgb(a),
+            b: colour::from_rgb(b),
+            transform_inverse: transform.inverse()?,
+        })
+    }
+}
+
+impl<T> Texture<T> for Procedural<T>
+where
+    T: Float + FromPrimitive,
+{
+    fn colour(&self, point: &Vec4<T>, _: (T, T)) -> [T; 3] {
+        let p = &self.transform_inverse * point;
+        let t = self.pattern.evaluate(
+            p.x.to_f64().unwrap(),
+            p.y.to_f64().unwrap(),
+            p.z.to_f64().unwrap(),
+        );
+        let t: T = FromPrimitive::from_f64(t).unwrap();
+
+        [
+            self.a[0] + (self.b[0] - self.a[0]) * t,
+            self.a[1] + (self.b[1] - self.a[1]) * t,
+            self.a[2] + (self.b[2] - self.a[2]) * t,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+    const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+
+    fn at(texture: &Procedural<f64>, x: f64, y: f64, z: f64) -> f64 {
+        texture.colour(&Vec4::position(x, y, z), (0.0, 0.0))[0]
+    }
+
+    #[test]
+    fn checker() {
+        let t = Procedural::new(Pattern::Checker, BLACK, WHITE, Mat4::i()).unwrap();
+        assert_eq!(0.0, at(&t, 0.5, 0.5, 0.5));
+        assert_eq!(1.0, at(&t, 1.5, 0.5, 0.5));
+        assert_eq!(1.0, at(&t, 0.5, -0.5, 0.5));
+        assert_eq!(0.0, at(&t, 1.5, 1.5, 0.5));
+    }
+
+    #[test]
+    fn stripes() {
+        let t = Procedural::new(Pattern::Stripes, BLACK, WHITE, Mat4::i()).unwrap();
+        assert_eq!(0.0, at(&t, 0.5, 0.0, 0.0));
+        assert_eq!(1.0, at(&t, 1.5, 0.0, 0.0));
+        assert_eq!(1.0, at(&t, -0.5, 0.0, 0.0));
+        assert_eq!(0.0, at(&t, 0.5, 100.0, -100.0));
+    }
+
+    #[test]
+    fn gradient() {
+        let t = Procedural::new(Pattern::Gradient, BLACK, WHITE, Mat4::i()).unwrap();
+        assert_eq!(0.0, at(&t, -1.0, 0.0, 0.0));
+        assert_eq!(0.25, at(&t, 0.25, 0.0, 0.0));
+        assert_eq!(1.0, at(&t, 2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn transformed() {
+        // Stripes two units wide
+        let scale = Mat4::scale(&Vec4::direction(2.0, 2.0, 2.0));
+        let t = Procedural::new(Pattern::Stripes, BLACK, WHITE, scale).unwrap();
+        assert_eq!(0.0, at(&t, 1.5, 0.0, 0.0));
+        assert_eq!(1.0, at(&t, 2.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn noisy_patterns_in_range() {
+        for pattern in [Pattern::Noise, Pattern::Marble, Pattern::Wood].iter() {
+            let t = Procedural::new(*pattern, BLACK, WHITE, Mat4::i()).unwrap();
+            for i in 0..200 {
+                let f = i as f64 * 0.173;
+                let c = at(&t, f, f * 0.5, -f);
+                assert!((0.0..=1.0).contains(&c), "{:?} gave {}", pattern, c);
+            }
+        }
+    }
+}
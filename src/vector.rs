@@ -69,6 +69,22 @@ where
             w: self.w,
         }
     }
+
+    /// Two unit directions that, together with this (normalised) vector, form an
+    /// orthonormal basis
+    pub fn basis(&self) -> (Vec4<T>, Vec4<T>) {
+        // Cross with whichever axis is least parallel to avoid a degenerate result
+        let axis = if self.x.abs() > self.y.abs() {
+            Vec4::direction(T::zero(), T::one(), T::zero())
+        } else {
+            Vec4::direction(T::one(), T::zero(), T::zero())
+        };
+
+        let tangent = axis.cross_product(self).normalized();
+        let bitangent = self.cross_product(&tangent).normalized();
+
+        (tangent, bitangent)
+    }
 }
 
 /*
@@ -250,6 +266,17 @@ mod tests {
         assert_eq!(Vec4::direction(0.0, 0.0, 1.0), w);
     }
 
+    #[test]
+    fn basis() {
+        let n = Vec4::direction(1.0, 2.0, 3.0).normalized();
+        let (t, b) = n.basis();
+        assert!(n.dot_product(&t).abs() < 1e-12);
+        assert!(n.dot_product(&b).abs() < 1e-12);
+        assert!(t.dot_product(&b).abs() < 1e-12);
+        assert!((t.mag() - 1.0).abs() < 1e-12);
+        assert!((b.mag() - 1.0).abs() < 1e-12);
+    }
+
     #[test]
     fn index_ro() {
         let u = Vec4::position(1.0, 2.0, 3.0);
@@ -0,0 +1,30 @@
+use tracer_rs::{Mat4, Ray, Vec4};
+
+fn close(a: &Vec4<f64>, b: &Vec4<f64>) -> bool {
+    (a - b).mag() < 1e-9 && (a.w - b.w).abs() < 1e-9
+}
+
+#[test]
+fn transforms_round_trip() {
+    let m = &(&Mat4::scale(&Vec4::direction(2.0, 3.0, 4.0))
+        * &Mat4::rotation(&Vec4::direction(1.0, 1.0, 0.0), 0.7))
+        * &Mat4::translation(&Vec4::direction(1.0, -2.0, 5.0));
+    let inverse = m.inverse().unwrap();
+
+    let p = Vec4::position(0.5, -1.5, 2.0);
+    let back = &inverse * &(&m * &p);
+    assert!(close(&p, &back), "{:?}", back);
+}
+
+#[test]
+fn rays() {
+    let ray = Ray::segment(
+        Vec4::position(0.0, 0.0, 0.0),
+        Vec4::direction(0.0, 1.0, 0.0),
+        1.0,
+        2.0,
+    );
+    assert!(close(&Vec4::position(0.0, 1.5, 0.0), &ray.at(1.5)));
+    assert!(ray.contains(1.5));
+    assert!(!ray.contains(2.5));
+}
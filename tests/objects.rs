@@ -0,0 +1,60 @@
+use std::rc::Rc;
+
+use tracer_rs::object::csg::Csg;
+use tracer_rs::object::group::Group;
+use tracer_rs::object::instance::Instance;
+use tracer_rs::object::sphere::Sphere;
+use tracer_rs::object::torus::Torus;
+use tracer_rs::object::{IntersectResult, Intersectable};
+use tracer_rs::{Mat4, Ray, Vec4};
+
+fn towards_z(x: f64) -> Ray<f64> {
+    Ray::new(
+        Vec4::position(x, 0.0, -10.0),
+        Vec4::direction(0.0, 0.0, 1.0),
+    )
+}
+
+fn nearest(object: &dyn Intersectable<f64>, ray: &Ray<f64>) -> Option<f64> {
+    match object.intersect(ray) {
+        IntersectResult::Intersect(hit) => Some(hit.t),
+        IntersectResult::NoIntersect => None,
+    }
+}
+
+#[test]
+fn primitives() {
+    let sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap();
+    assert_eq!(Some(9.0), nearest(&sphere, &towards_z(0.0)));
+    assert_eq!(None, nearest(&sphere, &towards_z(2.0)));
+
+    let torus = Torus::new(Vec4::position(0.0, 0.0, 0.0), 2.0, 0.5).unwrap();
+    let t = nearest(&torus, &towards_z(0.0)).unwrap();
+    assert!((t - 7.5).abs() < 1e-9);
+    assert_eq!(4, torus.crossings(&towards_z(0.0)).len());
+}
+
+#[test]
+fn composition() {
+    // A sphere with a bite out of it, placed twice through a shared group
+    let bitten = Csg::difference(
+        Box::new(Sphere::new(Vec4::position(0.0, 0.0, 0.0), 1.0).unwrap()),
+        Box::new(Sphere::new(Vec4::position(0.0, 0.0, -1.0), 0.5).unwrap()),
+    )
+    .unwrap();
+    let mut group = Group::new(Mat4::i()).unwrap();
+    group.add_object(Box::new(bitten));
+    let shared: Rc<dyn Intersectable<f64>> = Rc::new(group);
+
+    let left = Instance::new(
+        shared.clone(),
+        Mat4::translation(&Vec4::direction(-5.0, 0.0, 0.0)),
+    )
+    .unwrap();
+    let right = Instance::new(shared, Mat4::translation(&Vec4::direction(5.0, 0.0, 0.0))).unwrap();
+
+    // Straight into the bite, which goes half a unit in
+    assert_eq!(Some(9.5), nearest(&left, &towards_z(-5.0)));
+    assert_eq!(Some(9.5), nearest(&right, &towards_z(5.0)));
+    assert_eq!(None, nearest(&left, &towards_z(5.0)));
+}
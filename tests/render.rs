@@ -0,0 +1,59 @@
+use image::Rgb;
+use tracer_rs::background::Background;
+use tracer_rs::light::{ambientlight::AmbientLight, pointlight::PointLight};
+use tracer_rs::material::Material;
+use tracer_rs::object::plane::Plane;
+use tracer_rs::object::sphere::Sphere;
+use tracer_rs::{Engine, Mat4, Vec4};
+
+fn camera() -> Mat4<f64> {
+    Mat4::look(
+        &Vec4::position(0.0, 0.0, -10.0),
+        &Vec4::position(0.0, 0.0, 0.0),
+    )
+}
+
+#[test]
+fn renders_a_lit_sphere() {
+    let mut sphere = Sphere::new(Vec4::position(0.0, 0.0, 0.0), 4.0).unwrap();
+    sphere.set_material(Material::colour(Rgb([255, 0, 0])));
+
+    let mut engine = Engine::new(camera());
+    engine.add_object(Box::new(sphere));
+    engine.set_background(Background::solid(Rgb([0, 0, 255])));
+    engine.add_light(Box::new(AmbientLight::new(Rgb([40, 40, 40]))));
+    engine.add_light(Box::new(PointLight::new(Vec4::position(0.0, 0.0, -20.0))));
+
+    let image = engine.render(32, 24).unwrap();
+    assert_eq!((32, 24), image.dimensions());
+
+    // Red, and brightest where it faces the light head on
+    let middle = image.get_pixel(16, 12);
+    assert_eq!(0, middle[2]);
+    assert!(middle[0] > 200, "{:?}", middle);
+    assert!(middle[0] > image.get_pixel(16, 18)[0]);
+
+    assert_eq!(&Rgb([0, 0, 255]), image.get_pixel(0, 0));
+}
+
+#[test]
+fn renders_are_repeatable() {
+    let build = || {
+        let mut engine = Engine::new(camera());
+        engine.add_object(Box::new(
+            Sphere::new(Vec4::position(0.0, 0.0, 0.0), 3.0).unwrap(),
+        ));
+        engine.add_object(Box::new(
+            Plane::new(
+                Vec4::position(0.0, -3.0, 0.0),
+                Vec4::direction(0.0, 1.0, 0.0),
+            )
+            .unwrap(),
+        ));
+        engine.add_light(Box::new(PointLight::new(Vec4::position(5.0, 10.0, -5.0))));
+        engine.set_samples(4);
+        engine.render(16, 16).unwrap()
+    };
+
+    assert_eq!(build().into_raw(), build().into_raw());
+}